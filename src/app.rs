@@ -1,11 +1,17 @@
 use std::{
-    io,
-    time::{Duration, Instant},
-    sync::mpsc::Receiver
+    fs,
+    io::Write,
+    path::PathBuf,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+    sync::{mpsc::Receiver, Arc, Mutex, MutexGuard},
 };
+use serde::{Deserialize, Serialize};
 use tui::{
     backend::{CrosstermBackend},
-    Terminal
+    layout::Rect,
+    Terminal,
+    TerminalOptions,
+    Viewport,
 };
 use crossterm::{
     event::{DisableMouseCapture, EnableMouseCapture},
@@ -18,15 +24,51 @@ use crate::{
     ui::Screen,
     input_listener::{InputEvent, InputListener},
     board::Board,
+    bot::{self, Bot},
+    broadcast::{Broadcast, Watch, WatchEvent},
+    coop::{Coop, CoopEvent, Snapshot},
+    flags::{FlagsGame, FlagsMove},
+    flags_bot::{self, FlagsBot},
+    daily,
+    interop::{self, Replay, ReplayAction, ReplayEvent},
+    layout::{self, Layout, Mask},
+    neighborhood::Neighborhood,
+    race::{self, PlayerState, Progress, Race},
+    scoreboard::{Scoreboard, ScoreRecord, DailyRecord},
+    solver::Solver,
+    stats::GameStats,
+    topology::Grid,
+    world::{self, World},
 };
+#[cfg(feature = "leaderboard")]
+use crate::leaderboard::Leaderboard;
 
-#[derive(Hash, Eq, PartialEq, Clone, Copy)]
+#[derive(Hash, Eq, PartialEq, Clone, Copy, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Difficulty {
     Easy,
     Medium,
     Hard,
 }
 
+impl Difficulty {
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            Difficulty::Easy => "easy",
+            Difficulty::Medium => "medium",
+            Difficulty::Hard => "hard",
+        }
+    }
+}
+
+fn get_default_time_limit(difficulty: Difficulty) -> Duration {
+    match difficulty {
+        Difficulty::Easy => Duration::from_secs(60),
+        Difficulty::Medium => Duration::from_secs(240),
+        Difficulty::Hard => Duration::from_secs(600),
+    }
+}
+
 pub struct App{
     board: Board,
     start_time: Instant,
@@ -38,12 +80,79 @@ pub struct App{
     change_difficulty: bool,
     difficulty: Difficulty,
     debug: bool,
+    stats: GameStats,
+    hint_penalty: Duration,
+    status: String,
+    show_probabilities: bool,
+    cell_size: u16,
+    bot: Option<Bot>,
+    bot_delay: Duration,
+    // Mode name of a game loaded from a layout file
+    layout_mode: Option<String>,
+    replay_events: Vec<ReplayEvent>,
+    // Replay being played back and the number of its events already played
+    playback: Option<(Replay, usize)>,
+    // Shared with the other sessions when the game is served over SSH
+    scoreboard: Arc<Mutex<Scoreboard>>,
+    scoreboard_path: Option<PathBuf>,
+    show_scoreboard: bool,
+    // Team leaderboard the ranked wins are sent to
+    #[cfg(feature = "leaderboard")]
+    leaderboard: Option<Leaderboard>,
+    daily_mode: bool,
+    // Date of the daily challenge being played
+    daily_date: Option<String>,
+    is_daily_retry: bool,
+    time_attack: bool,
+    time_limit_override: Option<Duration>,
+    opening_bonus: Duration,
+    // Time on the clock of the current time attack game, including bonuses
+    time_limit: Duration,
+    max_lives: u32,
+    lives: u32,
+    endless: bool,
+    // The endless board, replaces the normal board in endless mode
+    world: Option<World>,
+    world_path: Option<PathBuf>,
+    // Head-to-head race against a player in another terminal
+    race: Option<Race>,
+    // Game shared with other players, each with their own cursor
+    coop: Option<Coop>,
+    is_flags_mode: bool,
+    // Scores and turns of the current Minesweeper Flags game
+    flags: Option<FlagsGame>,
+    flags_bot: Option<FlagsBot>,
+    is_split_screen: bool,
+    // Viewers following this game from other terminals
+    broadcast: Option<Broadcast>,
+    // Game of another player followed read-only
+    watch: Option<Watch>,
+    // The second board of a split screen race, played with its own keys
+    player_two: Option<Box<App>>,
+    // Player that won the split screen race, counted from 0
+    split_screen_winner: Option<usize>,
 }
 
 impl App {
     pub fn new(args: Args) -> Self {
+        let player_two = if args.split_screen {
+            let mut player_two = App::new(Args { split_screen: false, ..args.clone() });
+            player_two.is_split_screen = true;
+            Some(Box::new(player_two))
+        }
+        else {
+            None
+        };
+
+        let mut board = Board::new();
+        board.set_max_mines_per_cell(args.max_mines_per_cell);
+        board.set_neighborhood(args.neighborhood);
+        board.set_grid(args.grid);
+        board.set_wrap(args.wrap);
+        board.set_depth(args.depth as usize);
+
         Self {
-            board: Board::new(),
+            board,
             start_time: Instant::now(),
             end_time: Instant::now(),
             game_over: false,
@@ -53,34 +162,112 @@ impl App {
             change_difficulty: true,
             difficulty: Difficulty::Easy,
             debug: args.debug,
+            stats: GameStats::new(),
+            hint_penalty: Duration::from_secs(args.hint_penalty),
+            status: String::new(),
+            show_probabilities: false,
+            cell_size: args.cell_size,
+            bot: if args.autoplay { Some(Bot::new()) } else { None },
+            bot_delay: Duration::from_millis(args.autoplay_delay),
+            layout_mode: None,
+            replay_events: Vec::new(),
+            playback: None,
+            scoreboard: Arc::new(Mutex::new(Scoreboard::new())),
+            scoreboard_path: args.scoreboard.or_else(Scoreboard::get_default_path),
+            show_scoreboard: false,
+            #[cfg(feature = "leaderboard")]
+            leaderboard: args.leaderboard.as_deref().map(|url| {
+                let name = args.leaderboard_name.clone()
+                    .or_else(|| std::env::var("USER").ok())
+                    .unwrap_or_else(|| "anonymous".to_string());
+                Leaderboard::new(url, name, Leaderboard::get_default_queue_path())
+            }),
+            daily_mode: args.daily,
+            daily_date: None,
+            is_daily_retry: false,
+            time_attack: args.time_attack,
+            time_limit_override: args.time_limit.map(Duration::from_secs),
+            opening_bonus: Duration::from_secs(args.opening_bonus),
+            time_limit: Duration::ZERO,
+            max_lives: args.lives.max(1),
+            lives: args.lives.max(1),
+            endless: args.endless,
+            world: None,
+            world_path: args.world,
+            race: None,
+            coop: None,
+            is_flags_mode: args.flags,
+            flags: None,
+            flags_bot: args.flags_ai.map(|strength| FlagsBot::new(strength, Duration::from_millis(args.flags_ai_delay))),
+            is_split_screen: args.split_screen,
+            broadcast: None,
+            watch: None,
+            player_two,
+            split_screen_winner: None,
         }
     }
 
-    pub fn run(&mut self, rx: &Receiver<InputEvent>) -> Result<(), Box<dyn std::error::Error>>{
-        // Init stuff for rendering
-        let mut stdout = io::stdout();
-        execute!(stdout, EnterAlternateScreen, EnableMouseCapture).expect("stdout expect");
-        let backend = CrosstermBackend::new(stdout);
-        let mut terminal = Terminal::new(backend).expect("terminal expect");
+    // The game can be drawn on any output, not only the terminal it was started in. Without a size
+    // the output is taken to be the local terminal and asked for its size.
+    pub fn run<W: Write>(&mut self, rx: &Receiver<InputEvent>, mut output: W, size: Option<Rect>) -> Result<(), Box<dyn std::error::Error>>{
+        // Only games played in the ui end up on the scoreboard
+        if let Some(path) = self.scoreboard_path.clone() {
+            self.scoreboard = Arc::new(Mutex::new(Scoreboard::load(path)));
+        }
+        #[cfg(feature = "leaderboard")]
+        if let Some(leaderboard) = self.leaderboard.as_mut() {
+            leaderboard.sync();
+        }
 
-        let screen: Screen = Screen::new();
-        let input_listener: InputListener = InputListener::new(rx);
+        // Init stuff for rendering, a terminal without a known size is asked for it
+        execute!(output, EnterAlternateScreen, EnableMouseCapture).expect("output expect");
+        let backend = CrosstermBackend::new(output);
+        let mut terminal = match size {
+            Some(size) => Terminal::with_options(backend, TerminalOptions { viewport: Viewport::fixed(size) }),
+            None => Terminal::new(backend),
+        }.expect("terminal expect");
 
-        let mut game_duration: Duration = Instant::now() - self.start_time;
+        let screen: Screen = Screen::new(self.cell_size);
+        let input_timeout = if self.playback.is_some() {
+            Duration::from_millis(50)
+        }
+        else if self.bot.is_some() {
+            self.bot_delay
+        }
+        else {
+            Duration::from_millis(300)
+        };
+        let input_listener: InputListener = InputListener::new(rx, input_timeout, self.player_two.is_some());
+        let mut last_bot_move: Instant = Instant::now();
 
         // Game loop
         while !self.quit {
-            if !self.game_over {
-                game_duration = Instant::now() - self.start_time;
-            }
+            self.check_time_limit();
+            self.update_race();
+            self.update_coop();
+            self.update_split_screen();
+            self.update_broadcast();
+            self.update_watch();
+            #[cfg(feature = "leaderboard")]
+            self.update_leaderboard();
 
             screen.draw_ui(&mut terminal,
                            self,
-                           &self.board,
-                           game_duration,
+                           self.get_board(),
+                           self.get_game_duration(),
                            self.debug).expect("Failed to draw ui");
 
-            self.handle_input(&input_listener);
+            match input_listener.handle_input() {
+                InputEvent::Resize(width, height) => terminal.resize(Rect::new(0, 0, width, height))?,
+                event => self.handle_event(event),
+            }
+
+            if last_bot_move.elapsed() >= self.bot_delay {
+                last_bot_move = Instant::now();
+                self.play_bot_move();
+            }
+            self.play_replay_events();
+            self.play_flags_bot_move();
         }
 
         execute!(
@@ -92,49 +279,787 @@ impl App {
         Ok(())
     }
 
-    fn handle_input(&mut self, input_listener: &InputListener) {
-        match input_listener.handle_input() {
-            InputEvent::Navigation(direction) => {
-                if !self.game_over {
-                    self.board.change_active_cell(InputEvent::Navigation(direction))
+    fn play_bot_move(&mut self) {
+        let is_playing = self.is_playing();
+        let event = match self.bot.as_mut() {
+            Some(bot) => bot.next_event(&self.board, is_playing),
+            None => None,
+        };
+
+        match event {
+            Some(event) => self.handle_event(event),
+            None if is_playing => self.status = "The bot has no cell left to play, take a flag off to let it go on".to_string(),
+            None => { },
+        }
+    }
+
+    // Send the replay events that are due as the input a player would have given
+    fn play_replay_events(&mut self) {
+        let Some((replay, position)) = self.playback.as_mut() else {
+            return;
+        };
+
+        let elapsed = self.start_time.elapsed();
+        let mut events = vec![];
+        while let Some(replay_event) = replay.get_events().get(*position) {
+            if replay_event.get_time() > elapsed {
+                break;
+            }
+            events.push(replay_event.clone());
+            *position += 1;
+        }
+
+        for replay_event in events {
+            let route = bot::get_route(&self.board, self.board.get_selected_cell_index(), replay_event.get_index());
+            for event in route {
+                self.handle_event(event);
+            }
+            self.handle_event(match replay_event.get_action() {
+                ReplayAction::Open => InputEvent::Select,
+                ReplayAction::Flag => InputEvent::Flag,
+                ReplayAction::Chord => InputEvent::Chord,
+            });
+        }
+    }
+
+    pub fn handle_event(&mut self, event: InputEvent) {
+        if self.world.is_some() {
+            self.handle_world_event(event);
+            return;
+        }
+        if self.is_flags_mode {
+            self.handle_flags_event(event);
+            return;
+        }
+        if self.watch.is_some() {
+            // Someone else plays the watched game
+            match event {
+                InputEvent::Scoreboard => self.show_scoreboard = !self.show_scoreboard,
+                InputEvent::Quit => self.quit = true,
+                _ => { },
+            }
+            return;
+        }
+        if let InputEvent::Player(player, event) = event {
+            match self.player_two.as_mut() {
+                Some(player_two) if player == 1 => player_two.handle_event(*event),
+                _ => self.handle_event(*event),
+            }
+            self.update_split_screen();
+            return;
+        }
+
+        match event {
+            InputEvent::Navigation(direction) if !self.game_over => {
+                self.board.change_active_cell(InputEvent::Navigation(direction));
+                if let Some(coop) = self.coop.as_mut() {
+                    coop.set_cursor(self.board.get_selected_cell_index());
                 }
             },
-            InputEvent::Select => {
-                if !self.game_over {
-                    self.board.select_active_cell();
-                    if self.board.is_selected_cell_bomb() {
-                        self.set_is_game_over(true);
-                    }
-                    else if self.board.is_all_safe_cells_open() {
-                        self.set_is_victory(true);
-                    }
-                }
+            InputEvent::Select if self.is_playing() => self.play_action(ReplayAction::Open),
+            InputEvent::Chord if self.is_playing() => self.play_action(ReplayAction::Chord),
+            InputEvent::GameDifficulty(difficulty) if self.change_difficulty && self.can_choose_game() => {
+                self.initiate_game(difficulty);
+                self.share_game();
+                self.start_player_two();
+            },
+            InputEvent::Hint if !self.game_over && !self.start_up => self.show_hint(),
+            InputEvent::ToggleProbabilities if !self.start_up => {
+                self.show_probabilities = !self.show_probabilities;
+                self.update_probabilities();
             },
-            InputEvent::GameDifficulty(difficulty) => {
-                if self.change_difficulty {
-                    self.initiate_game(difficulty);
+            InputEvent::Export if !self.start_up => self.export_game(),
+            InputEvent::Scoreboard => self.show_scoreboard = !self.show_scoreboard,
+            InputEvent::Flag => self.play_action(ReplayAction::Flag),
+            InputEvent::Quit => self.quit = true,
+            _  => { },
+        }
+    }
+
+    // In co-op the host applies every action, a client only sends it to the host
+    fn play_action(&mut self, action: ReplayAction) {
+        let index = self.board.get_selected_cell_index();
+        if let Some(coop) = self.coop.as_mut() {
+            coop.send_action(coop.get_player(), action, index);
+            if !coop.is_host() {
+                return;
+            }
+        }
+        self.apply_action(action, index);
+    }
+
+    // Play a move given by another program, moves that would do nothing are refused
+    pub fn play_action_at(&mut self, action: ReplayAction, index: usize) -> Result<(), &'static str> {
+        self.check_action(action, index)?;
+        self.apply_action(action, index);
+        Ok(())
+    }
+
+    // Actions from outside of the game are checked before they are applied, a refused one is dropped
+    fn check_action(&self, action: ReplayAction, index: usize) -> Result<(), &'static str> {
+        if !self.is_playing() {
+            return Err("The game is over");
+        }
+        let Some(cell) = self.board.get_cells().get(index) else {
+            return Err("That cell is outside of the board");
+        };
+        if cell.is_disabled() {
+            return Err("That cell is not part of the board");
+        }
+        match action {
+            ReplayAction::Open if cell.is_open() => return Err("That cell is already open"),
+            ReplayAction::Open if cell.is_flagged() => return Err("That cell is flagged"),
+            ReplayAction::Flag if cell.is_open() => return Err("Open cells can not be flagged"),
+            ReplayAction::Chord if !cell.is_open() => return Err("Only open cells can be chorded"),
+            _ => { },
+        }
+        Ok(())
+    }
+
+    fn apply_action(&mut self, action: ReplayAction, index: usize) {
+        self.record_replay_event(action, index);
+        match action {
+            ReplayAction::Open => {
+                self.status.clear();
+                let cell = &self.board.get_cells()[index];
+                if self.time_attack && !cell.is_open() && !cell.is_bomb() && cell.get_value() == 0 {
+                    self.time_limit += self.opening_bonus;
                 }
+                let exploded_count = self.board.open_cell(index);
+                self.handle_opened_cells(exploded_count);
+            },
+            ReplayAction::Chord => {
+                self.status.clear();
+                let exploded_count = self.board.chord_cell(index);
+                self.handle_opened_cells(exploded_count);
+            },
+            ReplayAction::Flag => self.board.toggle_cell_flag(index),
+        }
+
+        let elapsed = self.get_game_duration();
+        if let Some(broadcast) = self.broadcast.as_mut() {
+            broadcast.send_action(elapsed, action, index);
+        }
+    }
+
+    fn handle_opened_cells(&mut self, exploded_count: usize) {
+        if exploded_count > 0 {
+            self.lives = self.lives.saturating_sub(exploded_count as u32);
+            if self.lives == 0 {
+                self.set_is_game_over(true);
+                return;
             }
-            InputEvent::Flag => self.board.toggle_active_cell_flag(),
+            self.status = format!("Boom! {} {} left", self.lives, if self.lives == 1 { "life" } else { "lives" });
+        }
+
+        if self.board.is_all_safe_cells_open() {
+            self.set_is_victory(true);
+        }
+        self.update_probabilities();
+    }
+
+    fn handle_flags_event(&mut self, event: InputEvent) {
+        let index = self.board.get_selected_cell_index();
+        match event {
+            InputEvent::Navigation(direction) if !self.game_over => {
+                self.board.change_active_cell(InputEvent::Navigation(direction));
+            },
+            InputEvent::Select if self.is_playing() && !self.is_flags_bot_turn() => self.play_flags_move(FlagsMove::Reveal(index)),
+            InputEvent::Bomb if self.is_playing() && !self.is_flags_bot_turn() => self.play_flags_move(FlagsMove::Bomb(index)),
+            InputEvent::GameDifficulty(difficulty) if self.change_difficulty => self.initiate_game(difficulty),
             InputEvent::Quit => self.quit = true,
-            _  => { },
+            _ => { },
+        }
+    }
+
+    fn play_flags_bot_move(&mut self) {
+        if !self.is_playing() {
+            return;
+        }
+        let flags_move = match (self.flags_bot.as_mut(), self.flags.as_ref()) {
+            (Some(flags_bot), Some(flags)) => flags_bot.next_move(&self.board, flags),
+            _ => None,
+        };
+
+        if let Some(flags_move) = flags_move {
+            self.play_flags_move(flags_move);
+        }
+    }
+
+    fn is_flags_bot_turn(&self) -> bool {
+        self.flags_bot.is_some() && self.flags.as_ref().is_some_and(|flags| flags.get_turn() == flags_bot::BOT_PLAYER)
+    }
+
+    pub fn get_flags_player_name(&self, player: usize) -> String {
+        if self.flags_bot.is_some() && player == flags_bot::BOT_PLAYER {
+            "Computer".to_string()
+        }
+        else {
+            format!("Player {}", player + 1)
+        }
+    }
+
+    fn play_flags_move(&mut self, flags_move: FlagsMove) {
+        let Some(flags) = self.flags.as_mut() else {
+            return;
+        };
+
+        match flags.play(&mut self.board, flags_move) {
+            Ok(()) => {
+                self.status.clear();
+                if flags.is_over() {
+                    self.set_is_victory(true);
+                }
+            },
+            Err(error) => self.status = error.to_string(),
+        }
+    }
+
+    fn handle_world_event(&mut self, event: InputEvent) {
+        let is_playing = self.is_playing();
+        let Some(world) = self.world.as_mut() else {
+            return;
+        };
+
+        let exploded = match event {
+            InputEvent::Navigation(direction) if is_playing => {
+                world.move_cursor(direction);
+                false
+            },
+            InputEvent::Select if is_playing => world.open(),
+            InputEvent::Chord if is_playing => world.chord(),
+            InputEvent::Flag if is_playing => {
+                world.toggle_flag();
+                false
+            },
+            InputEvent::Export => {
+                self.save_world();
+                false
+            },
+            InputEvent::GameDifficulty(difficulty) if self.change_difficulty => {
+                self.start_endless_game(difficulty);
+                false
+            },
+            InputEvent::Scoreboard => {
+                self.show_scoreboard = !self.show_scoreboard;
+                false
+            },
+            InputEvent::Quit => {
+                self.quit = true;
+                false
+            },
+            _ => false,
+        };
+
+        if exploded {
+            self.set_is_game_over(true);
+            self.status = format!("Boom! {} cells opened before the first mine", self.get_world_score());
+        }
+    }
+
+    pub fn start_race(&mut self, race: Race){
+        self.difficulty = race.get_rules().get_difficulty();
+        self.change_difficulty = false;
+        self.race = Some(race);
+    }
+
+    // Start the race once the countdown is over and stop playing once it is decided
+    fn update_race(&mut self) {
+        let progress = self.get_race_progress();
+        let Some(race) = self.race.as_mut() else {
+            return;
+        };
+
+        race.update(progress);
+        let is_decided = race.get_winner().is_some();
+        if self.start_up && !is_decided && race.get_countdown().is_zero() {
+            let (difficulty, seed) = (race.get_rules().get_difficulty(), race.get_rules().get_seed());
+            self.start_seeded_game(difficulty, seed);
+            self.broadcast_game();
+        }
+        else if is_decided && self.is_playing() {
+            self.set_is_game_over(true);
+        }
+    }
+
+    fn get_race_progress(&self) -> Progress {
+        let state = if self.victory {
+            PlayerState::Won
+        }
+        else if self.game_over {
+            PlayerState::Lost
+        }
+        else {
+            PlayerState::Playing
+        };
+        let percent = if self.start_up { 0 } else { self.board.get_opened_percentage() };
+        Progress::new(percent, self.lives, state)
+    }
+
+    pub fn start_coop(&mut self, mut coop: Coop){
+        if coop.is_host() {
+            let (difficulty, seed) = (coop.get_rules().get_difficulty(), coop.get_rules().get_seed());
+            self.start_seeded_game(difficulty, seed);
+            self.broadcast_game();
+            coop.set_cursor(self.board.get_selected_cell_index());
+        }
+        else {
+            // The game starts with the first snapshot of the host
+            self.change_difficulty = false;
+            self.status = "Waiting for the host".to_string();
+        }
+        self.coop = Some(coop);
+    }
+
+    fn update_coop(&mut self) {
+        let Some(coop) = self.coop.as_mut() else {
+            return;
+        };
+
+        for event in coop.poll() {
+            match event {
+                // Actions are only applied when they are still allowed when they arrive, so the first
+                // of two conflicting actions wins and a bad index can not end the game for everyone
+                CoopEvent::Action(player, action, index) if self.check_action(action, index).is_ok() => {
+                    if let Some(coop) = self.coop.as_mut().filter(|coop| coop.is_host()) {
+                        coop.send_action(player, action, index);
+                    }
+                    self.apply_action(action, index);
+                },
+                CoopEvent::Action(..) => { },
+                CoopEvent::SnapshotWanted(player) => {
+                    let snapshot = self.get_snapshot();
+                    if let Some(coop) = self.coop.as_mut() {
+                        coop.send_snapshot(player, snapshot);
+                    }
+                },
+                CoopEvent::Snapshot(snapshot) => {
+                    self.load_snapshot(&snapshot);
+                    self.broadcast_game();
+                    let index = self.board.get_selected_cell_index();
+                    if let Some(coop) = self.coop.as_mut() {
+                        coop.set_cursor(index);
+                    }
+                },
+            }
+        }
+    }
+
+    // Send a new game of the co-op host to the other players
+    fn share_game(&mut self) {
+        let snapshot = self.get_snapshot();
+        if let Some(coop) = self.coop.as_mut() {
+            coop.send_snapshot(None, snapshot);
+        }
+        self.broadcast_game();
+    }
+
+    pub fn start_broadcast(&mut self, broadcast: Broadcast) {
+        self.broadcast = Some(broadcast);
+    }
+
+    // Send the whole game to the viewers, they start over from it
+    fn broadcast_game(&mut self) {
+        let snapshot = self.get_snapshot();
+        if let Some(broadcast) = self.broadcast.as_mut() {
+            broadcast.send_snapshot(snapshot);
+        }
+    }
+
+    fn update_broadcast(&mut self) {
+        if self.broadcast.as_mut().is_some_and(Broadcast::poll) {
+            self.broadcast_game();
+        }
+    }
+
+    pub fn start_watch(&mut self, watch: Watch) {
+        self.change_difficulty = false;
+        self.watch = Some(watch);
+    }
+
+    // Play the actions of the watched player once they are due and keep the clock in step with theirs
+    fn update_watch(&mut self) {
+        let Some(watch) = self.watch.as_mut() else {
+            return;
+        };
+        let (events, is_connected) = (watch.poll(), watch.is_connected());
+
+        for event in events {
+            match event {
+                WatchEvent::Snapshot(snapshot) => self.load_snapshot(&snapshot),
+                WatchEvent::Action(elapsed, action, index) if self.check_action(action, index).is_ok() => {
+                    if self.is_playing() {
+                        self.start_time = Instant::now().checked_sub(elapsed).unwrap_or(self.start_time);
+                    }
+                    self.apply_action(action, index);
+                },
+                WatchEvent::Action(..) => { },
+            }
+        }
+        if !is_connected {
+            self.status = "The player stopped broadcasting".to_string();
+        }
+    }
+
+    fn get_snapshot(&self) -> Snapshot {
+        let cells = self.board.get_cells();
+        let open = (0..cells.len()).filter(|&i| cells[i].is_open()).collect();
+        let flags = (0..cells.len()).flat_map(|i| std::iter::repeat_n(i, cells[i].get_flag_count() as usize)).collect();
+        Snapshot::new(self.difficulty, self.board.get_seed().unwrap_or_default(), self.get_game_duration(), open, flags)
+    }
+
+    fn load_snapshot(&mut self, snapshot: &Snapshot) {
+        self.start_seeded_game(snapshot.get_difficulty(), snapshot.get_seed());
+        self.board.restore_progress(snapshot.get_open(), snapshot.get_flags());
+        self.start_time = Instant::now().checked_sub(snapshot.get_elapsed()).unwrap_or(self.start_time);
+        self.end_time = self.start_time;
+        self.check_restored_game();
+    }
+
+    // Only the race and the co-op host pick the next game
+    fn can_choose_game(&self) -> bool {
+        self.race.is_none() && self.coop.as_ref().is_none_or(Coop::is_host)
+    }
+
+    // Both boards of a split screen race get the same seed
+    fn start_player_two(&mut self) {
+        self.split_screen_winner = None;
+        let seed = self.board.get_seed().unwrap_or_default();
+        if let Some(player_two) = self.player_two.as_mut() {
+            player_two.start_seeded_game(self.difficulty, seed);
+        }
+    }
+
+    // Decide the race once a board is cleared or lost and stop the other board
+    fn update_split_screen(&mut self) {
+        let progress = self.get_race_progress();
+        let Some(player_two) = self.player_two.as_mut() else {
+            return;
+        };
+        player_two.check_time_limit();
+        if self.split_screen_winner.is_some() || self.start_up {
+            return;
+        }
+
+        let Some(is_first_winner) = race::decide_winner(progress, player_two.get_race_progress()) else {
+            return;
+        };
+        self.split_screen_winner = Some(if is_first_winner { 0 } else { 1 });
+        if player_two.is_playing() {
+            player_two.set_is_game_over(true);
+        }
+        if self.is_playing() {
+            self.set_is_game_over(true);
+        }
+    }
+
+    fn start_endless_game(&mut self, difficulty: Difficulty){
+        self.world = Some(World::new(rand::random(), world::get_mine_density(difficulty), self.board.get_neighborhood()));
+        self.reset_game(difficulty);
+    }
+
+    pub fn start_world(&mut self, world: World){
+        let is_exploded = world.is_exploded();
+        self.world = Some(world);
+        self.reset_game(self.difficulty);
+        if is_exploded {
+            self.set_is_game_over(true);
         }
     }
 
+    // Save the explored world to the world file, or to a new file in the current directory
+    fn save_world(&mut self) {
+        let Some(world) = &self.world else {
+            return;
+        };
+
+        let path = self.world_path.clone().unwrap_or_else(|| {
+            let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs());
+            PathBuf::from(format!("minesweeper-world-{}.json", timestamp))
+        });
+        self.status = match world.save(&path) {
+            Ok(()) => format!("World saved to {}", path.display()),
+            Err(error) => format!("Failed to save world: {}", error),
+        };
+    }
+
+    fn get_world_score(&self) -> usize {
+        self.world.as_ref().map_or(0, World::get_score)
+    }
+
     fn initiate_game(&mut self, difficulty: Difficulty){
+        if self.endless {
+            self.start_endless_game(difficulty);
+            return;
+        }
+        if self.daily_mode {
+            self.start_daily_game(difficulty);
+            return;
+        }
+
+        self.board.initiate_board(difficulty);
+        self.reset_game(difficulty);
+    }
+
+    fn start_daily_game(&mut self, difficulty: Difficulty){
+        let date = daily::get_today();
+        let neighborhood = self.board.get_neighborhood();
+        let (is_retry, is_completed) = {
+            let scoreboard = self.get_scoreboard();
+            (scoreboard.has_played_daily(&date, difficulty, neighborhood), scoreboard.is_daily_completed(&date, difficulty, neighborhood))
+        };
+        self.start_seeded_game(difficulty, daily::get_daily_seed(&date, difficulty, neighborhood));
+
+        self.status = format!("Daily challenge {}", date);
+        if is_completed {
+            self.status.push_str(", already completed today, this is a retry");
+        }
+        else if is_retry {
+            self.status.push_str(", this is a retry");
+        }
+        self.daily_date = Some(date);
+        self.is_daily_retry = is_retry;
+    }
+
+    pub fn start_seeded_game(&mut self, difficulty: Difficulty, seed: u64){
+        self.board.initiate_seeded_board(difficulty, seed);
+        self.reset_game(difficulty);
+    }
+
+    pub fn start_layout_game(&mut self, layout: &Layout){
+        layout.apply_to(&mut self.board);
+        self.reset_game(self.difficulty);
+        self.layout_mode = Some(layout.get_mode().unwrap_or("custom").to_string());
+        self.check_restored_game();
+    }
+
+    // A board that was already played on can be lost or won from the start
+    fn check_restored_game(&mut self) {
+        self.lives = self.max_lives.saturating_sub(self.board.get_exploded_count() as u32);
+        if self.lives == 0 {
+            self.set_is_game_over(true);
+        }
+        else if self.board.is_all_safe_cells_open() {
+            self.set_is_victory(true);
+        }
+    }
+
+    pub fn start_replay(&mut self, replay: Replay){
+        self.start_layout_game(&replay.get_layout());
+        self.playback = Some((replay, 0));
+    }
+
+    fn record_replay_event(&mut self, action: ReplayAction, index: usize) {
+        if self.is_playing() {
+            let event = ReplayEvent::new(self.start_time.elapsed(), action, index);
+            self.replay_events.push(event);
+        }
+    }
+
+    // Write the board as a text layout, an MBF board and a RawVF replay
+    fn export_game(&mut self) {
+        if self.is_multi_mine() {
+            self.status = "Boards with multi-mine cells can not be exported".to_string();
+            return;
+        }
+        if self.board.get_depth() > 1 {
+            self.status = "Layered boards can not be exported".to_string();
+            return;
+        }
+        // The file formats only describe flat square boards with the 8 surrounding neighbours,
+        // any other board would be read back with different numbers
+        if self.board.get_grid() != Grid::Square {
+            self.status = "Only square boards can be exported".to_string();
+            return;
+        }
+        if self.board.is_wrapping() || self.board.get_neighborhood() != Neighborhood::Moore {
+            self.status = "Boards that wrap around or use another neighbourhood can not be exported".to_string();
+            return;
+        }
+
+        let mode = self.layout_mode.clone().unwrap_or_else(|| self.difficulty.get_name().to_string());
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs());
+        let path = PathBuf::from(format!("minesweeper-{}", timestamp));
+
+        // MBF and RawVF have no way to leave cells out, so masked boards are only written as a layout
+        let is_masked = self.board.is_masked();
+        let mut result = layout::write_layout(&path.with_extension("board"), &Layout::from_board(&self.board, Some(mode)));
+        if !is_masked {
+            result = result
+                .and_then(|_| interop::write_mbf(&self.board))
+                .and_then(|bytes| fs::write(path.with_extension("mbf"), bytes))
+                .and_then(|_| fs::write(path.with_extension("rawvf"),
+                                        Replay::new(&self.board, self.replay_events.clone()).to_string()));
+        }
+
+        self.status = match result {
+            Ok(()) if is_masked => format!("Board exported to {}.board, masked boards can not be written as .mbf or .rawvf", path.display()),
+            Ok(()) => format!("Board exported to {}.board, .mbf and .rawvf", path.display()),
+            Err(error) => format!("Failed to export board: {}", error),
+        };
+    }
+
+    fn reset_game(&mut self, difficulty: Difficulty){
         self.difficulty = difficulty;
         self.game_over = false;
         self.victory = false;
         self.change_difficulty = false;
         self.start_up = false;
+        self.layout_mode = None;
+        self.replay_events.clear();
+        self.playback = None;
+        self.daily_date = None;
+        self.is_daily_retry = false;
+        self.lives = self.max_lives;
 
-        self.board.initiate_board(self.difficulty);
         self.start_time = Instant::now();
+        self.end_time = self.start_time;
+        self.time_limit = self.time_limit_override.unwrap_or_else(|| get_default_time_limit(difficulty));
+        self.stats = GameStats::new();
+        // Only full, flat square boards with single mines and a single life compete for the best times
+        if self.is_multi_mine() ||
+            self.max_lives > 1 ||
+            self.board.get_grid() != Grid::Square ||
+            self.board.is_wrapping() ||
+            self.board.is_masked() ||
+            self.board.get_depth() > 1 {
+            self.stats.set_unranked();
+        }
+        self.status.clear();
+        self.update_probabilities();
+        if self.is_flags_mode {
+            self.flags = Some(FlagsGame::new(&self.board));
+        }
+    }
+
+    fn update_probabilities(&mut self) {
+        if !self.show_probabilities || self.game_over || self.victory || self.is_multi_mine() {
+            self.board.set_mine_probabilities(None);
+            return;
+        }
+
+        self.stats.set_unranked();
+        let probabilities = Solver::new(&self.board).get_mine_probabilities();
+        self.board.set_mine_probabilities(Some(probabilities));
+    }
+
+    fn show_hint(&mut self) {
+        if self.is_multi_mine() {
+            self.status = "Hints are not available with multi-mine cells".to_string();
+            return;
+        }
+
+        match Solver::new(&self.board).get_hint() {
+            Some(deduction) => {
+                self.board.set_hinted_cell(Some(deduction.get_index()), deduction.is_mine());
+                self.stats.add_hint(self.hint_penalty);
+                self.status = deduction.get_explanation().to_string();
+            },
+            None => {
+                self.status = "No cell can be deduced, you will have to guess".to_string();
+            },
+        }
+    }
+
+    fn check_time_limit(&mut self) {
+        if self.is_playing() && self.get_time_left() == Some(Duration::ZERO) {
+            self.set_is_game_over(true);
+            self.status = "Time is up".to_string();
+        }
+    }
+
+    pub fn get_game_duration(&self) -> Duration {
+        let end_time = if self.is_playing() { Instant::now() } else { self.end_time };
+        end_time - self.start_time + self.stats.get_time_penalty()
+    }
+
+    // Time left on the clock in time attack
+    pub fn get_time_left(&self) -> Option<Duration> {
+        if !self.time_attack || self.start_up {
+            return None;
+        }
+        Some(self.time_limit.saturating_sub(self.get_game_duration()))
     }
 
     fn end_game(&mut self) {
         self.end_time = Instant::now();
         self.change_difficulty = true;
+        self.record_result();
+    }
+
+    fn record_result(&mut self) {
+        // Games played by the bot, from a file, on an endless board, together, side by side or by someone else do not count
+        if self.bot.is_some() || self.layout_mode.is_some() || self.world.is_some() || self.coop.is_some() || self.flags.is_some() ||
+            self.is_split_screen || self.watch.is_some() {
+            return;
+        }
+
+        let time = self.get_game_duration().as_secs_f64();
+        let result = if self.time_attack {
+            if !self.victory {
+                return;
+            }
+            self.get_scoreboard().save_time_attack_score(ScoreRecord::new(self.difficulty,
+                                                                          time,
+                                                                          daily::get_today(),
+                                                                          self.board.get_seed(),
+                                                                          self.stats.get_hint_count(),
+                                                                          self.stats.is_ranked(),
+                                                                          self.board.get_neighborhood()))
+        }
+        else if let Some(date) = self.daily_date.clone() {
+            self.get_scoreboard().save_daily_result(DailyRecord::new(date,
+                                                                     self.difficulty,
+                                                                     self.victory,
+                                                                     time,
+                                                                     self.is_daily_retry,
+                                                                     self.board.get_neighborhood(),
+                                                                     &self.stats))
+        }
+        else if self.victory {
+            self.get_scoreboard().save_score(ScoreRecord::new(self.difficulty,
+                                                              time,
+                                                              daily::get_today(),
+                                                              self.board.get_seed(),
+                                                              self.stats.get_hint_count(),
+                                                              self.stats.is_ranked(),
+                                                              self.board.get_neighborhood()))
+        }
+        else {
+            Ok(())
+        };
+
+        if let Err(error) = result {
+            self.status = format!("Failed to save score: {}", error);
+        }
+        #[cfg(feature = "leaderboard")]
+        self.submit_to_leaderboard(time);
+    }
+
+    // Only ranked wins on seeded boards can be checked by the leaderboard
+    #[cfg(feature = "leaderboard")]
+    fn submit_to_leaderboard(&mut self, time: f64) {
+        let (Some(leaderboard), Some(seed)) = (self.leaderboard.as_mut(), self.board.get_seed()) else {
+            return;
+        };
+        if !self.victory || !self.stats.is_ranked() || self.time_attack || self.daily_date.is_some() {
+            return;
+        }
+
+        let replay = Replay::new(&self.board, self.replay_events.clone()).to_string();
+        if let Err(error) = leaderboard.submit(self.difficulty, seed, time, daily::get_today(), &replay) {
+            self.status = format!("Failed to queue the leaderboard result: {}", error);
+        }
+    }
+
+    #[cfg(feature = "leaderboard")]
+    fn update_leaderboard(&mut self) {
+        let Some(leaderboard) = self.leaderboard.as_mut() else {
+            return;
+        };
+        if let Err(error) = leaderboard.update() {
+            self.status = format!("Failed to save the leaderboard queue: {}", error);
+        }
     }
 
     fn set_is_game_over(&mut self, game_over: bool) {
@@ -162,4 +1087,305 @@ impl App {
     pub fn is_start_up(&self) -> bool {
         self.start_up
     }
+
+    // Shape of the next generated boards
+    pub fn set_mask(&mut self, mask: Option<Mask>) {
+        if let Some(player_two) = self.player_two.as_mut() {
+            player_two.set_mask(mask.clone());
+        }
+        self.board.set_mask(mask);
+    }
+
+    fn is_multi_mine(&self) -> bool {
+        self.board.get_max_mines_per_cell() > 1
+    }
+
+    pub fn is_playing(&self) -> bool {
+        !self.start_up && !self.game_over && !self.victory
+    }
+
+    pub fn get_board(&self) -> &Board {
+        &self.board
+    }
+
+    pub fn get_world(&self) -> Option<&World> {
+        self.world.as_ref()
+    }
+
+    #[cfg(feature = "leaderboard")]
+    pub fn get_leaderboard(&self) -> Option<&Leaderboard> {
+        self.leaderboard.as_ref()
+    }
+
+    pub fn get_broadcast(&self) -> Option<&Broadcast> {
+        self.broadcast.as_ref()
+    }
+
+    pub fn get_watch(&self) -> Option<&Watch> {
+        self.watch.as_ref()
+    }
+
+    pub fn get_player_two(&self) -> Option<&App> {
+        self.player_two.as_deref()
+    }
+
+    pub fn get_split_screen_winner(&self) -> Option<usize> {
+        self.split_screen_winner
+    }
+
+    pub fn get_flags(&self) -> Option<&FlagsGame> {
+        self.flags.as_ref()
+    }
+
+    pub fn get_coop(&self) -> Option<&Coop> {
+        self.coop.as_ref()
+    }
+
+    pub fn get_race(&self) -> Option<&Race> {
+        self.race.as_ref()
+    }
+
+    pub fn get_scoreboard(&self) -> MutexGuard<'_, Scoreboard> {
+        self.scoreboard.lock().expect("scoreboard lock expect")
+    }
+
+    // Sessions served together save to one scoreboard instead of loading their own
+    #[cfg(feature = "ssh")]
+    pub fn set_scoreboard(&mut self, scoreboard: Arc<Mutex<Scoreboard>>) {
+        self.scoreboard = scoreboard;
+        self.scoreboard_path = None;
+    }
+
+    pub fn is_showing_scoreboard(&self) -> bool {
+        self.show_scoreboard
+    }
+
+    pub fn is_daily_mode(&self) -> bool {
+        self.daily_mode
+    }
+
+    pub fn get_daily_date(&self) -> Option<&str> {
+        self.daily_date.as_deref()
+    }
+
+    // Lives left, only when playing with more than one
+    pub fn get_lives(&self) -> Option<u32> {
+        if self.max_lives > 1 { Some(self.lives) } else { None }
+    }
+
+    pub fn get_stats(&self) -> &GameStats {
+        &self.stats
+    }
+
+    pub fn get_status(&self) -> &str {
+        &self.status
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use crate::input_listener::Direction;
+
+    #[test]
+    fn test_time_attack(){
+        let args: Args = Args::parse_from(["minesweeper-tui", "--time-attack", "--time-limit", "0"]);
+        let mut app: App = App::new(args);
+        assert_eq!(app.get_time_left(), None);
+
+        app.start_seeded_game(Difficulty::Easy, 1);
+        assert_eq!(app.get_time_left(), Some(Duration::ZERO));
+        app.check_time_limit();
+        assert!(app.get_is_game_over());
+    }
+
+    #[test]
+    fn test_export_refused(){
+        let args: Args = Args::parse_from(["minesweeper-tui", "--grid", "hex"]);
+        let mut app: App = App::new(args);
+        app.start_seeded_game(Difficulty::Easy, 1);
+        app.export_game();
+        assert_eq!(app.status, "Only square boards can be exported");
+
+        for flags in [vec!["minesweeper-tui", "--wrap"], vec!["minesweeper-tui", "--neighborhood", "knight"]] {
+            let args: Args = Args::parse_from(flags);
+            let mut app: App = App::new(args);
+            app.start_seeded_game(Difficulty::Easy, 1);
+            app.export_game();
+            assert_eq!(app.status, "Boards that wrap around or use another neighbourhood can not be exported");
+        }
+    }
+
+    #[test]
+    fn test_opening_bonus(){
+        let args: Args = Args::parse_from(["minesweeper-tui", "--time-attack", "--opening-bonus", "5"]);
+        let mut app: App = App::new(args);
+        app.start_layout_game(&Layout::parse("*....\n.....\n").unwrap());
+        assert_eq!(app.time_limit, Duration::from_secs(60));
+
+        // The last cell touches no mine, so opening it clears an opening
+        for _ in 0..4 {
+            app.handle_event(InputEvent::Navigation(Direction::Right));
+        }
+        app.handle_event(InputEvent::Select);
+        assert_eq!(app.time_limit, Duration::from_secs(65));
+    }
+
+    #[test]
+    fn test_lives(){
+        let args: Args = Args::parse_from(["minesweeper-tui", "--lives", "2"]);
+        let mut app: App = App::new(args);
+        app.start_layout_game(&Layout::parse("*..\n...\n").unwrap());
+        assert_eq!(app.get_lives(), Some(2));
+        assert!(!app.get_stats().is_ranked());
+
+        app.handle_event(InputEvent::Select);
+        assert_eq!(app.get_lives(), Some(1));
+        assert!(app.is_playing());
+
+        // The exploded mine counts as found for the hints, chords and the win check
+        app.handle_event(InputEvent::Navigation(Direction::Right));
+        app.handle_event(InputEvent::Select);
+        let hint = Solver::new(app.get_board()).get_hint().expect("hint expect");
+        assert!(!hint.is_mine());
+        app.handle_event(InputEvent::Chord);
+        assert!(app.get_is_victory());
+
+        // Losing the last life ends the game
+        app.start_layout_game(&Layout::parse("*.*\n...\n").unwrap());
+        app.handle_event(InputEvent::Select);
+        app.handle_event(InputEvent::Navigation(Direction::Right));
+        app.handle_event(InputEvent::Navigation(Direction::Right));
+        app.handle_event(InputEvent::Select);
+        assert!(app.get_is_game_over());
+    }
+
+    #[test]
+    fn test_flags(){
+        let args: Args = Args::parse_from(["minesweeper-tui", "--flags"]);
+        let mut app: App = App::new(args);
+        app.start_layout_game(&Layout::parse("*.*\n...\n").unwrap());
+
+        // Opening a mine claims it instead of ending the game
+        app.handle_event(InputEvent::Select);
+        assert!(app.is_playing());
+        app.handle_event(InputEvent::Navigation(Direction::Right));
+        app.handle_event(InputEvent::Select);
+        let flags = app.get_flags().expect("flags expect");
+        assert_eq!((flags.get_score(0), flags.get_turn()), (1, 1));
+
+        app.handle_event(InputEvent::Navigation(Direction::Right));
+        app.handle_event(InputEvent::Select);
+        assert!(app.get_is_victory());
+        assert_eq!(app.get_flags().expect("flags expect").get_winner(), None);
+    }
+
+    #[test]
+    fn test_split_screen(){
+        let args: Args = Args::parse_from(["minesweeper-tui", "--split-screen"]);
+        let mut app: App = App::new(args);
+        app.handle_event(InputEvent::GameDifficulty(Difficulty::Easy));
+        let player_two = app.get_player_two().expect("player two expect");
+        assert_eq!(player_two.get_board().get_seed(), app.get_board().get_seed());
+
+        // Player two steps on a mine, which hands the race to player one
+        let mine = app.get_board().get_cells().iter().position(|cell| cell.get_mine_count() > 0).expect("mine expect");
+        let width = app.get_board().get_board_width();
+        for _ in 0..mine % width {
+            app.handle_event(InputEvent::Player(1, Box::new(InputEvent::Navigation(Direction::Right))));
+        }
+        for _ in 0..mine / width {
+            app.handle_event(InputEvent::Player(1, Box::new(InputEvent::Navigation(Direction::Down))));
+        }
+        app.handle_event(InputEvent::Player(1, Box::new(InputEvent::Select)));
+        assert!(app.get_player_two().expect("player two expect").get_is_game_over());
+        assert_eq!(app.get_split_screen_winner(), Some(0));
+        assert!(!app.is_playing());
+
+        // A new game starts both boards again
+        app.handle_event(InputEvent::Player(0, Box::new(InputEvent::GameDifficulty(Difficulty::Easy))));
+        assert_eq!(app.get_split_screen_winner(), None);
+        assert!(app.get_player_two().expect("player two expect").is_playing());
+    }
+
+    #[test]
+    fn test_coop_refused_action(){
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind expect");
+        let address = listener.local_addr().expect("address expect");
+        let args: Args = Args::parse_from(["minesweeper-tui"]);
+        let mut host: App = App::new(args.clone());
+        host.start_coop(Coop::host(listener, race::Rules::new(&args, Difficulty::Easy, 3)));
+        let client = std::thread::spawn(move || Coop::join(address).expect("join expect"));
+        let start = Instant::now();
+        while !client.is_finished() {
+            assert!(start.elapsed() < Duration::from_secs(5), "timed out");
+            std::thread::sleep(Duration::from_millis(10));
+            host.update_coop();
+        }
+        let mut client = client.join().expect("client expect");
+
+        // A cell outside of the board is dropped, the action after it still goes through
+        client.send_action(client.get_player(), ReplayAction::Open, 10_000);
+        client.send_action(client.get_player(), ReplayAction::Flag, 0);
+        while !host.get_board().get_cells()[0].is_flagged() {
+            assert!(start.elapsed() < Duration::from_secs(5), "timed out");
+            std::thread::sleep(Duration::from_millis(10));
+            host.update_coop();
+        }
+        assert!(host.is_playing());
+        assert!(host.play_action_at(ReplayAction::Open, 0).is_err());
+    }
+
+    #[test]
+    fn test_watch(){
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind expect");
+        let address = listener.local_addr().expect("address expect");
+        let args: Args = Args::parse_from(["minesweeper-tui"]);
+        let mut player: App = App::new(args.clone());
+        player.start_broadcast(Broadcast::start(listener, race::Rules::new(&args, Difficulty::Easy, 0)));
+        let mut viewer: App = App::new(args);
+        viewer.start_watch(Watch::join(address, Duration::ZERO).expect("watch expect"));
+
+        let start = Instant::now();
+        while player.get_broadcast().expect("broadcast expect").get_viewer_count() == 0 {
+            assert!(start.elapsed() < Duration::from_secs(5), "timed out");
+            std::thread::sleep(Duration::from_millis(10));
+            player.update_broadcast();
+        }
+        player.handle_event(InputEvent::GameDifficulty(Difficulty::Medium));
+        player.handle_event(InputEvent::Flag);
+        player.handle_event(InputEvent::Navigation(Direction::Right));
+        player.handle_event(InputEvent::Select);
+
+        // The viewer ends up with the same board and can not play on it
+        let is_same_board = |player: &App, viewer: &App| {
+            let (cells, viewer_cells) = (player.get_board().get_cells(), viewer.get_board().get_cells());
+            player.get_board().get_seed() == viewer.get_board().get_seed() &&
+                cells.iter().zip(viewer_cells).all(|(cell, viewer_cell)| cell.is_open() == viewer_cell.is_open() && cell.get_flag_count() == viewer_cell.get_flag_count())
+        };
+        while !is_same_board(&player, &viewer) {
+            assert!(start.elapsed() < Duration::from_secs(5), "timed out");
+            std::thread::sleep(Duration::from_millis(10));
+            viewer.update_watch();
+        }
+        viewer.handle_event(InputEvent::Chord);
+        viewer.handle_event(InputEvent::Flag);
+        assert!(is_same_board(&player, &viewer));
+    }
+
+    #[test]
+    fn test_run_on_any_output(){
+        let path = std::env::temp_dir().join(format!("minesweeper-tui-run-{}.json", std::process::id()));
+        let args: Args = Args::parse_from(["minesweeper-tui", "--scoreboard", path.to_str().expect("path expect")]);
+        let mut app: App = App::new(args);
+        let (tx, rx) = std::sync::mpsc::channel();
+        tx.send(InputEvent::Resize(100, 50)).expect("send expect");
+        tx.send(InputEvent::Quit).expect("send expect");
+
+        let mut output: Vec<u8> = vec![];
+        app.run(&rx, &mut output, Some(Rect::new(0, 0, 80, 40))).expect("run expect");
+        assert!(String::from_utf8_lossy(&output).contains("Minesweeper"));
+        assert!(!path.exists());
+    }
 }
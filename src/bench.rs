@@ -0,0 +1,153 @@
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+
+use crate::{
+    Args,
+    app::{App, Difficulty},
+    bot::Bot,
+};
+
+// Number of buckets in the histogram of how far lost games got
+const LOSS_BUCKETS: usize = 10;
+
+struct GameResult {
+    victory: bool,
+    guess_count: u32,
+    duration: Duration,
+    // Share of the safe cells that were open when the game ended
+    progress: f64,
+}
+
+#[derive(Serialize)]
+pub struct DifficultyReport {
+    difficulty: &'static str,
+    games: usize,
+    wins: usize,
+    win_rate: f64,
+    average_guesses: f64,
+    average_solve_ms: f64,
+    // Lost games bucketed by the share of safe cells opened, in steps of 10%
+    loss_histogram: Vec<usize>,
+}
+
+pub fn run_bench(args: &Args, games: usize, base_seed: u64, difficulties: &[Difficulty], threads: usize, json: bool) {
+    let reports: Vec<DifficultyReport> = difficulties.iter()
+        .map(|&difficulty| get_report(args, difficulty, games, base_seed, threads.max(1)))
+        .collect();
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&reports).expect("Failed to serialize bench report"));
+    }
+    else {
+        print_table(&reports);
+    }
+}
+
+fn get_report(args: &Args, difficulty: Difficulty, games: usize, base_seed: u64, threads: usize) -> DifficultyReport {
+    // Every thread plays every n-th seed so the set of games does not depend on the thread count
+    let handles: Vec<thread::JoinHandle<Vec<GameResult>>> = (0..threads)
+        .map(|thread_index| {
+            let args = args.clone();
+            thread::spawn(move || {
+                (thread_index..games)
+                    .step_by(threads)
+                    .map(|game| play_game(&args, difficulty, base_seed.wrapping_add(game as u64)))
+                    .collect()
+            })
+        })
+        .collect();
+
+    let results: Vec<GameResult> = handles.into_iter()
+        .flat_map(|handle| handle.join().expect("Bench thread panicked"))
+        .collect();
+
+    let wins = results.iter().filter(|result| result.victory).count();
+    let mut loss_histogram = vec![0; LOSS_BUCKETS];
+    for result in results.iter().filter(|result| !result.victory) {
+        let bucket = (result.progress * LOSS_BUCKETS as f64) as usize;
+        loss_histogram[bucket.min(LOSS_BUCKETS - 1)] += 1;
+    }
+
+    let game_count = results.len().max(1) as f64;
+    DifficultyReport {
+        difficulty: difficulty.get_name(),
+        games: results.len(),
+        wins,
+        win_rate: wins as f64 / game_count,
+        average_guesses: results.iter().map(|result| result.guess_count as f64).sum::<f64>() / game_count,
+        average_solve_ms: results.iter().map(|result| result.duration.as_secs_f64() * 1000.0).sum::<f64>() / game_count,
+        loss_histogram,
+    }
+}
+
+fn play_game(args: &Args, difficulty: Difficulty, seed: u64) -> GameResult {
+    let mut app: App = App::new(args.clone());
+    let mut bot: Bot = Bot::new();
+    let start_time = Instant::now();
+
+    app.start_seeded_game(difficulty, seed);
+    while app.is_playing() {
+        match bot.next_event(app.get_board(), true) {
+            Some(event) => app.handle_event(event),
+            None => break,
+        }
+    }
+
+    let board = app.get_board();
+    let safe_cells = board.get_cells().len() - board.get_bomb_count();
+    let open_safe_cells = board.get_cells().iter()
+        .filter(|cell| cell.is_open() && !cell.is_bomb())
+        .count();
+
+    GameResult {
+        victory: app.get_is_victory(),
+        guess_count: bot.get_guess_count(),
+        duration: start_time.elapsed(),
+        progress: open_safe_cells as f64 / safe_cells.max(1) as f64,
+    }
+}
+
+fn print_table(reports: &[DifficultyReport]) {
+    println!("{:<10} {:>7} {:>7} {:>9} {:>9} {:>11}", "Difficulty", "Games", "Wins", "Win rate", "Guesses", "Solve (ms)");
+    for report in reports.iter() {
+        println!("{:<10} {:>7} {:>7} {:>8.1}% {:>9.2} {:>11.2}",
+                 report.difficulty,
+                 report.games,
+                 report.wins,
+                 report.win_rate * 100.0,
+                 report.average_guesses,
+                 report.average_solve_ms);
+    }
+
+    println!();
+    println!("Losses by share of safe cells opened");
+    for report in reports.iter() {
+        let buckets: Vec<String> = report.loss_histogram.iter()
+            .enumerate()
+            .map(|(i, count)| format!("{}-{}%: {}", i * 10, (i + 1) * 10, count))
+            .collect();
+        println!("{:<10} {}", report.difficulty, buckets.join("  "));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[test]
+    fn test_bench_is_reproducible(){
+        let args: Args = Args::parse_from(["minesweeper-tui"]);
+        let report = get_report(&args, Difficulty::Easy, 8, 7, 1);
+        let threaded_report = get_report(&args, Difficulty::Easy, 8, 7, 3);
+
+        assert_eq!(report.games, 8);
+        assert_eq!(report.wins, threaded_report.wins);
+        assert_eq!(report.loss_histogram, threaded_report.loss_histogram);
+        assert_eq!(report.average_guesses, threaded_report.average_guesses);
+    }
+}
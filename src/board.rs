@@ -1,6 +1,6 @@
 
 use std::collections::HashMap;
-use rand::Rng;
+use rand::{Rng, SeedableRng, rngs::StdRng};
 
 use crate::{
     cell::Cell,
@@ -9,15 +9,25 @@ use crate::{
         Direction,
     },
     app::Difficulty,
+    layout::Mask,
+    neighborhood::Neighborhood,
+    topology::{Grid, Topology},
 };
 
+#[derive(Clone)]
 pub struct Board{
     cells: Vec<Cell>,
-    board_width: usize,
-    board_height: usize,
+    topology: Topology,
     selected_cell_index: usize,
     bomb_count: usize,
     flag_count: usize,
+    max_mines_per_cell: u8,
+    neighborhood: Neighborhood,
+    mask: Option<Mask>,
+    // Layers of generated boards
+    depth: usize,
+    hinted_cell_index: Option<usize>,
+    seed: Option<u64>,
     board_size_map: HashMap<Difficulty, (usize, usize)>,
     board_bombs_map: HashMap<Difficulty, usize>,
 }
@@ -26,11 +36,16 @@ impl Board {
     pub fn new() -> Self {
         Self {
             cells: Vec::new(),
-            board_width: 0,
-            board_height: 0,
+            topology: Topology::new(Grid::Square, 0, 0, 1, false),
             selected_cell_index: 0,
             bomb_count: 0,
             flag_count: 0,
+            max_mines_per_cell: 1,
+            neighborhood: Neighborhood::Moore,
+            mask: None,
+            depth: 1,
+            hinted_cell_index: None,
+            seed: None,
             board_size_map: HashMap::from([
                 (Difficulty::Easy, (9, 9)),
                 (Difficulty::Medium, (16, 16)),
@@ -45,24 +60,82 @@ impl Board {
     }
 
     pub fn initiate_board(&mut self, difficulty: Difficulty){
-        self.board_width = self.board_size_map[&difficulty].0;
-        self.board_height = self.board_size_map[&difficulty].1;
-        self.bomb_count = self.board_bombs_map[&difficulty];
+        self.initiate_seeded_board(difficulty, rand::thread_rng().gen());
+    }
 
-        self.cells.clear();
-        self.selected_cell_index = 0;
+    pub fn initiate_seeded_board(&mut self, difficulty: Difficulty, seed: u64){
+        let (width, height) = match &self.mask {
+            Some(mask) => (mask.get_width(), mask.get_height()),
+            None => self.board_size_map[&difficulty],
+        };
+        self.reset_cells(width, height, self.depth);
+        if let Some(mask) = self.mask.clone() {
+            let layer_size = self.topology.get_layer_size();
+            let disabled_indices: Vec<usize> = (0..self.cells.len()).filter(|&i| !mask.is_enabled(i % layer_size)).collect();
+            self.disable_cells(&disabled_indices);
+        }
+        self.seed = Some(seed);
+        self.add_bombs(self.get_difficulty_bomb_count(difficulty) as i16);
+        self.update_cell_values();
+    }
+
+    // Every layer gets the mines of the difficulty and masked boards keep its mine density,
+    // with at least one mine and one safe cell
+    fn get_difficulty_bomb_count(&self, difficulty: Difficulty) -> usize {
+        let bomb_count = self.board_bombs_map[&difficulty] * self.depth;
+        let Some(mask) = &self.mask else {
+            return bomb_count;
+        };
+
+        let (width, height) = self.board_size_map[&difficulty];
+        let cell_count = width * height * self.depth;
+        let enabled_count = mask.get_enabled_count() * self.depth;
+        ((bomb_count * enabled_count + cell_count / 2) / cell_count).clamp(1, enabled_count - 1)
+    }
+
+    pub fn disable_cells(&mut self, indices: &[usize]){
+        for &index in indices {
+            self.cells[index].set_disabled();
+        }
+
+        // The cursor starts on the first cell that is part of the board
+        if self.cells[self.selected_cell_index].is_disabled() {
+            if let Some(index) = self.cells.iter().position(|cell| !cell.is_disabled()) {
+                self.set_active_cell(index);
+            }
+        }
+    }
 
-        self.create_cells((self.board_width * self.board_height) as usize);
-        self.add_bombs(self.bomb_count as i16);
+    // Boards loaded from a layout or replay file, the tests also use it to place mines by hand
+    pub fn initiate_board_from_layout(&mut self, width: usize, height: usize, bomb_indices: &[usize]){
+        self.reset_cells(width, height, 1);
+        self.bomb_count = bomb_indices.len();
+        for &index in bomb_indices {
+            self.cells[index].add_mine();
+        }
         self.update_cell_values();
     }
 
-    pub fn is_selected_cell_bomb(&self) -> bool {
-        let cell: &Cell = &self.cells[self.selected_cell_index];
-        if cell.is_open() && cell.is_bomb() {
-            return true
+    pub fn restore_progress(&mut self, open_indices: &[usize], flagged_indices: &[usize]){
+        for &index in open_indices {
+            self.cells[index].open();
+        }
+        for &index in flagged_indices {
+            self.cells[index].cycle_flag(self.max_mines_per_cell);
+            self.flag_count += 1;
         }
-        false
+    }
+
+    fn reset_cells(&mut self, width: usize, height: usize, depth: usize){
+        self.topology = Topology::new(self.topology.get_grid(), width, height, depth, self.topology.is_wrapping());
+        self.flag_count = 0;
+        self.hinted_cell_index = None;
+        self.seed = None;
+
+        self.cells.clear();
+        self.selected_cell_index = 0;
+
+        self.create_cells(self.topology.get_cell_count());
     }
 
     fn create_cells(&mut self, cell_count: usize){
@@ -74,54 +147,123 @@ impl Board {
 
     fn add_bombs(&mut self, bomb_count: i16){
         self.bomb_count = bomb_count as usize;
-        let mut rng = rand::thread_rng();
+        let mut rng = StdRng::seed_from_u64(self.seed.unwrap_or_default());
         for _i in 0..bomb_count {
             let mut index = rng.gen_range(0..self.cells.len());
-            while self.cells[index].is_bomb() {
+            while self.cells[index].get_mine_count() >= self.max_mines_per_cell || self.cells[index].is_disabled() {
                 index = rng.gen_range(0..self.cells.len());
             }
-            self.cells[index].set_is_bomb(true);
+            self.cells[index].add_mine();
         }
     }
 
     fn update_cell_values(&mut self) {
         for i in 0..self.cells.len() {
-            let cell: &Cell = &self.cells[i];
+            let mine_count = self.cells[i].get_mine_count() as i16;
 
             // Continue if this is not a bomb, if it is increase score of all adjacent cells
-            if !cell.is_bomb() {
+            if mine_count == 0 {
                 continue;
             }
 
-            // Iterate over the neighbors and increment their values
+            // Iterate over the neighbors and add the mines of this cell to their values
             let neighbors_indexes: Vec<usize> = self.get_cell_neighbors_indices(i as i16);
             for i in neighbors_indexes {
-                self.cells[i].increment_value();
+                self.cells[i].increase_value(mine_count);
             }
         }
     }
 
-    fn get_index_from_pos(&self, x: i16, y: i16) -> Option<usize> {
-        if x < 0 || x >= self.board_width as i16 ||
-            y < 0 || y >= self.board_height as i16 {
-                return None;
-            }
+    pub fn get_pos_from_index(&self, index: i16) -> (i16, i16) {
+        self.topology.get_pos_from_index(index)
+    }
 
-        Some((x + (y * self.board_width as i16)) as usize)
+    pub fn get_board_width(&self) -> usize {
+        self.topology.get_width()
     }
 
-    fn get_pos_from_index(&self, index: i16) -> (i16, i16) {
-        let y = index / (self.board_width as i16);
-        let x = index - (y * (self.board_width as i16));
-        (x, y)
+    pub fn get_board_height(&self) -> usize {
+        self.topology.get_height()
     }
 
-    pub fn get_board_width(&self) -> usize {
-        self.board_width
+    pub fn get_grid(&self) -> Grid {
+        self.topology.get_grid()
     }
 
-    pub fn get_board_height(&self) -> usize {
-        self.board_height
+    // Takes effect on the next generated board
+    pub fn set_grid(&mut self, grid: Grid) {
+        self.topology = Topology::new(grid, self.topology.get_width(), self.topology.get_height(), self.depth, self.topology.is_wrapping());
+    }
+
+    pub fn is_wrapping(&self) -> bool {
+        self.topology.is_wrapping()
+    }
+
+    // Takes effect on the next generated board
+    pub fn set_wrap(&mut self, wrap: bool) {
+        self.topology = Topology::new(self.topology.get_grid(), self.topology.get_width(), self.topology.get_height(), self.depth, wrap);
+    }
+
+    pub fn get_seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    pub fn set_seed(&mut self, seed: Option<u64>) {
+        self.seed = seed;
+    }
+
+    pub fn get_max_mines_per_cell(&self) -> u8 {
+        self.max_mines_per_cell
+    }
+
+    // Takes effect on the next generated board
+    pub fn set_max_mines_per_cell(&mut self, max_mines_per_cell: u8) {
+        self.max_mines_per_cell = max_mines_per_cell.max(1);
+    }
+
+    pub fn get_neighborhood(&self) -> Neighborhood {
+        self.neighborhood
+    }
+
+    // Takes effect on the next generated board
+    pub fn set_neighborhood(&mut self, neighborhood: Neighborhood) {
+        self.neighborhood = neighborhood;
+    }
+
+    // Any difficulty can be picked during a game, so a layered board has to fit the largest one
+    pub fn get_largest_size(&self) -> (usize, usize) {
+        self.board_size_map.values().copied().max_by_key(|(width, height)| width * height).unwrap_or((0, 0))
+    }
+
+    pub fn get_depth(&self) -> usize {
+        self.topology.get_depth()
+    }
+
+    // Takes effect on the next generated board
+    pub fn set_depth(&mut self, depth: usize) {
+        self.depth = depth.max(1);
+    }
+
+    // Layer the cursor is on, only that layer is shown
+    pub fn get_selected_layer(&self) -> usize {
+        self.topology.get_layer_from_index(self.selected_cell_index)
+    }
+
+    pub fn get_layer_size(&self) -> usize {
+        self.topology.get_layer_size()
+    }
+
+    pub fn is_masked(&self) -> bool {
+        self.mask.is_some()
+    }
+
+    // Takes effect on the next generated board, the mask decides the size of the board
+    pub fn set_mask(&mut self, mask: Option<Mask>) {
+        self.mask = mask;
+    }
+
+    pub fn get_selected_cell_index(&self) -> usize {
+        self.selected_cell_index
     }
 
     pub fn get_cells(&self) -> &Vec<Cell> {
@@ -129,55 +271,133 @@ impl Board {
     }
 
     pub fn change_active_cell(&mut self, input_event: InputEvent) {
-        match input_event {
-            InputEvent::Navigation(Direction::Up)  => {
-                self.set_active_cell(self.selected_cell_index as i16 - self.board_width as i16);
-            },
-            InputEvent::Navigation(Direction::Down)  => {
-                self.set_active_cell(self.selected_cell_index as i16 + self.board_width as i16);
-            },
-            InputEvent::Navigation(Direction::Left)  => {
-                self.set_active_cell(self.selected_cell_index as i16 - 1);
-            },
-            InputEvent::Navigation(Direction::Right)  => {
-                self.set_active_cell(self.selected_cell_index as i16 + 1);
-            },
-            _ => { },
+        if let InputEvent::Navigation(direction) = input_event {
+            if let Some(index) = self.get_next_cell(self.selected_cell_index, direction) {
+                self.set_active_cell(index);
+            }
         }
     }
 
-    pub fn toggle_active_cell_flag(&mut self) {
-        self.cells[self.selected_cell_index].toggle_is_flagged();
-        if self.cells[self.selected_cell_index].is_flagged() {
-            self.flag_count += 1;
-        }
-        else {
-            self.flag_count -= 1;
+    // The cell reached by moving from a cell, jumping over holes in the board
+    pub fn get_next_cell(&self, index: usize, direction: Direction) -> Option<usize> {
+        let mut next_index = index;
+        for _ in 0..self.cells.len() {
+            next_index = self.topology.step(next_index, direction)?;
+            if next_index == index {
+                return None;
+            }
+            if !self.cells[next_index].is_disabled() {
+                return Some(next_index);
+            }
         }
+        None
+    }
+
+    pub fn toggle_cell_flag(&mut self, index: usize) {
+        self.clear_hinted_cell();
+        let cell: &mut Cell = &mut self.cells[index];
+        let previous_flag_count = cell.get_flag_count() as usize;
+        cell.cycle_flag(self.max_mines_per_cell);
+        self.flag_count = self.flag_count + cell.get_flag_count() as usize - previous_flag_count;
     }
 
+    // Exploded mines are open too, so only the safe cells matter
     pub fn is_all_safe_cells_open(&self) -> bool {
-        let open_cell_count = self.get_open_cell_count();
-        self.cells.len() - open_cell_count - self.bomb_count == 0
+        self.cells.iter().all(|cell| cell.is_open() || cell.is_bomb() || cell.is_disabled())
     }
 
-    fn set_active_cell(&mut self, index: i16){
-        if index < 0 || index >= self.cells.len() as i16 {
+    fn set_active_cell(&mut self, index: usize){
+        if index >= self.cells.len() {
             return;
         }
 
         self.cells[self.selected_cell_index].set_is_selected(false);
-        self.cells[index as usize].set_is_selected(true);
-        self.selected_cell_index = index as usize;
+        self.cells[index].set_is_selected(true);
+        self.selected_cell_index = index;
+    }
+
+    // Returns the number of mines that exploded
+    pub fn open_cell(&mut self, index: usize) -> usize {
+        self.clear_hinted_cell();
+        if self.cells[index].is_open() {
+            return 0;
+        }
+
+        self.cells[index].open();
+        if self.cells[index].is_bomb() {
+            return self.cells[index].get_mine_count() as usize;
+        }
+        if self.cells[index].get_value() == 0 {
+            self.open_adjacent_cells(index);
+        }
+        0
+    }
+
+    // Open the unflagged neighbours of an open number whose mines are all flagged or exploded
+    pub fn chord_cell(&mut self, index: usize) -> usize {
+        let cell = &self.cells[index];
+        if !cell.is_open() || cell.is_bomb() || cell.get_value() == 0 {
+            return 0;
+        }
+
+        let neighbors = self.get_cell_neighbors_indices(index as i16);
+        let marked_count: i16 = neighbors.iter()
+            .map(|&neighbor| {
+                let cell = &self.cells[neighbor];
+                if cell.is_open() { cell.get_mine_count() as i16 } else { cell.get_flag_count() as i16 }
+            })
+            .sum();
+        if marked_count != self.cells[index].get_value() {
+            return 0;
+        }
+
+        let mut exploded_count = 0;
+        for neighbor in neighbors {
+            if !self.cells[neighbor].is_flagged() {
+                exploded_count += self.open_cell(neighbor);
+            }
+        }
+        exploded_count
+    }
+
+    // Share of the safe cells that are open, from 0 to 100
+    pub fn get_opened_percentage(&self) -> u8 {
+        let safe_cells = self.cells.iter().filter(|cell| !cell.is_bomb() && !cell.is_disabled());
+        let (opened, total) = safe_cells.fold((0, 0), |(opened, total), cell| (opened + cell.is_open() as usize, total + 1));
+        if total == 0 {
+            return 100;
+        }
+        (opened * 100 / total) as u8
+    }
+
+    // Number of mines in the opened mine cells
+    pub fn get_exploded_count(&self) -> usize {
+        self.cells.iter()
+            .filter(|cell| cell.is_open())
+            .map(|cell| cell.get_mine_count() as usize)
+            .sum()
+    }
+
+    pub fn set_hinted_cell(&mut self, index: Option<usize>, is_mine: bool) {
+        if let Some(hinted_index) = self.hinted_cell_index {
+            self.cells[hinted_index].set_hint(None);
+        }
+        if let Some(index) = index {
+            self.cells[index].set_hint(Some(is_mine));
+        }
+        self.hinted_cell_index = index;
     }
 
-    pub fn select_active_cell(&mut self) {
-        self.cells[self.selected_cell_index].open();
-        if self.cells[self.selected_cell_index].get_value() == 0 {
-            self.open_adjacent_cells(self.selected_cell_index);
+    pub fn set_mine_probabilities(&mut self, probabilities: Option<Vec<Option<f64>>>) {
+        for (i, cell) in self.cells.iter_mut().enumerate() {
+            cell.set_mine_probability(probabilities.as_ref().and_then(|probabilities| probabilities[i]));
         }
     }
 
+    pub fn clear_hinted_cell(&mut self) {
+        self.set_hinted_cell(None, false);
+    }
+
     pub fn get_bomb_count(&self) -> usize {
         self.bomb_count
     }
@@ -186,16 +406,6 @@ impl Board {
         self.flag_count
     }
 
-    fn get_open_cell_count(&self) -> usize {
-        let mut open_cell_count: usize = 0;
-        for cell in self.cells.iter() {
-            if cell.is_open() {
-                open_cell_count += 1;
-            }
-        }
-        open_cell_count
-    }
-
     fn open_adjacent_cells(&mut self, current_index: usize) {
         let indices = self.get_cell_neighbors_indices(current_index as i16);
 
@@ -211,18 +421,26 @@ impl Board {
         }
     }
 
-    fn get_cell_neighbors_indices(&self, index: i16) -> Vec<usize> {
-        let mut neighbors = vec![];
-        let pos: (i16, i16) = self.get_pos_from_index(index as i16);
-        for j in 0..3 {
-            for k in 0..3 {
-                let neighbor_pos: (i16, i16) = (pos.0 + (k-1), pos.1 + (j-1));
-                let neighbor_index = self.get_index_from_pos(neighbor_pos.0, neighbor_pos.1);
-                if neighbor_index.is_some() == true && neighbor_index.unwrap() != index as usize {
-                    neighbors.push(neighbor_index.unwrap());
+    // Cells of the same layer at most radius cells away in both directions, the cell itself included
+    pub fn get_area_indices(&self, index: usize, radius: i16) -> Vec<usize> {
+        let (x, y) = self.topology.get_pos_from_index(index as i16);
+        let z = self.topology.get_layer_from_index(index) as i16;
+        let mut indices = vec![];
+        for dy in -radius..=radius {
+            for dx in -radius..=radius {
+                if let Some(area_index) = self.topology.get_index_from_layer_pos(x + dx, y + dy, z) {
+                    if !indices.contains(&area_index) && !self.cells[area_index].is_disabled() {
+                        indices.push(area_index);
+                    }
                 }
             }
         }
+        indices
+    }
+
+    pub fn get_cell_neighbors_indices(&self, index: i16) -> Vec<usize> {
+        let mut neighbors = self.topology.get_neighbors(index as usize, self.neighborhood);
+        neighbors.retain(|&neighbor| !self.cells[neighbor].is_disabled());
         neighbors
     }
 }
@@ -260,6 +478,18 @@ mod tests {
         assert_eq!(board.get_bomb_count(), 60);
     }
 
+    #[test]
+    fn test_seeded_board(){
+        let mut board: Board = Board::new();
+        let mut other_board: Board = Board::new();
+        board.initiate_seeded_board(Difficulty::Medium, 42);
+        other_board.initiate_seeded_board(Difficulty::Medium, 42);
+
+        let bombs: Vec<bool> = board.get_cells().iter().map(|cell| cell.is_bomb()).collect();
+        let other_bombs: Vec<bool> = other_board.get_cells().iter().map(|cell| cell.is_bomb()).collect();
+        assert_eq!(bombs, other_bombs);
+    }
+
     #[test]
     fn test_change_active_cell(){
         let mut board: Board = Board::new();
@@ -279,7 +509,7 @@ mod tests {
 
         // Test go down one row
         board.change_active_cell(InputEvent::Navigation(Direction::Down));
-        assert_eq!(board.selected_cell_index, board.board_width);
+        assert_eq!(board.selected_cell_index, board.get_board_width());
 
         // Test go back up one row
         board.change_active_cell(InputEvent::Navigation(Direction::Up));
@@ -294,6 +524,204 @@ mod tests {
         assert_eq!(board.selected_cell_index, 0);
     }
 
+    #[test]
+    fn test_exploded_mines_count_as_found(){
+        let mut board: Board = Board::new();
+        board.initiate_board_from_layout(3, 1, &[0]);
+
+        assert_eq!(board.open_cell(board.get_selected_cell_index()), 1);
+        assert_eq!(board.open_cell(board.get_selected_cell_index()), 0);
+        assert_eq!(board.get_exploded_count(), 1);
+        assert!(!board.is_all_safe_cells_open());
+
+        board.open_cell(2);
+        assert!(board.is_all_safe_cells_open());
+    }
+
+    #[test]
+    fn test_chord_cell(){
+        let mut board: Board = Board::new();
+        board.initiate_board_from_layout(3, 2, &[0, 2]);
+        board.open_cell(1);
+        board.change_active_cell(InputEvent::Navigation(Direction::Right));
+
+        // Not enough flags around the 2 yet
+        assert_eq!(board.chord_cell(board.get_selected_cell_index()), 0);
+        assert!(!board.get_cells()[4].is_open());
+
+        board.change_active_cell(InputEvent::Navigation(Direction::Left));
+        board.toggle_cell_flag(board.get_selected_cell_index());
+        board.change_active_cell(InputEvent::Navigation(Direction::Right));
+        board.change_active_cell(InputEvent::Navigation(Direction::Right));
+        board.toggle_cell_flag(board.get_selected_cell_index());
+        board.change_active_cell(InputEvent::Navigation(Direction::Left));
+        assert_eq!(board.chord_cell(board.get_selected_cell_index()), 0);
+        assert!(board.get_cells()[3].is_open() && board.get_cells()[4].is_open() && board.get_cells()[5].is_open());
+
+        // A wrong flag makes the chord open a mine
+        let mut board: Board = Board::new();
+        board.initiate_board_from_layout(3, 1, &[0]);
+        board.open_cell(1);
+        board.change_active_cell(InputEvent::Navigation(Direction::Right));
+        board.change_active_cell(InputEvent::Navigation(Direction::Right));
+        board.toggle_cell_flag(board.get_selected_cell_index());
+        board.change_active_cell(InputEvent::Navigation(Direction::Left));
+        assert_eq!(board.chord_cell(board.get_selected_cell_index()), 1);
+    }
+
+    #[test]
+    fn test_multi_mine_cells(){
+        let mut board: Board = Board::new();
+        board.set_max_mines_per_cell(3);
+        board.initiate_seeded_board(Difficulty::Easy, 3);
+
+        let cells = board.get_cells();
+        assert_eq!(cells.iter().map(|cell| cell.get_mine_count() as usize).sum::<usize>(), 10);
+        assert!(cells.iter().all(|cell| cell.get_mine_count() <= 3));
+        for (i, cell) in cells.iter().enumerate() {
+            let value: i16 = board.get_cell_neighbors_indices(i as i16).iter()
+                .map(|&neighbor| cells[neighbor].get_mine_count() as i16)
+                .sum();
+            assert_eq!(cell.get_value(), value);
+        }
+
+        // Two mines on one cell, flags cycle through 1, 2 and 3 and back to none
+        board.initiate_board_from_layout(3, 1, &[2, 2]);
+        assert_eq!(board.get_bomb_count(), 2);
+        assert_eq!(board.get_cells()[1].get_value(), 2);
+        board.open_cell(1);
+        board.change_active_cell(InputEvent::Navigation(Direction::Right));
+        board.change_active_cell(InputEvent::Navigation(Direction::Right));
+        board.toggle_cell_flag(board.get_selected_cell_index());
+        assert_eq!(board.get_flag_count(), 1);
+
+        board.change_active_cell(InputEvent::Navigation(Direction::Left));
+        assert_eq!(board.chord_cell(board.get_selected_cell_index()), 0);
+        assert!(!board.get_cells()[0].is_open());
+
+        board.change_active_cell(InputEvent::Navigation(Direction::Right));
+        for _ in 0..3 {
+            board.toggle_cell_flag(board.get_selected_cell_index());
+        }
+        assert_eq!(board.get_flag_count(), 0);
+        board.toggle_cell_flag(board.get_selected_cell_index());
+        board.toggle_cell_flag(board.get_selected_cell_index());
+        assert_eq!(board.get_flag_count(), 2);
+
+        board.change_active_cell(InputEvent::Navigation(Direction::Left));
+        assert_eq!(board.chord_cell(board.get_selected_cell_index()), 0);
+        assert!(board.is_all_safe_cells_open());
+
+        // Every mine of an opened cell explodes
+        board.initiate_board_from_layout(3, 1, &[2, 2]);
+        assert_eq!(board.open_cell(2), 2);
+        assert_eq!(board.get_exploded_count(), 2);
+    }
+
+    #[test]
+    fn test_neighborhoods(){
+        let mut board: Board = Board::new();
+        board.initiate_board_from_layout(5, 5, &[]);
+        assert_eq!(board.get_cell_neighbors_indices(12).len(), 8);
+        assert_eq!(board.get_cell_neighbors_indices(0), vec![1, 5, 6]);
+
+        board.set_neighborhood(Neighborhood::Orthogonal);
+        assert_eq!(board.get_cell_neighbors_indices(12), vec![7, 11, 13, 17]);
+
+        board.set_neighborhood(Neighborhood::Knight);
+        assert_eq!(board.get_cell_neighbors_indices(0), vec![7, 11]);
+
+        board.set_neighborhood(Neighborhood::Radius2);
+        assert_eq!(board.get_cell_neighbors_indices(12).len(), 24);
+
+        // Values and the flood fill follow the rule
+        board.set_neighborhood(Neighborhood::Knight);
+        board.initiate_board_from_layout(5, 5, &[11]);
+        assert_eq!(board.get_cells()[0].get_value(), 1);
+        assert_eq!(board.get_cells()[1].get_value(), 0);
+        board.open_cell(12);
+        assert!(board.is_all_safe_cells_open());
+    }
+
+    #[test]
+    fn test_wrapping_board(){
+        let mut board: Board = Board::new();
+        board.set_wrap(true);
+        board.initiate_board_from_layout(4, 4, &[15]);
+
+        // The mine in the far corner touches the first cell across both edges
+        assert_eq!(board.get_cells()[0].get_value(), 1);
+        assert_eq!(board.get_cells()[3].get_value(), 1);
+        assert_eq!(board.get_cells()[5].get_value(), 0);
+
+        board.change_active_cell(InputEvent::Navigation(Direction::Left));
+        assert_eq!(board.get_selected_cell_index(), 3);
+        board.change_active_cell(InputEvent::Navigation(Direction::Up));
+        assert_eq!(board.get_selected_cell_index(), 15);
+
+        board.open_cell(5);
+        assert!(board.is_all_safe_cells_open());
+    }
+
+    #[test]
+    fn test_masked_board(){
+        let mut board: Board = Board::new();
+        board.set_mask(Some(Mask::parse("-..\n.-.\n...\n").unwrap()));
+        board.initiate_seeded_board(Difficulty::Hard, 4);
+
+        assert_eq!(board.get_board_width(), 3);
+        assert_eq!(board.get_bomb_count(), 1);
+        assert_eq!(board.get_selected_cell_index(), 1);
+        assert!(!board.get_cells()[0].is_bomb() && !board.get_cells()[4].is_bomb());
+        assert!(!board.get_cell_neighbors_indices(1).contains(&4));
+
+        // Navigation jumps over the holes
+        board.change_active_cell(InputEvent::Navigation(Direction::Down));
+        assert_eq!(board.get_selected_cell_index(), 7);
+        board.change_active_cell(InputEvent::Navigation(Direction::Left));
+        board.change_active_cell(InputEvent::Navigation(Direction::Up));
+        assert_eq!(board.get_selected_cell_index(), 3);
+        board.change_active_cell(InputEvent::Navigation(Direction::Up));
+        assert_eq!(board.get_selected_cell_index(), 3);
+
+        for i in 0..9 {
+            if !board.get_cells()[i].is_bomb() {
+                board.open_cell(i);
+            }
+        }
+        assert!(!board.get_cells()[0].is_open());
+        assert!(board.is_all_safe_cells_open());
+    }
+
+    #[test]
+    fn test_layered_board(){
+        let mut board: Board = Board::new();
+        board.set_depth(3);
+        board.initiate_seeded_board(Difficulty::Easy, 6);
+        assert_eq!(board.get_cells().len(), 81 * 3);
+        assert_eq!(board.get_bomb_count(), 30);
+
+        board.change_active_cell(InputEvent::Navigation(Direction::LayerDown));
+        board.change_active_cell(InputEvent::Navigation(Direction::LayerDown));
+        assert_eq!(board.get_selected_layer(), 2);
+        assert_eq!(board.get_selected_cell_index(), 162);
+
+        // Flood fills and the win check reach through the layers
+        let mut layered_board: Board = Board::new();
+        layered_board.set_depth(2);
+        layered_board.set_mask(Some(Mask::parse("..\n..\n").unwrap()));
+        layered_board.initiate_seeded_board(Difficulty::Easy, 1);
+        assert_eq!(layered_board.get_bomb_count(), 1);
+        let mine = layered_board.get_cells().iter().position(|cell| cell.is_bomb()).unwrap();
+        assert!(layered_board.get_cells().iter().all(|cell| cell.is_bomb() || cell.get_value() == 1));
+        for i in 0..8 {
+            if i != mine {
+                layered_board.open_cell(i);
+            }
+        }
+        assert!(layered_board.is_all_safe_cells_open());
+    }
+
     #[test]
     fn test_get_pos_from_index(){
         let mut board: Board = Board::new();
@@ -301,7 +729,7 @@ mod tests {
 
         assert_eq!(board.get_pos_from_index(0), (0, 0));
         assert_eq!(board.get_pos_from_index(1), (1, 0));
-        assert_eq!(board.get_pos_from_index(board.board_width as i16), (0, 1));
+        assert_eq!(board.get_pos_from_index(board.get_board_width() as i16), (0, 1));
         // TODO: Write more tests
     }
 
@@ -310,9 +738,9 @@ mod tests {
         let mut board: Board = Board::new();
         board.initiate_board(Difficulty::Easy);
 
-        assert_eq!(board.get_index_from_pos(0, 0).unwrap(), 0);
-        assert_eq!(board.get_index_from_pos(1, 0).unwrap(), 1);
-        assert_eq!(board.get_index_from_pos(0, 1).unwrap(), board.board_width);
+        assert_eq!(board.topology.get_index_from_pos(0, 0).unwrap(), 0);
+        assert_eq!(board.topology.get_index_from_pos(1, 0).unwrap(), 1);
+        assert_eq!(board.topology.get_index_from_pos(0, 1).unwrap(), board.get_board_width());
         // TODO: Write more tests
     }
 }
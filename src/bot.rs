@@ -0,0 +1,200 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    app::Difficulty,
+    board::Board,
+    input_listener::{InputEvent, Direction},
+    solver::Solver,
+};
+
+// How long a finished game stays on screen before the bot starts a new one
+const RESTART_DELAY: Duration = Duration::from_secs(3);
+
+/// Plays the game by sending the same input events as a human player.
+pub struct Bot {
+    difficulties: Vec<Difficulty>,
+    game_count: usize,
+    game_ended_at: Option<Instant>,
+    guess_count: u32,
+}
+
+pub struct BotMove {
+    target: usize,
+    action: InputEvent,
+    is_guess: bool,
+}
+
+impl Bot {
+    pub fn new() -> Self {
+        Self {
+            difficulties: vec![Difficulty::Easy, Difficulty::Medium, Difficulty::Hard],
+            game_count: 0,
+            game_ended_at: None,
+            guess_count: 0,
+        }
+    }
+
+    pub fn get_guess_count(&self) -> u32 {
+        self.guess_count
+    }
+
+    // The move is planned again from the cursor on every step, so the player can move the cursor or
+    // play in between. Returns None during a game when there is no cell left to play.
+    pub fn next_event(&mut self, board: &Board, is_playing: bool) -> Option<InputEvent> {
+        if !is_playing {
+            return self.next_game_event();
+        }
+        self.game_ended_at = None;
+
+        let bot_move = choose_move(board)?;
+        let route = get_route(board, board.get_selected_cell_index(), bot_move.target);
+        if let Some(event) = route.into_iter().next() {
+            return Some(event);
+        }
+        if bot_move.is_guess {
+            self.guess_count += 1;
+        }
+        Some(bot_move.action)
+    }
+
+    // Start the next game in the rotation once the last one has been shown for a while
+    fn next_game_event(&mut self) -> Option<InputEvent> {
+        let ended_at = *self.game_ended_at.get_or_insert_with(Instant::now);
+        if self.game_count > 0 && ended_at.elapsed() < RESTART_DELAY {
+            return None;
+        }
+
+        let difficulty = self.difficulties[self.game_count % self.difficulties.len()];
+        self.game_count += 1;
+        self.game_ended_at = None;
+        self.guess_count = 0;
+        Some(InputEvent::GameDifficulty(difficulty))
+    }
+}
+
+// Pick the cell to act on: a deduced safe cell, then an unflagged deduced mine, then the safest guess
+pub fn choose_move(board: &Board) -> Option<BotMove> {
+    let deductions = Solver::new(board).solve();
+    if let Some(deduction) = deductions.iter().find(|deduction| !deduction.is_mine()) {
+        return Some(BotMove { target: deduction.get_index(), action: InputEvent::Select, is_guess: false });
+    }
+
+    let cells = board.get_cells();
+    if let Some(deduction) = deductions.iter().find(|deduction| !cells[deduction.get_index()].is_flagged()) {
+        return Some(BotMove { target: deduction.get_index(), action: InputEvent::Flag, is_guess: false });
+    }
+
+    let probabilities = Solver::new(board).get_mine_probabilities();
+    probabilities.iter()
+        .enumerate()
+        .filter_map(|(i, probability)| probability.map(|probability| (i, probability)))
+        .filter(|(i, _)| !cells[*i].is_flagged())
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(i, _)| BotMove { target: i, action: InputEvent::Select, is_guess: true })
+}
+
+// Shortest way to move the cursor, found by searching the board since navigation jumps over holes
+pub fn get_route(board: &Board, from: usize, to: usize) -> Vec<InputEvent> {
+    let directions = [Direction::Right, Direction::Left, Direction::Down, Direction::Up, Direction::LayerUp, Direction::LayerDown];
+    let mut previous: HashMap<usize, (usize, Direction)> = HashMap::new();
+    let mut queue: VecDeque<usize> = VecDeque::from([from]);
+    while let Some(index) = queue.pop_front() {
+        if index == to {
+            break;
+        }
+        for direction in directions {
+            if let Some(next_index) = board.get_next_cell(index, direction) {
+                if next_index != from && !previous.contains_key(&next_index) {
+                    previous.insert(next_index, (index, direction));
+                    queue.push_back(next_index);
+                }
+            }
+        }
+    }
+
+    let mut route = vec![];
+    let mut index = to;
+    while let Some(&(previous_index, direction)) = previous.get(&index) {
+        route.push(InputEvent::Navigation(direction));
+        index = previous_index;
+    }
+    route.reverse();
+    route
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use crate::{Args, app::App};
+
+    #[test]
+    fn test_get_route(){
+        let mut board: Board = Board::new();
+        board.initiate_board(Difficulty::Easy);
+
+        let route = get_route(&board, 0, 2 + board.get_board_width());
+        assert!(route == vec![
+            InputEvent::Navigation(Direction::Right),
+            InputEvent::Navigation(Direction::Right),
+            InputEvent::Navigation(Direction::Down),
+        ]);
+        assert!(get_route(&board, 5, 5).is_empty());
+    }
+
+    #[test]
+    fn test_choose_move_prefers_safe_cells(){
+        let mut board: Board = Board::new();
+        board.initiate_board_from_layout(3, 1, &[0]);
+        board.open_cell(1);
+        board.open_cell(0);
+
+        let bot_move = choose_move(&board).unwrap();
+        assert_eq!(bot_move.target, 2);
+        assert!(bot_move.action == InputEvent::Select);
+        assert!(!bot_move.is_guess);
+    }
+
+    #[test]
+    fn test_bot_follows_the_cursor(){
+        let mut board: Board = Board::new();
+        board.initiate_board_from_layout(3, 1, &[0]);
+        board.open_cell(1);
+        board.open_cell(0);
+        let mut bot: Bot = Bot::new();
+        assert!(bot.next_event(&board, true) == Some(InputEvent::Navigation(Direction::Right)));
+
+        // The player moves the cursor onto the safe cell, so the bot opens it right away
+        board.change_active_cell(InputEvent::Navigation(Direction::Right));
+        board.change_active_cell(InputEvent::Navigation(Direction::Right));
+        assert!(bot.next_event(&board, true) == Some(InputEvent::Select));
+
+        // With every closed cell flagged there is nothing left to play
+        let mut board: Board = Board::new();
+        board.initiate_board_from_layout(2, 1, &[0]);
+        board.toggle_cell_flag(0);
+        board.toggle_cell_flag(1);
+        assert!(bot.next_event(&board, true).is_none());
+    }
+
+    #[test]
+    fn test_bot_finishes_game(){
+        let mut app: App = App::new(Args::parse_from(["minesweeper-tui", "--autoplay"]));
+        let mut bot: Bot = Bot::new();
+        app.handle_event(bot.next_event(app.get_board(), app.is_playing()).unwrap());
+        assert!(app.is_playing());
+
+        for _ in 0..10000 {
+            if !app.is_playing() {
+                break;
+            }
+            if let Some(event) = bot.next_event(app.get_board(), app.is_playing()) {
+                app.handle_event(event);
+            }
+        }
+        assert!(app.get_is_game_over() || app.get_is_victory());
+    }
+}
@@ -0,0 +1,281 @@
+use std::{
+    collections::VecDeque,
+    io::{self, BufReader},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+    time::{Duration, Instant},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    coop::Snapshot,
+    interop::ReplayAction,
+    net::{read_message, write_message, WRITE_TIMEOUT},
+    race::Rules,
+};
+
+// A broadcast game sends one JSON message per line to everyone watching it:
+//   viewer -> player  {"type":"watch","version":2}
+//   player -> viewer  {"type":"welcome","version":2,"rules":{...}} or {"type":"reject","reason":"..."}
+//   player -> viewer  {"type":"snapshot",...} the whole game, on joining and on every new game
+//   player -> viewer  {"type":"action","elapsed_ms":1520,"action":"open","index":12}
+// Viewers never send anything after the handshake, they can only watch. The rules of the welcome
+// carry the board settings, the board itself comes with the snapshots.
+pub const PROTOCOL_VERSION: u32 = 2;
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum Message {
+    Watch { version: u32 },
+    Welcome { version: u32, rules: Rules },
+    Reject { reason: String },
+    Snapshot(Snapshot),
+    Action { elapsed_ms: u64, action: ReplayAction, index: usize },
+}
+
+pub enum WatchEvent {
+    Snapshot(Snapshot),
+    // An action of the player and the time on their clock when they played it
+    Action(Duration, ReplayAction, usize),
+}
+
+// The sending side, it accepts viewers in the background for as long as the game runs
+pub struct Broadcast {
+    rx: Receiver<TcpStream>,
+    viewers: Vec<TcpStream>,
+}
+
+impl Broadcast {
+    pub fn start(listener: TcpListener, rules: Rules) -> Self {
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let (tx, rules) = (tx.clone(), rules.clone());
+                thread::spawn(move || accept_viewer(stream, rules, &tx));
+            }
+        });
+
+        Self {
+            rx,
+            viewers: vec![],
+        }
+    }
+
+    // Returns whether viewers joined since the last call, they need a snapshot of the game
+    pub fn poll(&mut self) -> bool {
+        let count = self.viewers.len();
+        self.viewers.extend(self.rx.try_iter());
+        self.viewers.len() > count
+    }
+
+    pub fn send_snapshot(&mut self, snapshot: Snapshot) {
+        self.send(&Message::Snapshot(snapshot));
+    }
+
+    pub fn send_action(&mut self, elapsed: Duration, action: ReplayAction, index: usize) {
+        self.send(&Message::Action { elapsed_ms: elapsed.as_millis() as u64, action, index });
+    }
+
+    // Viewers that can not be reached anymore are dropped
+    fn send(&mut self, message: &Message) {
+        self.viewers.retain(|stream| write_message(stream, message).is_ok());
+    }
+
+    pub fn get_viewer_count(&self) -> usize {
+        self.viewers.len()
+    }
+}
+
+fn accept_viewer(stream: TcpStream, rules: Rules, tx: &Sender<TcpStream>) {
+    let Ok(reader_stream) = stream.try_clone() else {
+        return;
+    };
+    if stream.set_write_timeout(Some(WRITE_TIMEOUT)).is_err() {
+        return;
+    }
+    let reply = match read_message(&mut BufReader::new(reader_stream)) {
+        Ok(Message::Watch { version }) if version == PROTOCOL_VERSION => {
+            if write_message(&stream, &Message::Welcome { version, rules }).is_ok() {
+                let _ = tx.send(stream);
+            }
+            return;
+        },
+        Ok(Message::Watch { version }) => Message::Reject {
+            reason: format!("Protocol version {} is not supported, the player uses version {}", version, PROTOCOL_VERSION),
+        },
+        _ => Message::Reject { reason: "Expected a watch message".to_string() },
+    };
+    let _ = write_message(&stream, &reply);
+}
+
+// The watching side, it holds every message back for the delay so the game can not be used to cheat
+pub struct Watch {
+    rules: Rules,
+    delay: Duration,
+    // Messages with the time they arrived, None once the connection is lost
+    rx: Receiver<(Instant, Option<Message>)>,
+    pending: VecDeque<(Instant, Option<Message>)>,
+    is_connected: bool,
+}
+
+impl Watch {
+    pub fn join<A: ToSocketAddrs>(address: A, delay: Duration) -> io::Result<Self> {
+        let stream = TcpStream::connect(address)?;
+        let mut reader = BufReader::new(stream);
+        write_message(reader.get_ref(), &Message::Watch { version: PROTOCOL_VERSION })?;
+
+        let rules = match read_message(&mut reader)? {
+            Message::Welcome { version, rules } if version == PROTOCOL_VERSION => rules,
+            Message::Welcome { version, .. } => return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                                                          format!("The player uses protocol version {}", version))),
+            Message::Reject { reason } => return Err(io::Error::new(io::ErrorKind::ConnectionRefused, reason)),
+            _ => return Err(io::Error::new(io::ErrorKind::InvalidData, "Expected a welcome message")),
+        };
+
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || loop {
+            match read_message(&mut reader) {
+                Ok(message) => {
+                    if tx.send((Instant::now(), Some(message))).is_err() {
+                        return;
+                    }
+                },
+                Err(error) if error.kind() == io::ErrorKind::InvalidData => { },
+                Err(_) => {
+                    let _ = tx.send((Instant::now(), None));
+                    return;
+                },
+            }
+        });
+
+        Ok(Self {
+            rules,
+            delay,
+            rx,
+            pending: VecDeque::new(),
+            is_connected: true,
+        })
+    }
+
+    // The events that arrived at least the delay ago
+    pub fn poll(&mut self) -> Vec<WatchEvent> {
+        self.pending.extend(self.rx.try_iter());
+
+        let mut events = vec![];
+        while let Some((received_at, _)) = self.pending.front() {
+            if received_at.elapsed() < self.delay {
+                break;
+            }
+            match self.pending.pop_front().and_then(|(_, message)| message) {
+                Some(Message::Snapshot(snapshot)) => events.push(WatchEvent::Snapshot(snapshot)),
+                Some(Message::Action { elapsed_ms, action, index }) => {
+                    events.push(WatchEvent::Action(Duration::from_millis(elapsed_ms), action, index));
+                },
+                Some(_) => { },
+                None => self.is_connected = false,
+            }
+        }
+        events
+    }
+
+    pub fn get_rules(&self) -> &Rules {
+        &self.rules
+    }
+
+    pub fn get_delay(&self) -> Duration {
+        self.delay
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.is_connected
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use crate::{Args, app::Difficulty};
+
+    fn start_broadcast() -> (Broadcast, std::net::SocketAddr) {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind expect");
+        let address = listener.local_addr().expect("address expect");
+        let args = Args::parse_from(["minesweeper-tui", "--lives", "3"]);
+        (Broadcast::start(listener, Rules::new(&args, Difficulty::Easy, 0)), address)
+    }
+
+    fn wait_for<F: FnMut() -> bool>(mut condition: F) {
+        let start = Instant::now();
+        while !condition() {
+            assert!(start.elapsed() < Duration::from_secs(5), "timed out");
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn test_broadcast(){
+        let (mut broadcast, address) = start_broadcast();
+        let mut watch = Watch::join(address, Duration::ZERO).expect("watch expect");
+        let mut args = Args::parse_from(["minesweeper-tui"]);
+        watch.get_rules().apply_to(&mut args);
+        assert_eq!(args.lives, 3);
+
+        wait_for(|| broadcast.poll());
+        assert_eq!(broadcast.get_viewer_count(), 1);
+        broadcast.send_snapshot(Snapshot::new(Difficulty::Medium, 7, Duration::from_secs(2), vec![3], vec![]));
+        broadcast.send_action(Duration::from_millis(2500), ReplayAction::Flag, 5);
+
+        let mut events = vec![];
+        wait_for(|| {
+            events.extend(watch.poll());
+            events.len() == 2
+        });
+        assert!(matches!(&events[0], WatchEvent::Snapshot(snapshot) if snapshot.get_seed() == 7 && snapshot.get_open() == [3]));
+        assert!(matches!(events[1], WatchEvent::Action(elapsed, ReplayAction::Flag, 5) if elapsed == Duration::from_millis(2500)));
+
+        // Viewers that leave are dropped on the next message
+        drop(watch);
+        wait_for(|| {
+            broadcast.send_action(Duration::ZERO, ReplayAction::Open, 0);
+            broadcast.get_viewer_count() == 0
+        });
+    }
+
+    #[test]
+    fn test_slow_viewer(){
+        let (mut broadcast, address) = start_broadcast();
+        let stream = TcpStream::connect(address).expect("connect expect");
+        write_message(&stream, &Message::Watch { version: PROTOCOL_VERSION }).expect("write expect");
+        wait_for(|| broadcast.poll());
+
+        // A viewer that stops reading is dropped instead of holding up the game
+        let open: Vec<usize> = (0..10_000).collect();
+        wait_for(|| {
+            broadcast.send_snapshot(Snapshot::new(Difficulty::Hard, 1, Duration::ZERO, open.clone(), vec![]));
+            broadcast.get_viewer_count() == 0
+        });
+    }
+
+    #[test]
+    fn test_watch_delay(){
+        let (mut broadcast, address) = start_broadcast();
+        let mut watch = Watch::join(address, Duration::from_millis(300)).expect("watch expect");
+        wait_for(|| broadcast.poll());
+        let sent_at = Instant::now();
+        broadcast.send_action(Duration::ZERO, ReplayAction::Open, 1);
+
+        let mut events = vec![];
+        wait_for(|| {
+            events.extend(watch.poll());
+            !events.is_empty()
+        });
+        assert!(sent_at.elapsed() >= Duration::from_millis(300));
+
+        drop(broadcast);
+        wait_for(|| {
+            watch.poll();
+            !watch.is_connected()
+        });
+    }
+}
@@ -10,16 +10,29 @@ use tui::{
 
 use crate::ui::Draw;
 
+// Glyphs for one to nine flags on a cell, a cell is drawn one character wide by default
+const FLAG_GLYPHS: [&str; 9] = ["F", "Ⅱ", "Ⅲ", "Ⅳ", "Ⅴ", "Ⅵ", "Ⅶ", "Ⅷ", "Ⅸ"];
+
+#[derive(Clone)]
 pub struct Cell {
     value: i16,
     is_open: bool,
-    is_bomb: bool,
-    is_flagged: bool,
+    mine_count: u8,
+    flag_count: u8,
     is_selected: bool,
+    // Holes in the board shape, never mines and never opened
+    is_disabled: bool,
+    // Set by a hint, true when the hinted cell is a mine
+    hint: Option<bool>,
+    mine_probability: Option<f64>,
 }
 
 impl<B: Backend> Draw<B> for Cell {
     fn draw(&self, frame: &mut Frame<B>, chunk: Rect, debug: bool) {
+        if self.is_disabled {
+            return;
+        }
+
         let border_color = self.get_border_color(debug);
 
         // code to actually draw a select box
@@ -27,7 +40,7 @@ impl<B: Backend> Draw<B> for Cell {
             .borders(Borders::ALL)
             .border_style(Style::default().fg(border_color));
 
-        if self.is_open || self.is_flagged {
+        if self.is_open || self.is_flagged() {
             let span = Span::styled(
                 self.get_cell_text(),
                 Style::default()
@@ -41,6 +54,22 @@ impl<B: Backend> Draw<B> for Cell {
 
             frame.render_widget(paragraph, chunk);
         }
+        else if let Some(probability) = self.mine_probability {
+            // Only show the percentage when it fits inside the borders
+            let text = if chunk.width >= 6 {
+                format!("{:.0}%", probability * 100.0)
+            }
+            else {
+                "".to_string()
+            };
+
+            let paragraph = Paragraph::new(Span::styled(text, Style::default().fg(Color::Black)))
+                .block(block)
+                .style(Style::default().bg(get_probability_color(probability)))
+                .alignment(Alignment::Center);
+
+            frame.render_widget(paragraph, chunk);
+        }
         else {
             frame.render_widget(block, chunk);
         }
@@ -52,43 +81,74 @@ impl Cell {
         Self {
             value: 0,
             is_open: false,
-            is_bomb: false,
-            is_flagged: false,
-            is_selected: false
+            mine_count: 0,
+            flag_count: 0,
+            is_selected: false,
+            is_disabled: false,
+            hint: None,
+            mine_probability: None,
         }
     }
 
     pub fn is_bomb(&self) -> bool{
-        self.is_bomb
+        self.mine_count > 0
+    }
+
+    pub fn get_mine_count(&self) -> u8 {
+        self.mine_count
+    }
+
+    pub fn add_mine(&mut self) {
+        self.mine_count += 1;
+    }
+
+    pub fn is_disabled(&self) -> bool {
+        self.is_disabled
     }
 
-    pub fn set_is_bomb(&mut self, is_bomb: bool) {
-        self.is_bomb = is_bomb;
+    pub fn set_disabled(&mut self) {
+        self.is_disabled = true;
     }
 
     pub fn is_flagged(&self) -> bool {
-        self.is_flagged
+        self.flag_count > 0
     }
 
-    pub fn toggle_is_flagged(&mut self) {
-        if self.is_open {
+    pub fn get_flag_count(&self) -> u8 {
+        self.flag_count
+    }
+
+    // Cycle through 1 to max_flags flags and back to none
+    pub fn cycle_flag(&mut self, max_flags: u8) {
+        if self.is_open || self.is_disabled {
             return
         }
 
-        self.is_flagged = !self.is_flagged;
+        self.flag_count = (self.flag_count + 1) % (max_flags + 1);
     }
 
     pub fn set_is_selected(&mut self, is_selected: bool){
         self.is_selected = is_selected;
     }
 
+    pub fn set_hint(&mut self, hint: Option<bool>){
+        self.hint = hint;
+    }
+
+    pub fn set_mine_probability(&mut self, mine_probability: Option<f64>){
+        self.mine_probability = mine_probability;
+    }
+
     pub fn open(&mut self) {
+        if self.is_disabled {
+            return;
+        }
         self.is_open = true;
-        self.is_flagged = false;
+        self.flag_count = 0;
     }
 
-    pub fn increment_value(&mut self) {
-        self.value += 1;
+    pub fn increase_value(&mut self, amount: i16) {
+        self.value += amount;
     }
 
     pub fn get_value(&self) -> i16 {
@@ -100,10 +160,10 @@ impl Cell {
     }
 
     fn get_cell_text(&self) -> String {
-        if self.is_flagged == true {
-            return "F".to_string();
+        if self.is_flagged() {
+            return FLAG_GLYPHS[(self.flag_count as usize - 1).min(FLAG_GLYPHS.len() - 1)].to_string();
         }
-        else if self.is_bomb == true {
+        else if self.is_bomb() {
             return "B".to_string();
         }
 
@@ -111,13 +171,16 @@ impl Cell {
     }
 
     fn get_border_color(&self, debug: bool) -> Color {
-        if self.is_bomb && debug {
+        if self.is_bomb() && debug {
             return Color::Red;
         }
+        else if let Some(is_mine) = self.hint {
+            return if is_mine { Color::Magenta } else { Color::LightGreen };
+        }
         else if self.is_selected {
             return Color::Cyan;
         }
-        else if self.is_open == false {
+        else if !self.is_open {
             return Color::Gray;
         }
 
@@ -125,23 +188,26 @@ impl Cell {
     }
 
     fn get_text_color(&self) -> Color {
-        if self.is_flagged == true {
-            return Color::Red;
-        }
-        else if self.is_bomb {
+        if self.is_flagged() || self.is_bomb() {
             return Color::Red;
         }
 
         match self.value {
-            1 => return Color::Blue,
-            2 => return Color::Yellow,
-            3 => return Color::LightRed,
-            4 => return Color::DarkGray,
-            5 => return Color::Red,
-            6 => return Color::Magenta,
-            7 => return Color::Magenta,
-            8 => return Color::Black,
-            _ => return Color::White,
+            1 => Color::Blue,
+            2 => Color::Yellow,
+            3 => Color::LightRed,
+            4 => Color::DarkGray,
+            5 => Color::Red,
+            6 => Color::Magenta,
+            7 => Color::Magenta,
+            8 => Color::Black,
+            _ => Color::White,
         }
     }
 }
+
+// Gradient from green for safe cells to red for certain mines
+fn get_probability_color(probability: f64) -> Color {
+    let probability = probability.clamp(0.0, 1.0);
+    Color::Rgb((220.0 * probability) as u8, (180.0 * (1.0 - probability)) as u8, 0)
+}
@@ -0,0 +1,475 @@
+use std::{
+    collections::BTreeMap,
+    io::{self, BufReader},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+    time::Duration,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app::Difficulty,
+    interop::ReplayAction,
+    net::{read_message, write_message, WRITE_TIMEOUT},
+    race::Rules,
+};
+
+// Players of a co-op game send one JSON message per line:
+//   client -> host  {"type":"join","version":2}
+//   host -> client  {"type":"welcome","version":2,"player":2,"rules":{...}} or {"type":"reject","reason":"..."}
+//   host -> client  {"type":"snapshot",...} the whole game, on joining, on a new game and on request
+//   client -> host  {"type":"action","action":"open","index":12} and {"type":"cursor","index":13}
+//   host -> client  {"type":"applied","seq":7,"player":2,"action":"open","index":12}
+//   host -> client  {"type":"cursors","cursors":[[0,4],[2,13]]}
+//   client -> host  {"type":"resync"} when an applied action was missed
+// Only the host changes the board, clients send their actions to it and apply them once the host
+// broadcasts them, so every board sees the actions in the same order. Every applied action has the
+// next sequence number and a snapshot carries the number of the last action it contains.
+pub const PROTOCOL_VERSION: u32 = 2;
+
+// Players playing at the same time, the host included
+const MAX_PLAYERS: usize = 8;
+
+// The game as the host sees it, enough for a client to rebuild the same board
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    seq: u64,
+    difficulty: Difficulty,
+    seed: u64,
+    elapsed_ms: u64,
+    open: Vec<usize>,
+    // A cell with several flags is listed once for every flag
+    flags: Vec<usize>,
+}
+
+impl Snapshot {
+    pub fn new(difficulty: Difficulty, seed: u64, elapsed: Duration, open: Vec<usize>, flags: Vec<usize>) -> Self {
+        Self {
+            seq: 0,
+            difficulty,
+            seed,
+            elapsed_ms: elapsed.as_millis() as u64,
+            open,
+            flags,
+        }
+    }
+
+    pub fn get_difficulty(&self) -> Difficulty {
+        self.difficulty
+    }
+
+    pub fn get_seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn get_elapsed(&self) -> Duration {
+        Duration::from_millis(self.elapsed_ms)
+    }
+
+    pub fn get_open(&self) -> &[usize] {
+        &self.open
+    }
+
+    pub fn get_flags(&self) -> &[usize] {
+        &self.flags
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum Message {
+    Join { version: u32 },
+    Welcome { version: u32, player: u8, rules: Rules },
+    Reject { reason: String },
+    Snapshot(Snapshot),
+    Action { action: ReplayAction, index: usize },
+    Cursor { index: usize },
+    Applied { seq: u64, player: u8, action: ReplayAction, index: usize },
+    Cursors { cursors: Vec<(u8, usize)> },
+    Resync,
+}
+
+enum Incoming {
+    Joined(TcpStream, BufReader<TcpStream>),
+    Message(u8, Message),
+    Left(u8),
+}
+
+pub enum CoopEvent {
+    // An action to apply, on the host only when it is still allowed
+    Action(u8, ReplayAction, usize),
+    // The host has to send a snapshot to a player, or to everyone
+    SnapshotWanted(Option<u8>),
+    Snapshot(Snapshot),
+}
+
+pub struct Coop {
+    is_host: bool,
+    player: u8,
+    rules: Rules,
+    tx: Sender<Incoming>,
+    rx: Receiver<Incoming>,
+    // Connections to the clients on the host, or to the host on a client
+    streams: BTreeMap<u8, TcpStream>,
+    next_player: u8,
+    // Number of the last applied action
+    seq: u64,
+    // A client that missed an action ignores the following ones until the next snapshot
+    is_resyncing: bool,
+    cursors: BTreeMap<u8, usize>,
+    is_connected: bool,
+}
+
+impl Coop {
+    // Accept players in the background for as long as the game runs
+    pub fn host(listener: TcpListener, rules: Rules) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let accept_tx = tx.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let tx = accept_tx.clone();
+                thread::spawn(move || accept_player(stream, &tx));
+            }
+        });
+
+        Self::new(true, 0, rules, tx, rx)
+    }
+
+    pub fn join<A: ToSocketAddrs>(address: A) -> io::Result<Self> {
+        let stream = TcpStream::connect(address)?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        write_message(&stream, &Message::Join { version: PROTOCOL_VERSION })?;
+
+        match read_message(&mut reader)? {
+            Message::Welcome { version, player, rules } if version == PROTOCOL_VERSION => {
+                let (tx, rx) = mpsc::channel();
+                spawn_reader(0, reader, tx.clone());
+                let mut coop = Self::new(false, player, rules, tx, rx);
+                coop.streams.insert(0, stream);
+                Ok(coop)
+            },
+            Message::Welcome { version, .. } => Err(io::Error::new(io::ErrorKind::InvalidData,
+                                                                   format!("The host uses protocol version {}", version))),
+            Message::Reject { reason } => Err(io::Error::new(io::ErrorKind::ConnectionRefused, reason)),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidData, "Expected a welcome message")),
+        }
+    }
+
+    fn new(is_host: bool, player: u8, rules: Rules, tx: Sender<Incoming>, rx: Receiver<Incoming>) -> Self {
+        Self {
+            is_host,
+            player,
+            rules,
+            tx,
+            rx,
+            streams: BTreeMap::new(),
+            next_player: 1,
+            seq: 0,
+            is_resyncing: false,
+            cursors: BTreeMap::new(),
+            is_connected: true,
+        }
+    }
+
+    // Handle everything the other players sent since the last call
+    pub fn poll(&mut self) -> Vec<CoopEvent> {
+        let mut events = vec![];
+        while let Ok(incoming) = self.rx.try_recv() {
+            match incoming {
+                Incoming::Joined(stream, reader) => self.add_player(stream, reader, &mut events),
+                Incoming::Message(player, message) if self.is_host => self.handle_client_message(player, message, &mut events),
+                Incoming::Message(_, message) => self.handle_host_message(message, &mut events),
+                Incoming::Left(player) => self.remove_player(player),
+            }
+        }
+        events
+    }
+
+    fn add_player(&mut self, stream: TcpStream, reader: BufReader<TcpStream>, events: &mut Vec<CoopEvent>) {
+        // Players are numbered from 0, the host, and numbers are not reused
+        if self.streams.len() + 1 >= MAX_PLAYERS || self.next_player == u8::MAX {
+            let _ = write_message(&stream, &Message::Reject { reason: "The game is full".to_string() });
+            return;
+        }
+
+        let player = self.next_player;
+        let welcome = Message::Welcome { version: PROTOCOL_VERSION, player, rules: self.rules.clone() };
+        if write_message(&stream, &welcome).is_err() {
+            return;
+        }
+        self.next_player += 1;
+        spawn_reader(player, reader, self.tx.clone());
+        self.streams.insert(player, stream);
+        events.push(CoopEvent::SnapshotWanted(Some(player)));
+    }
+
+    fn handle_client_message(&mut self, player: u8, message: Message, events: &mut Vec<CoopEvent>) {
+        match message {
+            Message::Action { action, index } => events.push(CoopEvent::Action(player, action, index)),
+            Message::Cursor { index } => {
+                self.cursors.insert(player, index);
+                self.send_cursors();
+            },
+            Message::Resync => events.push(CoopEvent::SnapshotWanted(Some(player))),
+            _ => { },
+        }
+    }
+
+    fn handle_host_message(&mut self, message: Message, events: &mut Vec<CoopEvent>) {
+        match message {
+            Message::Applied { seq, .. } if self.is_resyncing || seq <= self.seq => { },
+            Message::Applied { seq, .. } if seq != self.seq + 1 => {
+                self.is_resyncing = true;
+                self.send_to_host(&Message::Resync);
+            },
+            Message::Applied { seq, player, action, index } => {
+                self.seq = seq;
+                events.push(CoopEvent::Action(player, action, index));
+            },
+            Message::Snapshot(snapshot) => {
+                self.seq = snapshot.seq;
+                self.is_resyncing = false;
+                events.push(CoopEvent::Snapshot(snapshot));
+            },
+            Message::Cursors { cursors } => self.cursors = cursors.into_iter().collect(),
+            _ => { },
+        }
+    }
+
+    // Clients send their actions to the host, the host sends the actions it applied to everyone
+    pub fn send_action(&mut self, player: u8, action: ReplayAction, index: usize) {
+        if self.is_host {
+            self.seq += 1;
+            self.broadcast(&Message::Applied { seq: self.seq, player, action, index });
+        }
+        else {
+            self.send_to_host(&Message::Action { action, index });
+        }
+    }
+
+    pub fn send_snapshot(&mut self, player: Option<u8>, mut snapshot: Snapshot) {
+        snapshot.seq = self.seq;
+        let message = Message::Snapshot(snapshot);
+        match player {
+            Some(player) => {
+                if self.streams.get(&player).is_some_and(|stream| write_message(stream, &message).is_err()) {
+                    self.remove_player(player);
+                }
+            },
+            None => self.broadcast(&message),
+        }
+    }
+
+    pub fn set_cursor(&mut self, index: usize) {
+        self.cursors.insert(self.player, index);
+        if self.is_host {
+            self.send_cursors();
+        }
+        else {
+            self.send_to_host(&Message::Cursor { index });
+        }
+    }
+
+    fn send_cursors(&mut self) {
+        let cursors = self.cursors.iter().map(|(&player, &index)| (player, index)).collect();
+        self.broadcast(&Message::Cursors { cursors });
+    }
+
+    // A client that can not be reached in time is dropped right away
+    fn broadcast(&mut self, message: &Message) {
+        let unreachable: Vec<u8> = self.streams.iter()
+            .filter(|(_, stream)| write_message(*stream, message).is_err())
+            .map(|(&player, _)| player)
+            .collect();
+        for player in unreachable {
+            self.remove_player(player);
+        }
+    }
+
+    fn remove_player(&mut self, player: u8) {
+        self.streams.remove(&player);
+        self.cursors.remove(&player);
+        if self.is_host {
+            self.send_cursors();
+        }
+        else {
+            self.is_connected = false;
+        }
+    }
+
+    fn send_to_host(&mut self, message: &Message) {
+        let is_sent = self.streams.get(&0).is_some_and(|stream| write_message(stream, message).is_ok());
+        if !is_sent {
+            self.is_connected = false;
+        }
+    }
+
+    pub fn is_host(&self) -> bool {
+        self.is_host
+    }
+
+    pub fn get_player(&self) -> u8 {
+        self.player
+    }
+
+    pub fn get_rules(&self) -> &Rules {
+        &self.rules
+    }
+
+    pub fn get_cursors(&self) -> &BTreeMap<u8, usize> {
+        &self.cursors
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.is_connected
+    }
+}
+
+fn accept_player(stream: TcpStream, tx: &Sender<Incoming>) {
+    let Ok(reader_stream) = stream.try_clone() else {
+        return;
+    };
+    if stream.set_write_timeout(Some(WRITE_TIMEOUT)).is_err() {
+        return;
+    }
+    let mut reader = BufReader::new(reader_stream);
+    let reason = match read_message(&mut reader) {
+        Ok(Message::Join { version }) if version == PROTOCOL_VERSION => {
+            let _ = tx.send(Incoming::Joined(stream, reader));
+            return;
+        },
+        Ok(Message::Join { version }) => format!("Protocol version {} is not supported, the host uses version {}", version, PROTOCOL_VERSION),
+        _ => "Expected a join message".to_string(),
+    };
+    let _ = write_message(&stream, &Message::Reject { reason });
+}
+
+fn spawn_reader(player: u8, mut reader: BufReader<TcpStream>, tx: Sender<Incoming>) {
+    thread::spawn(move || loop {
+        match read_message(&mut reader) {
+            Ok(message) => {
+                if tx.send(Incoming::Message(player, message)).is_err() {
+                    return;
+                }
+            },
+            Err(error) if error.kind() == io::ErrorKind::InvalidData => { },
+            Err(_) => {
+                let _ = tx.send(Incoming::Left(player));
+                return;
+            },
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use std::time::Instant;
+    use crate::Args;
+
+    fn poll_until<F: FnMut(&CoopEvent) -> bool>(coop: &mut Coop, mut condition: F) -> CoopEvent {
+        let start = Instant::now();
+        loop {
+            if let Some(event) = coop.poll().into_iter().find(|event| condition(event)) {
+                return event;
+            }
+            assert!(start.elapsed() < Duration::from_secs(5), "timed out");
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    // The host welcomes new players while it polls
+    fn join(host: &mut Coop, address: std::net::SocketAddr) -> Coop {
+        let client = thread::spawn(move || Coop::join(address).expect("join expect"));
+        let event = poll_until(host, |event| matches!(event, CoopEvent::SnapshotWanted(Some(_))));
+        let mut client = client.join().expect("client expect");
+        let player = client.get_player();
+        assert!(matches!(event, CoopEvent::SnapshotWanted(Some(wanted)) if wanted == player));
+        host.send_snapshot(Some(player), Snapshot::new(Difficulty::Easy, 5, Duration::ZERO, vec![3], vec![]));
+        poll_until(&mut client, |event| matches!(event, CoopEvent::Snapshot(_)));
+        client
+    }
+
+    #[test]
+    fn test_coop(){
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind expect");
+        let address = listener.local_addr().expect("address expect");
+        let args = Args::parse_from(["minesweeper-tui"]);
+        let mut host = Coop::host(listener, Rules::new(&args, Difficulty::Easy, 5));
+
+        let mut first = join(&mut host, address);
+        let mut second = join(&mut host, address);
+        assert_eq!((first.get_player(), second.get_player()), (1, 2));
+        assert_eq!(first.get_rules().get_seed(), 5);
+
+        // An action goes through the host, which numbers it and sends it to everyone
+        first.send_action(1, ReplayAction::Flag, 7);
+        let event = poll_until(&mut host, |event| matches!(event, CoopEvent::Action(..)));
+        assert!(matches!(event, CoopEvent::Action(1, ReplayAction::Flag, 7)));
+        host.send_action(1, ReplayAction::Flag, 7);
+        let event = poll_until(&mut second, |event| matches!(event, CoopEvent::Action(..)));
+        assert!(matches!(event, CoopEvent::Action(1, ReplayAction::Flag, 7)));
+        assert_eq!(second.seq, 1);
+
+        // Cursors are shared with every player
+        second.set_cursor(9);
+        let start = Instant::now();
+        while first.get_cursors().get(&2) != Some(&9) {
+            assert!(start.elapsed() < Duration::from_secs(5), "timed out");
+            host.poll();
+            first.poll();
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        // A late joiner gets the sequence number of the snapshot
+        let third = join(&mut host, address);
+        assert_eq!(third.seq, 1);
+    }
+
+    #[test]
+    fn test_missed_action(){
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind expect");
+        let address = listener.local_addr().expect("address expect");
+        let args = Args::parse_from(["minesweeper-tui"]);
+        let mut host = Coop::host(listener, Rules::new(&args, Difficulty::Easy, 5));
+        let mut client = join(&mut host, address);
+
+        // Skipping a sequence number makes the client ask for a new snapshot
+        host.seq += 1;
+        host.send_action(0, ReplayAction::Open, 2);
+        let start = Instant::now();
+        while !client.is_resyncing {
+            assert!(start.elapsed() < Duration::from_secs(5), "timed out");
+            assert!(client.poll().iter().all(|event| !matches!(event, CoopEvent::Action(..))));
+            thread::sleep(Duration::from_millis(10));
+        }
+        poll_until(&mut host, |event| matches!(event, CoopEvent::SnapshotWanted(Some(1))));
+
+        host.send_snapshot(Some(1), Snapshot::new(Difficulty::Easy, 5, Duration::ZERO, vec![2], vec![]));
+        poll_until(&mut client, |event| matches!(event, CoopEvent::Snapshot(_)));
+        assert_eq!(client.seq, 2);
+        assert!(!client.is_resyncing);
+    }
+
+    #[test]
+    fn test_slow_player(){
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind expect");
+        let address = listener.local_addr().expect("address expect");
+        let args = Args::parse_from(["minesweeper-tui"]);
+        let mut host = Coop::host(listener, Rules::new(&args, Difficulty::Easy, 5));
+        let stream = TcpStream::connect(address).expect("connect expect");
+        write_message(&stream, &Message::Join { version: PROTOCOL_VERSION }).expect("write expect");
+        poll_until(&mut host, |event| matches!(event, CoopEvent::SnapshotWanted(Some(_))));
+
+        // A player that stops reading is dropped instead of holding up the game
+        let open: Vec<usize> = (0..10_000).collect();
+        let start = Instant::now();
+        while !host.streams.is_empty() {
+            assert!(start.elapsed() < Duration::from_secs(5), "timed out");
+            host.send_snapshot(None, Snapshot::new(Difficulty::Easy, 5, Duration::ZERO, open.clone(), vec![]));
+        }
+        assert!(host.get_cursors().keys().all(|&player| player == host.get_player()));
+    }
+}
@@ -0,0 +1,34 @@
+use chrono::Local;
+
+use crate::{
+    app::Difficulty,
+    neighborhood::Neighborhood,
+};
+
+pub fn get_today() -> String {
+    Local::now().format("%Y-%m-%d").to_string()
+}
+
+// FNV-1a over the date, difficulty and neighbourhood rule, so every build derives the same seed for a day
+pub fn get_daily_seed(date: &str, difficulty: Difficulty, neighborhood: Neighborhood) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in format!("{}/{}/{}", date, difficulty.get_name(), neighborhood.get_name()).bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_daily_seed(){
+        let seed = get_daily_seed("2024-05-06", Difficulty::Easy, Neighborhood::Moore);
+        assert_eq!(seed, get_daily_seed("2024-05-06", Difficulty::Easy, Neighborhood::Moore));
+        assert_ne!(seed, get_daily_seed("2024-05-07", Difficulty::Easy, Neighborhood::Moore));
+        assert_ne!(seed, get_daily_seed("2024-05-06", Difficulty::Hard, Neighborhood::Moore));
+        assert_ne!(seed, get_daily_seed("2024-05-06", Difficulty::Easy, Neighborhood::Knight));
+    }
+}
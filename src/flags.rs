@@ -0,0 +1,173 @@
+use std::collections::BTreeMap;
+
+use crate::board::Board;
+
+// Minesweeper Flags: two players take turns opening cells. Opening a mine claims it for a point
+// and another turn, opening a safe cell passes the turn. The first player with more than half of
+// the mines wins. Once per game a player who is behind can bomb a 5x5 area, which opens all of
+// it and claims the mines in it.
+pub const PLAYER_COUNT: usize = 2;
+
+// The bomb opens the cells up to two cells away from its centre
+pub const BOMB_RADIUS: i16 = 2;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FlagsMove {
+    Reveal(usize),
+    Bomb(usize),
+}
+
+pub struct FlagsGame {
+    // Player whose turn it is
+    turn: usize,
+    scores: [u32; PLAYER_COUNT],
+    // Player that claimed each opened mine
+    claims: BTreeMap<usize, usize>,
+    used_bombs: [bool; PLAYER_COUNT],
+    mine_count: u32,
+}
+
+impl FlagsGame {
+    pub fn new(board: &Board) -> Self {
+        Self {
+            turn: 0,
+            scores: [0; PLAYER_COUNT],
+            claims: BTreeMap::new(),
+            used_bombs: [false; PLAYER_COUNT],
+            mine_count: board.get_bomb_count() as u32,
+        }
+    }
+
+    // Play a move for the player whose turn it is, illegal moves leave the game as it was
+    pub fn play(&mut self, board: &mut Board, flags_move: FlagsMove) -> Result<(), &'static str> {
+        if self.is_over() {
+            return Err("The game is over");
+        }
+
+        match flags_move {
+            FlagsMove::Reveal(index) => {
+                let cell = &board.get_cells()[index];
+                if cell.is_open() {
+                    return Err("That cell is already open");
+                }
+                if cell.is_disabled() {
+                    return Err("That cell is not part of the board");
+                }
+
+                // Finding a mine earns another turn
+                if !self.open(board, index) {
+                    self.turn = (self.turn + 1) % PLAYER_COUNT;
+                }
+            },
+            FlagsMove::Bomb(index) => {
+                if !self.can_bomb(self.turn) {
+                    return Err("The bomb can only be used once, by the player who is behind");
+                }
+
+                self.used_bombs[self.turn] = true;
+                for area_index in board.get_area_indices(index, BOMB_RADIUS) {
+                    self.open(board, area_index);
+                }
+                self.turn = (self.turn + 1) % PLAYER_COUNT;
+            },
+        }
+        Ok(())
+    }
+
+    // Returns whether the cell held a mine
+    fn open(&mut self, board: &mut Board, index: usize) -> bool {
+        if board.get_cells()[index].is_open() {
+            return false;
+        }
+
+        board.open_cell(index);
+        let mine_count = board.get_cells()[index].get_mine_count() as u32;
+        if mine_count == 0 {
+            return false;
+        }
+        self.scores[self.turn] += mine_count;
+        self.claims.insert(index, self.turn);
+        true
+    }
+
+    pub fn can_bomb(&self, player: usize) -> bool {
+        let best_other_score = (0..PLAYER_COUNT).filter(|&other| other != player).map(|other| self.scores[other]).max().unwrap_or(0);
+        !self.used_bombs[player] && self.scores[player] < best_other_score
+    }
+
+    pub fn get_winner(&self) -> Option<usize> {
+        (0..PLAYER_COUNT).find(|&player| self.scores[player] * 2 > self.mine_count)
+    }
+
+    // A game can also end in a draw when every mine is claimed
+    pub fn is_over(&self) -> bool {
+        self.get_winner().is_some() || self.scores.iter().sum::<u32>() >= self.mine_count
+    }
+
+    pub fn get_turn(&self) -> usize {
+        self.turn
+    }
+
+    pub fn get_score(&self, player: usize) -> u32 {
+        self.scores[player]
+    }
+
+    pub fn get_claims(&self) -> &BTreeMap<usize, usize> {
+        &self.claims
+    }
+
+    pub fn get_mine_count(&self) -> u32 {
+        self.mine_count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_turns(){
+        let mut board: Board = Board::new();
+        board.initiate_board_from_layout(5, 1, &[0, 1, 4]);
+        let mut flags = FlagsGame::new(&board);
+
+        // A mine scores and keeps the turn, a safe cell passes it
+        flags.play(&mut board, FlagsMove::Reveal(0)).expect("reveal expect");
+        assert_eq!((flags.get_turn(), flags.get_score(0)), (0, 1));
+        flags.play(&mut board, FlagsMove::Reveal(2)).expect("reveal expect");
+        assert_eq!(flags.get_turn(), 1);
+        assert!(flags.play(&mut board, FlagsMove::Reveal(2)).is_err());
+        assert_eq!(flags.get_turn(), 1);
+
+        // Two of the three mines win the game
+        flags.play(&mut board, FlagsMove::Reveal(4)).expect("reveal expect");
+        assert_eq!(flags.get_winner(), None);
+        flags.play(&mut board, FlagsMove::Reveal(1)).expect("reveal expect");
+        assert_eq!(flags.get_claims().get(&1), Some(&1));
+        assert_eq!(flags.get_winner(), Some(1));
+        assert!(flags.is_over());
+        assert!(flags.play(&mut board, FlagsMove::Reveal(3)).is_err());
+    }
+
+    #[test]
+    fn test_bomb(){
+        let mut board: Board = Board::new();
+        board.initiate_board_from_layout(7, 7, &[0, 1, 2, 24, 25, 48]);
+        let mut flags = FlagsGame::new(&board);
+        assert!(!flags.can_bomb(0));
+        assert!(flags.play(&mut board, FlagsMove::Bomb(24)).is_err());
+
+        flags.play(&mut board, FlagsMove::Reveal(0)).expect("reveal expect");
+        flags.play(&mut board, FlagsMove::Reveal(6)).expect("reveal expect");
+        assert_eq!(flags.get_turn(), 1);
+        assert!(flags.can_bomb(1));
+
+        // The 5x5 area around the centre holds two mines
+        flags.play(&mut board, FlagsMove::Bomb(24)).expect("bomb expect");
+        assert_eq!(flags.get_score(1), 2);
+        assert!(board.get_cells()[8].is_open() && board.get_cells()[40].is_open());
+        assert!(!board.get_cells()[48].is_open());
+        assert_eq!(flags.get_turn(), 0);
+        assert!(!flags.can_bomb(1));
+    }
+}
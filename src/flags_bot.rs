@@ -0,0 +1,172 @@
+use std::{
+    sync::mpsc::{self, Receiver},
+    thread,
+    time::{Duration, Instant},
+};
+use rand::seq::SliceRandom;
+
+use crate::{
+    board::Board,
+    flags::{FlagsGame, FlagsMove, BOMB_RADIUS},
+    solver::Solver,
+};
+
+// The computer always plays the second player
+pub const BOT_PLAYER: usize = 1;
+
+// How much the bot dislikes giving the other player a number to work with, against finding a mine
+const INFO_WEIGHT: f64 = 0.3;
+
+// Mines the bot expects in a 5x5 area before it uses its bomb there
+const BOMB_THRESHOLD: f64 = 2.5;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, clap::ValueEnum)]
+pub enum Strength {
+    // Opens random closed cells
+    Random,
+    // Opens the mines the solver can prove, random cells otherwise
+    Deduction,
+    // Opens the likeliest mine while avoiding cells that would show a useful number to the other player
+    Probability,
+}
+
+/// Plays the second player of a Flags game, it thinks on its own thread so the ui keeps running.
+pub struct FlagsBot {
+    strength: Strength,
+    delay: Duration,
+    // The move being worked out and when the bot started thinking about it
+    thinking: Option<(Receiver<FlagsMove>, Instant)>,
+}
+
+impl FlagsBot {
+    pub fn new(strength: Strength, delay: Duration) -> Self {
+        Self {
+            strength,
+            delay,
+            thinking: None,
+        }
+    }
+
+    // The move of the bot once it is its turn, it was worked out and the thinking delay passed
+    pub fn next_move(&mut self, board: &Board, flags: &FlagsGame) -> Option<FlagsMove> {
+        if flags.get_turn() != BOT_PLAYER || flags.is_over() {
+            self.thinking = None;
+            return None;
+        }
+
+        let (rx, started_at) = self.thinking.get_or_insert_with(|| {
+            let (tx, rx) = mpsc::channel();
+            let (board, strength, can_bomb) = (board.clone(), self.strength, flags.can_bomb(BOT_PLAYER));
+            thread::spawn(move || {
+                let _ = tx.send(choose_move(&board, strength, can_bomb));
+            });
+            (rx, Instant::now())
+        });
+        if started_at.elapsed() < self.delay {
+            return None;
+        }
+
+        let flags_move = rx.try_recv().ok()?;
+        self.thinking = None;
+        Some(flags_move)
+    }
+}
+
+pub fn choose_move(board: &Board, strength: Strength, can_bomb: bool) -> FlagsMove {
+    let closed: Vec<usize> = (0..board.get_cells().len())
+        .filter(|&i| !board.get_cells()[i].is_open() && !board.get_cells()[i].is_disabled())
+        .collect();
+    let random_move = || FlagsMove::Reveal(*closed.choose(&mut rand::thread_rng()).expect("closed cell expect"));
+
+    match strength {
+        Strength::Random => random_move(),
+        Strength::Deduction => {
+            let deductions = Solver::new(board).solve();
+            if let Some(mine) = deductions.iter().find(|deduction| deduction.is_mine()) {
+                return FlagsMove::Reveal(mine.get_index());
+            }
+
+            // Cells that are known to be safe would only pass the turn
+            let unknown: Vec<usize> = closed.iter()
+                .copied()
+                .filter(|&i| !deductions.iter().any(|deduction| deduction.get_index() == i))
+                .collect();
+            match unknown.choose(&mut rand::thread_rng()) {
+                Some(&index) => FlagsMove::Reveal(index),
+                None => random_move(),
+            }
+        },
+        Strength::Probability => choose_probable_move(board, &closed, can_bomb).unwrap_or_else(random_move),
+    }
+}
+
+fn choose_probable_move(board: &Board, closed: &[usize], can_bomb: bool) -> Option<FlagsMove> {
+    let probabilities = Solver::new(board).get_mine_probabilities();
+
+    if can_bomb {
+        let best_area = (0..probabilities.len())
+            .map(|center| {
+                let expected_mines: f64 = board.get_area_indices(center, BOMB_RADIUS).iter().filter_map(|&i| probabilities[i]).sum();
+                (center, expected_mines)
+            })
+            .max_by(|(_, a), (_, b)| a.total_cmp(b));
+        if let Some((center, expected_mines)) = best_area {
+            if expected_mines >= BOMB_THRESHOLD {
+                return Some(FlagsMove::Bomb(center));
+            }
+        }
+    }
+
+    closed.iter()
+        .filter_map(|&i| probabilities[i].map(|probability| (i, probability - (1.0 - probability) * INFO_WEIGHT * get_revealed_info(board, i))))
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(index, _)| FlagsMove::Reveal(index))
+}
+
+// Share of the neighbours of a cell that are still closed, the number of a safe cell tells the
+// other player the most when little around it is known
+fn get_revealed_info(board: &Board, index: usize) -> f64 {
+    let neighbors = board.get_cell_neighbors_indices(index as i16);
+    if neighbors.is_empty() {
+        return 0.0;
+    }
+    let closed_count = neighbors.iter().filter(|&&neighbor| !board.get_cells()[neighbor].is_open()).count();
+    closed_count as f64 / neighbors.len() as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deduction_finds_mines(){
+        let mut board: Board = Board::new();
+        board.initiate_board_from_layout(3, 3, &[8]);
+        board.open_cell(0);
+        assert!(!board.get_cells()[8].is_open());
+
+        for strength in [Strength::Random, Strength::Deduction, Strength::Probability] {
+            assert_eq!(choose_move(&board, strength, false), FlagsMove::Reveal(8));
+        }
+    }
+
+    #[test]
+    fn test_bot_waits_for_its_turn(){
+        let mut board: Board = Board::new();
+        board.initiate_board_from_layout(4, 1, &[0, 3]);
+        let mut flags = FlagsGame::new(&board);
+        let mut bot = FlagsBot::new(Strength::Random, Duration::ZERO);
+        assert_eq!(bot.next_move(&board, &flags), None);
+
+        flags.play(&mut board, FlagsMove::Reveal(1)).expect("reveal expect");
+        let start = Instant::now();
+        let flags_move = loop {
+            if let Some(flags_move) = bot.next_move(&board, &flags) {
+                break flags_move;
+            }
+            assert!(start.elapsed() < Duration::from_secs(5), "timed out");
+            thread::sleep(Duration::from_millis(10));
+        };
+        assert!(flags.play(&mut board, flags_move).is_ok());
+    }
+}
@@ -7,12 +7,18 @@ use crossterm::event::{self, Event, KeyEvent, KeyCode, KeyModifiers};
 
 use crate::app::Difficulty;
 
-#[derive(PartialEq, Eq)]
+#[derive(PartialEq, Eq, Clone, Copy)]
 pub enum Direction {
     Up,
     Down,
     Left,
-    Right
+    Right,
+    UpLeft,
+    UpRight,
+    DownLeft,
+    DownRight,
+    LayerUp,
+    LayerDown,
 }
 
 #[derive(PartialEq, Eq)]
@@ -22,46 +28,109 @@ pub enum InputEvent {
     GameDifficulty(Difficulty),
     Select,
     Flag,
+    Chord,
+    Bomb,
+    Hint,
+    ToggleProbabilities,
+    Export,
+    Scoreboard,
+    // An event for one of the boards of a split screen game, counted from 0
+    Player(usize, Box<InputEvent>),
+    // The terminal was resized to the given columns and rows
+    Resize(u16, u16),
     Tick,
     Quit
 }
 
 pub struct InputListener<'a> {
     rx:  &'a mpsc::Receiver<InputEvent>,
+    timeout: Duration,
+    split_screen: bool,
 }
 
 impl<'a> InputListener<'a> {
-    pub fn new(rx: &'a mpsc::Receiver<InputEvent>,) -> Self {
+    pub fn new(rx: &'a mpsc::Receiver<InputEvent>, timeout: Duration, split_screen: bool) -> Self {
         InputListener {
-            rx
+            rx,
+            timeout,
+            split_screen,
         }
     }
 
     pub fn handle_input(&self) -> InputEvent {
-        let recv = self.rx.recv_timeout(Duration::from_millis(300));
+        let recv = self.rx.recv_timeout(self.timeout);
         if recv.is_err(){
             return InputEvent::Tick;
         }
 
         match recv.unwrap() {
+            InputEvent::Input(input) if self.split_screen => get_split_screen_event(input),
+            InputEvent::Flag if self.split_screen => InputEvent::Player(0, Box::new(InputEvent::Flag)),
             InputEvent::Input(input) => match input {
-                KeyEvent{ code: KeyCode::Char('d'), modifiers: KeyModifiers::NONE, ..} => return InputEvent::Navigation(Direction::Right),
-                KeyEvent{ code: KeyCode::Char('a'), modifiers: KeyModifiers::NONE, ..} => return InputEvent::Navigation(Direction::Left),
-                KeyEvent{ code: KeyCode::Char('w'), modifiers: KeyModifiers::NONE, ..} => return InputEvent::Navigation(Direction::Up),
-                KeyEvent{ code: KeyCode::Char('s'), modifiers: KeyModifiers::NONE, ..} => return InputEvent::Navigation(Direction::Down),
-                KeyEvent{ code: KeyCode::Char('e'), modifiers: KeyModifiers::NONE, ..} => return InputEvent::GameDifficulty(Difficulty::Easy),
-                KeyEvent{ code: KeyCode::Char('m'), modifiers: KeyModifiers::NONE, ..} => return InputEvent::GameDifficulty(Difficulty::Medium),
-                KeyEvent{ code: KeyCode::Char('h'), modifiers: KeyModifiers::NONE, ..} => return InputEvent::GameDifficulty(Difficulty::Hard),
-                KeyEvent{ code: KeyCode::Enter, modifiers: KeyModifiers::NONE, ..} => return InputEvent::Select,
+                KeyEvent{ code: KeyCode::Char('d'), modifiers: KeyModifiers::NONE, ..} => InputEvent::Navigation(Direction::Right),
+                KeyEvent{ code: KeyCode::Char('a'), modifiers: KeyModifiers::NONE, ..} => InputEvent::Navigation(Direction::Left),
+                KeyEvent{ code: KeyCode::Char('w'), modifiers: KeyModifiers::NONE, ..} => InputEvent::Navigation(Direction::Up),
+                KeyEvent{ code: KeyCode::Char('s'), modifiers: KeyModifiers::NONE, ..} => InputEvent::Navigation(Direction::Down),
+                KeyEvent{ code: KeyCode::Char('['), modifiers: KeyModifiers::NONE, ..} => InputEvent::Navigation(Direction::LayerUp),
+                KeyEvent{ code: KeyCode::Char(']'), modifiers: KeyModifiers::NONE, ..} => InputEvent::Navigation(Direction::LayerDown),
+                KeyEvent{ code: KeyCode::Char('y'), modifiers: KeyModifiers::NONE, ..} => InputEvent::Navigation(Direction::UpLeft),
+                KeyEvent{ code: KeyCode::Char('u'), modifiers: KeyModifiers::NONE, ..} => InputEvent::Navigation(Direction::UpRight),
+                KeyEvent{ code: KeyCode::Char('b'), modifiers: KeyModifiers::NONE, ..} => InputEvent::Navigation(Direction::DownLeft),
+                KeyEvent{ code: KeyCode::Char('n'), modifiers: KeyModifiers::NONE, ..} => InputEvent::Navigation(Direction::DownRight),
+                KeyEvent{ code: KeyCode::Char('e'), modifiers: KeyModifiers::NONE, ..} => InputEvent::GameDifficulty(Difficulty::Easy),
+                KeyEvent{ code: KeyCode::Char('m'), modifiers: KeyModifiers::NONE, ..} => InputEvent::GameDifficulty(Difficulty::Medium),
+                KeyEvent{ code: KeyCode::Char('h'), modifiers: KeyModifiers::NONE, ..} => InputEvent::GameDifficulty(Difficulty::Hard),
+                KeyEvent{ code: KeyCode::Char('c'), modifiers: KeyModifiers::NONE, ..} => InputEvent::Chord,
+                KeyEvent{ code: KeyCode::Char('o'), modifiers: KeyModifiers::NONE, ..} => InputEvent::Bomb,
+                KeyEvent{ code: KeyCode::Char('i'), modifiers: KeyModifiers::NONE, ..} => InputEvent::Hint,
+                KeyEvent{ code: KeyCode::Char('p'), modifiers: KeyModifiers::NONE, ..} => InputEvent::ToggleProbabilities,
+                KeyEvent{ code: KeyCode::Char('x'), modifiers: KeyModifiers::NONE, ..} => InputEvent::Export,
+                KeyEvent{ code: KeyCode::Char('l'), modifiers: KeyModifiers::NONE, ..} => InputEvent::Scoreboard,
+                KeyEvent{ code: KeyCode::Enter, modifiers: KeyModifiers::NONE, ..} => InputEvent::Select,
                 _ => InputEvent::Input(input),
             },
-            InputEvent::Flag => return InputEvent::Flag,
-            InputEvent::Quit => return InputEvent::Quit,
-            _ => return InputEvent::Tick,
+            InputEvent::Flag => InputEvent::Flag,
+            InputEvent::Quit => InputEvent::Quit,
+            InputEvent::Resize(width, height) => InputEvent::Resize(width, height),
+            _ => InputEvent::Tick,
         }
     }
 }
 
+// The first player plays with wasd, space, f and c, the second with the arrows, enter, '.' and ','
+fn get_split_screen_event(input: KeyEvent) -> InputEvent {
+    let (player, event) = match input.code {
+        KeyCode::Char('w') => (0, InputEvent::Navigation(Direction::Up)),
+        KeyCode::Char('a') => (0, InputEvent::Navigation(Direction::Left)),
+        KeyCode::Char('s') => (0, InputEvent::Navigation(Direction::Down)),
+        KeyCode::Char('d') => (0, InputEvent::Navigation(Direction::Right)),
+        KeyCode::Char(' ') => (0, InputEvent::Select),
+        KeyCode::Char('c') => (0, InputEvent::Chord),
+        KeyCode::Up => (1, InputEvent::Navigation(Direction::Up)),
+        KeyCode::Left => (1, InputEvent::Navigation(Direction::Left)),
+        KeyCode::Down => (1, InputEvent::Navigation(Direction::Down)),
+        KeyCode::Right => (1, InputEvent::Navigation(Direction::Right)),
+        KeyCode::Enter => (1, InputEvent::Select),
+        KeyCode::Char('.') => (1, InputEvent::Flag),
+        KeyCode::Char(',') => (1, InputEvent::Chord),
+        KeyCode::Char('e') => return InputEvent::GameDifficulty(Difficulty::Easy),
+        KeyCode::Char('m') => return InputEvent::GameDifficulty(Difficulty::Medium),
+        KeyCode::Char('h') => return InputEvent::GameDifficulty(Difficulty::Hard),
+        KeyCode::Char('l') => return InputEvent::Scoreboard,
+        _ => return InputEvent::Input(input),
+    };
+    InputEvent::Player(player, Box::new(event))
+}
+
+// Quitting and flagging work the same on every board, the other keys are read by the input listener
+pub fn get_key_input_event(key: KeyEvent) -> InputEvent {
+    match key {
+        KeyEvent{ code: KeyCode::Char('q'), modifiers: KeyModifiers::NONE, ..} => InputEvent::Quit,
+        KeyEvent{ code: KeyCode::Char('f'), modifiers: KeyModifiers::NONE, ..} => InputEvent::Flag,
+        _ => InputEvent::Input(key),
+    }
+}
+
 pub fn listen_for_key_input(tx: &mpsc::Sender<InputEvent>){
     let mut last_tick: Instant = Instant::now();
     let tick_rate: Duration = Duration::from_millis(200);
@@ -72,16 +141,10 @@ pub fn listen_for_key_input(tx: &mpsc::Sender<InputEvent>){
             .unwrap_or_else(|| Duration::from_secs(0));
 
         if event::poll(timeout).expect("poll expect") {
-            if let Event::Key(key) = event::read().expect("event read expect") {
-                match key {
-                    KeyEvent{ code: KeyCode::Char('q'), modifiers: KeyModifiers::NONE, ..} => {
-                        tx.send(InputEvent::Quit).expect("tx send expect");
-                    },
-                    KeyEvent{ code: KeyCode::Char('f'), modifiers: KeyModifiers::NONE, ..} => {
-                        tx.send(InputEvent::Flag).expect("tx send expect");
-                    },
-                    _ => tx.send(InputEvent::Input(key)).expect("tx send expect"),
-                }
+            match event::read().expect("event read expect") {
+                Event::Key(key) => tx.send(get_key_input_event(key)).expect("tx send expect"),
+                Event::Resize(width, height) => tx.send(InputEvent::Resize(width, height)).expect("tx send expect"),
+                _ => { },
             }
         }
 
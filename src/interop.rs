@@ -0,0 +1,414 @@
+use std::{
+    fmt,
+    io,
+    str::FromStr,
+    time::Duration,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    board::Board,
+    layout::{Layout, MAX_CELLS},
+};
+
+// Minesweeper Board Format (MBF), as used by Minesweeper Arbiter:
+//   byte 0      board width
+//   byte 1      board height
+//   bytes 2-3   mine count, big endian
+//   then per mine one byte for its column and one for its row, counted from 0
+//
+// Replays use a subset of the RawVF text format of Viennasweeper:
+//   RawVF_Version: Rev4
+//   Width: 9
+//   Height: 9
+//   Mines: 10
+//   Seed: 42             (optional)
+//   Board:
+//   *0000000             (one row per line, '*' for mines and '0' for safe cells)
+//   Events:
+//   1.250 lr 4 5         (seconds since start, event, column and row counted from 1)
+// Events are "lr" (left release, opens a cell), "rc" (right click, toggles a flag) and "mr"
+// (middle release, opens around a number). Other
+// headers and event types written by other programs are skipped when reading.
+
+#[derive(Debug)]
+pub struct ParseError {
+    offset: usize,
+    message: String,
+}
+
+impl ParseError {
+    fn new(offset: usize, message: String) -> Self {
+        Self { offset, message }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "byte {}: {}", self.offset, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+pub fn write_mbf(board: &Board) -> io::Result<Vec<u8>> {
+    let width = board.get_board_width();
+    let height = board.get_board_height();
+    let mines: Vec<usize> = get_mine_indices(board);
+    if width > u8::MAX as usize || height > u8::MAX as usize || mines.len() > u16::MAX as usize {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "board is too large for MBF"));
+    }
+
+    let mut bytes = vec![width as u8, height as u8];
+    bytes.extend((mines.len() as u16).to_be_bytes());
+    for index in mines {
+        bytes.push((index % width) as u8);
+        bytes.push((index / width) as u8);
+    }
+    Ok(bytes)
+}
+
+pub fn parse_mbf(bytes: &[u8]) -> Result<Layout, ParseError> {
+    if bytes.len() < 4 {
+        return Err(ParseError::new(bytes.len(), "header needs 4 bytes".to_string()));
+    }
+
+    let width = bytes[0] as usize;
+    let height = bytes[1] as usize;
+    let mine_count = u16::from_be_bytes([bytes[2], bytes[3]]) as usize;
+    if width == 0 || height == 0 || width * height > MAX_CELLS {
+        return Err(ParseError::new(0, format!("invalid board size {}x{}", width, height)));
+    }
+    if mine_count > width * height {
+        return Err(ParseError::new(2, format!("{} mines do not fit on a {}x{} board", mine_count, width, height)));
+    }
+
+    let expected_length = 4 + mine_count * 2;
+    if bytes.len() != expected_length {
+        return Err(ParseError::new(bytes.len().min(expected_length),
+                                   format!("expected {} bytes for {} mines, found {}", expected_length, mine_count, bytes.len())));
+    }
+
+    let mut mines = vec![];
+    for (i, position) in bytes[4..].chunks(2).enumerate() {
+        let offset = 4 + i * 2;
+        let (x, y) = (position[0] as usize, position[1] as usize);
+        if x >= width || y >= height {
+            return Err(ParseError::new(offset, format!("mine at ({}, {}) is outside the board", x, y)));
+        }
+
+        let index = x + y * width;
+        if mines.contains(&index) {
+            return Err(ParseError::new(offset, format!("mine at ({}, {}) is listed twice", x, y)));
+        }
+        mines.push(index);
+    }
+
+    Ok(Layout::from_mines(width, height, &mines))
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReplayAction {
+    Open,
+    Flag,
+    Chord,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ReplayEvent {
+    time: Duration,
+    action: ReplayAction,
+    index: usize,
+}
+
+impl ReplayEvent {
+    pub fn new(time: Duration, action: ReplayAction, index: usize) -> Self {
+        Self { time, action, index }
+    }
+
+    pub fn get_time(&self) -> Duration {
+        self.time
+    }
+
+    pub fn get_action(&self) -> ReplayAction {
+        self.action
+    }
+
+    pub fn get_index(&self) -> usize {
+        self.index
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Replay {
+    width: usize,
+    height: usize,
+    mines: Vec<usize>,
+    seed: Option<u64>,
+    events: Vec<ReplayEvent>,
+}
+
+impl Replay {
+    pub fn new(board: &Board, events: Vec<ReplayEvent>) -> Self {
+        Self {
+            width: board.get_board_width(),
+            height: board.get_board_height(),
+            mines: get_mine_indices(board),
+            seed: board.get_seed(),
+            events,
+        }
+    }
+
+    pub fn get_layout(&self) -> Layout {
+        let mut layout = Layout::from_mines(self.width, self.height, &self.mines);
+        layout.set_seed(self.seed);
+        layout
+    }
+
+    pub fn get_events(&self) -> &[ReplayEvent] {
+        &self.events
+    }
+
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let mut width: Option<usize> = None;
+        let mut height: Option<usize> = None;
+        let mut mine_count: Option<usize> = None;
+        let mut seed = None;
+        let mut rows: Vec<(usize, &str)> = vec![];
+        let mut events = vec![];
+        let mut section = "";
+
+        let mut offset = 0;
+        for line in text.split_inclusive('\n') {
+            let line_offset = offset;
+            offset += line.len();
+            let line = line.trim_end();
+            if line.is_empty() {
+                continue;
+            }
+
+            if line == "Board:" || line == "Events:" {
+                section = line;
+                continue;
+            }
+
+            match section {
+                "Board:" => rows.push((line_offset, line)),
+                "Events:" => {
+                    if let Some(event) = parse_event(line, line_offset, width.unwrap_or(0), height.unwrap_or(0))? {
+                        events.push(event);
+                    }
+                },
+                _ => {
+                    let Some((key, value)) = line.split_once(':') else {
+                        return Err(ParseError::new(line_offset, format!("expected a header, found '{}'", line)));
+                    };
+                    let value_offset = line_offset + key.len() + 1;
+                    match key {
+                        "Width" => width = Some(parse_number(value, value_offset)?),
+                        "Height" => height = Some(parse_number(value, value_offset)?),
+                        "Mines" => mine_count = Some(parse_number(value, value_offset)?),
+                        "Seed" => seed = Some(parse_number(value, value_offset)?),
+                        _ => { },
+                    }
+                },
+            }
+        }
+
+        let (Some(width), Some(height)) = (width, height) else {
+            return Err(ParseError::new(text.len(), "missing Width or Height header".to_string()));
+        };
+        if width == 0 || height == 0 || width.checked_mul(height).is_none_or(|cells| cells > MAX_CELLS) {
+            return Err(ParseError::new(text.len(), format!("invalid board size {}x{}", width, height)));
+        }
+        if rows.len() != height {
+            return Err(ParseError::new(text.len(), format!("expected {} board rows, found {}", height, rows.len())));
+        }
+
+        let mut mines = vec![];
+        for (y, (row_offset, row)) in rows.into_iter().enumerate() {
+            if row.len() != width {
+                return Err(ParseError::new(row_offset, format!("expected {} cells in row, found {}", width, row.len())));
+            }
+            for (x, c) in row.bytes().enumerate() {
+                match c {
+                    b'*' => mines.push(x + y * width),
+                    b'0' => { },
+                    _ => return Err(ParseError::new(row_offset + x, format!("unknown cell '{}'", c as char))),
+                }
+            }
+        }
+
+        if let Some(mine_count) = mine_count {
+            if mine_count != mines.len() {
+                return Err(ParseError::new(text.len(), format!("Mines header says {} but the board has {}", mine_count, mines.len())));
+            }
+        }
+
+        Ok(Self { width, height, mines, seed, events })
+    }
+}
+
+impl fmt::Display for Replay {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "RawVF_Version: Rev4")?;
+        writeln!(f, "Program: minesweeper-tui")?;
+        writeln!(f, "Width: {}", self.width)?;
+        writeln!(f, "Height: {}", self.height)?;
+        writeln!(f, "Mines: {}", self.mines.len())?;
+        if let Some(seed) = self.seed {
+            writeln!(f, "Seed: {}", seed)?;
+        }
+
+        writeln!(f, "Board:")?;
+        for y in 0..self.height {
+            let row: String = (0..self.width)
+                .map(|x| if self.mines.contains(&(x + y * self.width)) { '*' } else { '0' })
+                .collect();
+            writeln!(f, "{}", row)?;
+        }
+
+        writeln!(f, "Events:")?;
+        for event in self.events.iter() {
+            let name = match event.action {
+                ReplayAction::Open => "lr",
+                ReplayAction::Flag => "rc",
+                ReplayAction::Chord => "mr",
+            };
+            writeln!(f, "{:.3} {} {} {}",
+                     event.time.as_secs_f64(),
+                     name,
+                     event.index % self.width + 1,
+                     event.index / self.width + 1)?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_event(line: &str, line_offset: usize, width: usize, height: usize) -> Result<Option<ReplayEvent>, ParseError> {
+    // Every field with its offset from the start of the line
+    let mut fields: Vec<(usize, &str)> = vec![];
+    let mut field_start = None;
+    for (i, c) in line.char_indices().chain([(line.len(), ' ')]) {
+        match (c.is_whitespace(), field_start) {
+            (true, Some(start)) => {
+                fields.push((start, &line[start..i]));
+                field_start = None;
+            },
+            (false, None) => field_start = Some(i),
+            _ => { },
+        }
+    }
+
+    let action = match fields.get(1) {
+        Some((_, "lr")) => ReplayAction::Open,
+        Some((_, "rc")) => ReplayAction::Flag,
+        Some((_, "mr")) => ReplayAction::Chord,
+        _ => return Ok(None),
+    };
+    if fields.len() < 4 {
+        return Err(ParseError::new(line_offset, format!("event needs a time, type, column and row: '{}'", line)));
+    }
+
+    let time = fields[0].1.parse::<f64>().ok().filter(|time| time.is_finite() && *time >= 0.0)
+        .ok_or_else(|| ParseError::new(line_offset, format!("invalid time '{}'", fields[0].1)))?;
+
+    let x: usize = parse_number(fields[2].1, line_offset + fields[2].0)?;
+    let y: usize = parse_number(fields[3].1, line_offset + fields[3].0)?;
+    if x == 0 || y == 0 || x > width || y > height {
+        return Err(ParseError::new(line_offset + fields[2].0, format!("cell ({}, {}) is outside the board", x, y)));
+    }
+
+    Ok(Some(ReplayEvent::new(Duration::from_secs_f64(time), action, (x - 1) + (y - 1) * width)))
+}
+
+fn parse_number<T: FromStr>(value: &str, offset: usize) -> Result<T, ParseError> {
+    let offset = offset + value.len() - value.trim_start().len();
+    value.trim().parse().map_err(|_| ParseError::new(offset, format!("invalid number '{}'", value.trim())))
+}
+
+fn get_mine_indices(board: &Board) -> Vec<usize> {
+    board.get_cells().iter()
+        .enumerate()
+        .filter(|(_, cell)| cell.is_bomb())
+        .map(|(i, _)| i)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::Difficulty;
+
+    #[test]
+    fn test_mbf_round_trip(){
+        let mut board: Board = Board::new();
+        board.initiate_seeded_board(Difficulty::Hard, 3);
+
+        let bytes = write_mbf(&board).unwrap();
+        assert_eq!(&bytes[..4], &[30, 16, 0, 60]);
+
+        let mut other_board: Board = Board::new();
+        parse_mbf(&bytes).unwrap().apply_to(&mut other_board);
+        assert_eq!(get_mine_indices(&board), get_mine_indices(&other_board));
+        assert_eq!(write_mbf(&other_board).unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_mbf_errors(){
+        assert_eq!(parse_mbf(&[9, 9]).unwrap_err().offset, 2);
+        assert_eq!(parse_mbf(&[9, 9, 0, 2, 1, 1]).unwrap_err().offset, 6);
+
+        let error = parse_mbf(&[9, 9, 0, 2, 1, 1, 9, 0]).unwrap_err();
+        assert_eq!(error.to_string(), "byte 6: mine at (9, 0) is outside the board");
+
+        assert_eq!(parse_mbf(&[9, 9, 0, 2, 1, 1, 1, 1]).unwrap_err().offset, 6);
+
+        assert_eq!(parse_mbf(&[0, 9, 0, 0]).unwrap_err().to_string(), "byte 0: invalid board size 0x9");
+        assert_eq!(parse_mbf(&[200, 200, 0, 0]).unwrap_err().offset, 0);
+    }
+
+    #[test]
+    fn test_replay_round_trip(){
+        let mut board: Board = Board::new();
+        board.initiate_seeded_board(Difficulty::Easy, 8);
+        let events = vec![
+            ReplayEvent::new(Duration::from_millis(0), ReplayAction::Open, 40),
+            ReplayEvent::new(Duration::from_millis(1500), ReplayAction::Flag, 9),
+            ReplayEvent::new(Duration::from_millis(2250), ReplayAction::Open, 80),
+            ReplayEvent::new(Duration::from_millis(3000), ReplayAction::Chord, 40),
+        ];
+
+        let replay = Replay::new(&board, events);
+        let parsed = Replay::parse(&replay.to_string()).unwrap();
+        assert_eq!(parsed, replay);
+        assert_eq!(parsed.to_string(), replay.to_string());
+    }
+
+    #[test]
+    fn test_replay_skips_foreign_lines(){
+        let text = "RawVF_Version: Rev4\nLevel: Beginner\nWidth: 3\nHeight: 1\nMines: 1\nBoard:\n*00\nEvents:\n0.00 start\n0.10 lc 2 1 (24 8)\n0.12 lr 2 1 (24 8)\n";
+        let replay = Replay::parse(text).unwrap();
+        assert_eq!(replay.mines, vec![0]);
+        assert_eq!(replay.get_events(), &[ReplayEvent::new(Duration::from_millis(120), ReplayAction::Open, 1)]);
+    }
+
+    #[test]
+    fn test_replay_errors(){
+        let text = "Width: 3\nHeight: 1\nBoard:\n*x0\n";
+        assert_eq!(Replay::parse(text).unwrap_err().offset, 27);
+
+        let text = "Width: 3\nHeight: 1\nBoard:\n*00\nEvents:\n0.5 lr 4 1\n";
+        assert_eq!(Replay::parse(text).unwrap_err().offset, 45);
+
+        let text = "Width: three\n";
+        assert_eq!(Replay::parse(text).unwrap_err().to_string(), "byte 7: invalid number 'three'");
+
+        let text = "Width: 0\nHeight: 0\nBoard:\n";
+        assert_eq!(Replay::parse(text).unwrap_err().to_string(), "byte 26: invalid board size 0x0");
+
+        let text = "Width: 200\nHeight: 200\nBoard:\n";
+        assert!(Replay::parse(text).is_err());
+    }
+}
@@ -0,0 +1,489 @@
+use std::{
+    fmt,
+    fs,
+    io,
+    path::Path,
+};
+
+use crate::{
+    board::Board,
+    interop::{self, Replay},
+};
+
+// A board written as text, one character per cell:
+//   .  safe cell        *  mine
+//   o  opened safe cell X  opened mine
+//   f  flagged safe cell F  flagged mine
+//   -  disabled cell, not part of the board
+// Lines starting with '#' are headers in the form "# key: value", "seed" and "mode" are known.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LayoutCell {
+    Safe,
+    Mine,
+    OpenSafe,
+    OpenMine,
+    FlaggedSafe,
+    FlaggedMine,
+    Disabled,
+}
+
+impl LayoutCell {
+    fn from_char(c: char) -> Option<Self> {
+        match c {
+            '.' => Some(LayoutCell::Safe),
+            '*' => Some(LayoutCell::Mine),
+            'o' => Some(LayoutCell::OpenSafe),
+            'X' => Some(LayoutCell::OpenMine),
+            'f' => Some(LayoutCell::FlaggedSafe),
+            'F' => Some(LayoutCell::FlaggedMine),
+            '-' => Some(LayoutCell::Disabled),
+            _ => None,
+        }
+    }
+
+    fn to_char(self) -> char {
+        match self {
+            LayoutCell::Safe => '.',
+            LayoutCell::Mine => '*',
+            LayoutCell::OpenSafe => 'o',
+            LayoutCell::OpenMine => 'X',
+            LayoutCell::FlaggedSafe => 'f',
+            LayoutCell::FlaggedMine => 'F',
+            LayoutCell::Disabled => '-',
+        }
+    }
+
+    pub fn is_mine(self) -> bool {
+        matches!(self, LayoutCell::Mine | LayoutCell::OpenMine | LayoutCell::FlaggedMine)
+    }
+
+    pub fn is_open(self) -> bool {
+        matches!(self, LayoutCell::OpenSafe | LayoutCell::OpenMine)
+    }
+
+    pub fn is_flagged(self) -> bool {
+        matches!(self, LayoutCell::FlaggedSafe | LayoutCell::FlaggedMine)
+    }
+
+    pub fn is_disabled(self) -> bool {
+        self == LayoutCell::Disabled
+    }
+}
+
+// Boards find their cells with i16 positions, so larger boards can not be played
+pub const MAX_CELLS: usize = i16::MAX as usize;
+
+// Layered boards count the cells of every layer
+pub fn is_playable_size(width: usize, height: usize, depth: usize) -> bool {
+    width.checked_mul(height).and_then(|cells| cells.checked_mul(depth)).is_some_and(|cells| cells <= MAX_CELLS)
+}
+
+#[derive(Debug)]
+pub struct LayoutError {
+    line: usize,
+    message: String,
+}
+
+impl fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for LayoutError {}
+
+#[derive(Debug)]
+pub struct Layout {
+    width: usize,
+    height: usize,
+    cells: Vec<LayoutCell>,
+    seed: Option<u64>,
+    mode: Option<String>,
+}
+
+impl Layout {
+    pub fn from_mines(width: usize, height: usize, mines: &[usize]) -> Self {
+        let mut cells = vec![LayoutCell::Safe; width * height];
+        for &index in mines {
+            cells[index] = LayoutCell::Mine;
+        }
+
+        Self {
+            width,
+            height,
+            cells,
+            seed: None,
+            mode: None,
+        }
+    }
+
+    pub fn from_board(board: &Board, mode: Option<String>) -> Self {
+        let cells = board.get_cells().iter()
+            .map(|cell| match (cell.is_bomb(), cell.is_open(), cell.is_flagged()) {
+                _ if cell.is_disabled() => LayoutCell::Disabled,
+                (false, true, _) => LayoutCell::OpenSafe,
+                (true, true, _) => LayoutCell::OpenMine,
+                (false, false, true) => LayoutCell::FlaggedSafe,
+                (true, false, true) => LayoutCell::FlaggedMine,
+                (false, false, false) => LayoutCell::Safe,
+                (true, false, false) => LayoutCell::Mine,
+            })
+            .collect();
+
+        Self {
+            width: board.get_board_width(),
+            height: board.get_board_height(),
+            cells,
+            seed: board.get_seed(),
+            mode,
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Self, LayoutError> {
+        let mut seed = None;
+        let mut mode = None;
+        let mut rows: Vec<Vec<LayoutCell>> = vec![];
+
+        for (i, line) in text.lines().enumerate() {
+            let line_number = i + 1;
+            let line = line.trim_end();
+            if let Some(header) = line.strip_prefix('#') {
+                if let Some((key, value)) = header.split_once(':') {
+                    match key.trim() {
+                        "seed" => {
+                            let value = value.trim().parse().map_err(|_| LayoutError {
+                                line: line_number,
+                                message: format!("invalid seed '{}'", value.trim()),
+                            })?;
+                            seed = Some(value);
+                        },
+                        "mode" => mode = Some(value.trim().to_string()),
+                        _ => { },
+                    }
+                }
+                continue;
+            }
+            if line.is_empty() {
+                continue;
+            }
+
+            let mut row = vec![];
+            for (column, c) in line.chars().enumerate() {
+                let cell = LayoutCell::from_char(c).ok_or_else(|| LayoutError {
+                    line: line_number,
+                    message: format!("unknown cell '{}' in column {}", c, column + 1),
+                })?;
+                row.push(cell);
+            }
+
+            if let Some(first_row) = rows.first() {
+                if first_row.len() != row.len() {
+                    return Err(LayoutError {
+                        line: line_number,
+                        message: format!("row has {} cells, expected {}", row.len(), first_row.len()),
+                    });
+                }
+            }
+            rows.push(row);
+        }
+
+        if rows.is_empty() {
+            return Err(LayoutError { line: text.lines().count(), message: "no rows in board".to_string() });
+        }
+        if rows.len() * rows[0].len() > MAX_CELLS {
+            return Err(LayoutError {
+                line: text.lines().count(),
+                message: format!("board has {} cells, at most {} are supported", rows.len() * rows[0].len(), MAX_CELLS),
+            });
+        }
+
+        Ok(Self {
+            width: rows[0].len(),
+            height: rows.len(),
+            cells: rows.into_iter().flatten().collect(),
+            seed,
+            mode,
+        })
+    }
+
+    pub fn apply_to(&self, board: &mut Board) {
+        let bomb_indices: Vec<usize> = self.get_indices(LayoutCell::is_mine);
+        board.initiate_board_from_layout(self.width, self.height, &bomb_indices);
+        board.disable_cells(&self.get_indices(LayoutCell::is_disabled));
+        board.restore_progress(&self.get_indices(LayoutCell::is_open), &self.get_indices(LayoutCell::is_flagged));
+        board.set_seed(self.seed);
+    }
+
+    fn get_indices(&self, filter: fn(LayoutCell) -> bool) -> Vec<usize> {
+        self.cells.iter()
+            .enumerate()
+            .filter(|(_, &cell)| filter(cell))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    pub fn set_seed(&mut self, seed: Option<u64>) {
+        self.seed = seed;
+    }
+
+    pub fn get_mode(&self) -> Option<&str> {
+        self.mode.as_deref()
+    }
+}
+
+impl fmt::Display for Layout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(seed) = self.seed {
+            writeln!(f, "# seed: {}", seed)?;
+        }
+        if let Some(mode) = &self.mode {
+            writeln!(f, "# mode: {}", mode)?;
+        }
+        for row in self.cells.chunks(self.width) {
+            writeln!(f, "{}", row.iter().map(|cell| cell.to_char()).collect::<String>())?;
+        }
+        Ok(())
+    }
+}
+
+// Cells left out of the board, drawn as text with '.' for a cell and ' ', 'x' or '-' for a hole.
+// Short rows are filled up with holes and lines starting with '#' are comments.
+#[derive(Clone, Debug)]
+pub struct Mask {
+    width: usize,
+    height: usize,
+    enabled: Vec<bool>,
+}
+
+const CIRCLE_MASK: &str = "     .....
+   .........
+  ...........
+ .............
+ .............
+...............
+...............
+...............
+...............
+...............
+ .............
+ .............
+  ...........
+   .........
+     .....
+";
+
+const DONUT_MASK: &str = "     .....
+   .........
+  ...........
+ .............
+ .............
+......   ......
+.....     .....
+.....     .....
+.....     .....
+......   ......
+ .............
+ .............
+  ...........
+   .........
+     .....
+";
+
+const HEART_MASK: &str = "  .....   .....
+ ....... .......
+.................
+.................
+.................
+ ...............
+  .............
+   ...........
+    .........
+     .......
+      .....
+       ...
+        .
+";
+
+pub const BUILT_IN_MASKS: [&str; 3] = ["circle", "donut", "heart"];
+
+impl Mask {
+    pub fn parse(text: &str) -> Result<Self, LayoutError> {
+        let mut rows: Vec<(usize, Vec<bool>)> = vec![];
+        for (i, line) in text.lines().enumerate() {
+            if line.starts_with('#') {
+                continue;
+            }
+
+            let mut row = vec![];
+            for (column, c) in line.trim_end().chars().enumerate() {
+                row.push(match c {
+                    '.' => true,
+                    ' ' | 'x' | '-' => false,
+                    _ => return Err(LayoutError {
+                        line: i + 1,
+                        message: format!("unknown mask cell '{}' in column {}", c, column + 1),
+                    }),
+                });
+            }
+            rows.push((i + 1, row));
+        }
+
+        // Blank lines around the shape are not part of it
+        while rows.last().is_some_and(|(_, row)| row.is_empty()) {
+            rows.pop();
+        }
+        let first_row = rows.iter().position(|(_, row)| !row.is_empty()).unwrap_or(rows.len());
+        rows.drain(..first_row);
+
+        let width = rows.iter().map(|(_, row)| row.len()).max().unwrap_or(0);
+        if !is_playable_size(width, rows.len(), 1) {
+            return Err(LayoutError {
+                line: text.lines().count(),
+                message: format!("mask has {} cells, at most {} are supported", width * rows.len(), MAX_CELLS),
+            });
+        }
+        let mut enabled = vec![];
+        for (_, row) in rows.iter() {
+            enabled.extend(row.iter().copied().chain(std::iter::repeat_n(false, width - row.len())));
+        }
+
+        let mask = Self { width, height: rows.len(), enabled };
+        if mask.get_enabled_count() < 2 {
+            return Err(LayoutError { line: text.lines().count(), message: "mask needs at least two cells".to_string() });
+        }
+        Ok(mask)
+    }
+
+    pub fn get_built_in(name: &str) -> Option<Self> {
+        let text = match name {
+            "circle" => CIRCLE_MASK,
+            "donut" => DONUT_MASK,
+            "heart" => HEART_MASK,
+            _ => return None,
+        };
+        Some(Self::parse(text).expect("built-in mask expect"))
+    }
+
+    pub fn get_width(&self) -> usize {
+        self.width
+    }
+
+    pub fn get_height(&self) -> usize {
+        self.height
+    }
+
+    pub fn is_enabled(&self, index: usize) -> bool {
+        self.enabled[index]
+    }
+
+    pub fn get_enabled_count(&self) -> usize {
+        self.enabled.iter().filter(|&&enabled| enabled).count()
+    }
+}
+
+// A built-in shape by name or a mask file
+pub fn read_mask(name: &str) -> Result<Mask, Box<dyn std::error::Error>> {
+    if let Some(mask) = Mask::get_built_in(name) {
+        return Ok(mask);
+    }
+    Ok(Mask::parse(&fs::read_to_string(name)?)?)
+}
+
+// Reads a board in the format matching the file extension, MBF and RawVF files are understood too
+pub fn read_layout(path: &Path) -> Result<Layout, Box<dyn std::error::Error>> {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("mbf") => Ok(interop::parse_mbf(&fs::read(path)?)?),
+        Some("rawvf") => Ok(Replay::parse(&fs::read_to_string(path)?)?.get_layout()),
+        _ => Ok(Layout::parse(&fs::read_to_string(path)?)?),
+    }
+}
+
+pub fn write_layout(path: &Path, layout: &Layout) -> io::Result<()> {
+    fs::write(path, layout.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_layout(){
+        let layout = Layout::parse("# seed: 12\n# mode: custom\n*..\n.o.\nfF.\n").unwrap();
+        assert_eq!(layout.width, 3);
+        assert_eq!(layout.height, 3);
+        assert_eq!(layout.seed, Some(12));
+        assert_eq!(layout.get_mode(), Some("custom"));
+        assert_eq!(layout.cells[0], LayoutCell::Mine);
+        assert_eq!(layout.cells[4], LayoutCell::OpenSafe);
+        assert_eq!(layout.cells[7], LayoutCell::FlaggedMine);
+    }
+
+    #[test]
+    fn test_parse_errors(){
+        let error = Layout::parse("...\n..\n").unwrap_err();
+        assert_eq!(error.line, 2);
+
+        let error = Layout::parse("# seed: abc\n...\n").unwrap_err();
+        assert_eq!(error.line, 1);
+
+        let error = Layout::parse("..?\n").unwrap_err();
+        assert_eq!(error.to_string(), "line 1: unknown cell '?' in column 3");
+
+        assert!(Layout::parse("# mode: custom\n").is_err());
+
+        let row = ".".repeat(200);
+        let error = Layout::parse(&format!("{}\n", row).repeat(200)).unwrap_err();
+        assert_eq!(error.to_string(), "line 200: board has 40000 cells, at most 32767 are supported");
+    }
+
+    #[test]
+    fn test_playable_size(){
+        assert!(is_playable_size(30, 16, 4));
+        assert!(!is_playable_size(100, 100, 4));
+        assert!(!is_playable_size(usize::MAX, 2, 1));
+    }
+
+    #[test]
+    fn test_apply_layout(){
+        let mut board: Board = Board::new();
+        Layout::parse("*..\n...\n..*\n").unwrap().apply_to(&mut board);
+
+        assert_eq!(board.get_board_width(), 3);
+        assert_eq!(board.get_bomb_count(), 2);
+        assert!(board.get_cells()[0].is_bomb());
+        assert_eq!(board.get_cells()[4].get_value(), 2);
+        assert_eq!(board.get_cells()[1].get_value(), 1);
+    }
+
+    #[test]
+    fn test_parse_mask(){
+        let mask = Mask::parse("\n# a small ring\n...\n. .\n..\n\n").unwrap();
+        assert_eq!(mask.get_width(), 3);
+        assert_eq!(mask.get_height(), 3);
+        assert_eq!(mask.get_enabled_count(), 7);
+        assert!(!mask.is_enabled(4));
+        assert!(!mask.is_enabled(8));
+
+        assert_eq!(Mask::parse("..?\n").unwrap_err().to_string(), "line 1: unknown mask cell '?' in column 3");
+        assert!(Mask::parse(".\n").is_err());
+
+        let row = ".".repeat(200);
+        let error = Mask::parse(&format!("{}\n", row).repeat(200)).unwrap_err();
+        assert_eq!(error.to_string(), "line 200: mask has 40000 cells, at most 32767 are supported");
+        for name in BUILT_IN_MASKS {
+            assert!(Mask::get_built_in(name).is_some());
+        }
+    }
+
+    #[test]
+    fn test_round_trip(){
+        let text = "# seed: 5\n# mode: easy\n*o.\nfFX\n-.*\n";
+        let mut board: Board = Board::new();
+        let layout = Layout::parse(text).unwrap();
+        layout.apply_to(&mut board);
+
+        assert_eq!(board.get_flag_count(), 2);
+        assert!(board.get_cells()[1].is_open());
+        assert_eq!(Layout::from_board(&board, layout.mode.clone()).to_string(), text);
+    }
+}
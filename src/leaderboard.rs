@@ -0,0 +1,302 @@
+use std::{
+    fs,
+    io,
+    path::PathBuf,
+    sync::mpsc::{self, Receiver, TryRecvError},
+    thread,
+    time::{Duration, Instant},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::app::Difficulty;
+
+// Results are sent to a team leaderboard over HTTP, as JSON:
+//   POST {url}/results  {"name":"ada","difficulty":"easy","seed":42,"time":12.5,"date":"2024-01-01","replay_hash":"..."}
+//   GET  {url}/top      [{"name":"ada","difficulty":"easy","time":12.5}, ...]
+// Results that can not be sent are kept in a queue file and sent again later. A result the
+// server refuses with an error status is dropped, sending it again would not change that.
+
+// Time to wait before trying to send the queued results again
+const RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Submission {
+    name: String,
+    difficulty: Difficulty,
+    seed: u64,
+    // Game time in seconds, including hint penalties
+    time: f64,
+    date: String,
+    // Lets the server check the result against the replay of the game
+    replay_hash: String,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct LeaderboardEntry {
+    name: String,
+    difficulty: Difficulty,
+    time: f64,
+}
+
+impl LeaderboardEntry {
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_time(&self) -> f64 {
+        self.time
+    }
+}
+
+struct SyncResult {
+    // Results that could not be sent
+    remaining: Vec<Submission>,
+    top_scores: Result<Vec<LeaderboardEntry>, String>,
+}
+
+pub struct Leaderboard {
+    url: String,
+    name: String,
+    queue_path: Option<PathBuf>,
+    // Results waiting to be sent, oldest first
+    queue: Vec<Submission>,
+    // The sync running in the background and the number of queued results it is sending
+    syncing: Option<(Receiver<SyncResult>, usize)>,
+    last_sync: Option<Instant>,
+    top_scores: Vec<LeaderboardEntry>,
+    error: Option<String>,
+}
+
+impl Leaderboard {
+    pub fn new(url: &str, name: String, queue_path: Option<PathBuf>) -> Self {
+        let queue = queue_path.as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default();
+
+        Self {
+            url: url.trim_end_matches('/').to_string(),
+            name,
+            queue_path,
+            queue,
+            syncing: None,
+            last_sync: None,
+            top_scores: vec![],
+            error: None,
+        }
+    }
+
+    pub fn get_default_queue_path() -> Option<PathBuf> {
+        dirs::home_dir().map(|home| home.join(".minesweeper-tui").join("leaderboard-queue.json"))
+    }
+
+    pub fn submit(&mut self, difficulty: Difficulty, seed: u64, time: f64, date: String, replay: &str) -> io::Result<()> {
+        self.queue.push(Submission {
+            name: self.name.clone(),
+            difficulty,
+            seed,
+            time,
+            date,
+            replay_hash: get_replay_hash(replay),
+        });
+        self.sync();
+        self.save_queue()
+    }
+
+    // Send the queued results and fetch the top scores in the background
+    pub fn sync(&mut self) {
+        if self.syncing.is_some() {
+            return;
+        }
+
+        let (tx, rx) = mpsc::channel();
+        let (url, submissions) = (self.url.clone(), self.queue.clone());
+        thread::spawn(move || {
+            let _ = tx.send(sync(&url, submissions));
+        });
+        self.syncing = Some((rx, self.queue.len()));
+        self.last_sync = Some(Instant::now());
+    }
+
+    // Pick up the result of a finished sync and retry the queue once in a while
+    pub fn update(&mut self) -> io::Result<()> {
+        if let Some((rx, sent_count)) = self.syncing.as_ref() {
+            let result = match rx.try_recv() {
+                Ok(result) => result,
+                Err(TryRecvError::Empty) => return Ok(()),
+                Err(TryRecvError::Disconnected) => SyncResult {
+                    remaining: self.queue[..*sent_count].to_vec(),
+                    top_scores: Err("The sync stopped".to_string()),
+                },
+            };
+
+            // Results submitted while the sync ran are still in the queue after the ones it sent
+            let mut queue = result.remaining;
+            queue.extend(self.queue.drain(*sent_count..));
+            self.queue = queue;
+            self.syncing = None;
+            match result.top_scores {
+                Ok(top_scores) => {
+                    self.top_scores = top_scores;
+                    self.error = None;
+                },
+                Err(error) => self.error = Some(error),
+            }
+            self.save_queue()?;
+        }
+
+        if !self.queue.is_empty() && self.last_sync.is_none_or(|last_sync| last_sync.elapsed() >= RETRY_INTERVAL) {
+            self.sync();
+        }
+        Ok(())
+    }
+
+    fn save_queue(&self) -> io::Result<()> {
+        let Some(path) = &self.queue_path else {
+            return Ok(());
+        };
+
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(&self.queue)?)
+    }
+
+    // Fastest times of a difficulty on the team leaderboard
+    pub fn get_top_scores(&self, difficulty: Difficulty, count: usize) -> Vec<&LeaderboardEntry> {
+        let mut scores: Vec<&LeaderboardEntry> = self.top_scores.iter()
+            .filter(|entry| entry.difficulty == difficulty)
+            .collect();
+        scores.sort_by(|a, b| a.time.total_cmp(&b.time));
+        scores.truncate(count);
+        scores
+    }
+
+    pub fn get_pending_count(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn get_error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+}
+
+fn sync(url: &str, submissions: Vec<Submission>) -> SyncResult {
+    let agent = ureq::AgentBuilder::new().timeout(REQUEST_TIMEOUT).build();
+    let mut remaining = vec![];
+    let mut is_reachable = true;
+
+    for submission in submissions {
+        if !is_reachable {
+            remaining.push(submission);
+            continue;
+        }
+        match agent.post(&format!("{}/results", url)).send_json(&submission) {
+            Ok(_) | Err(ureq::Error::Status(..)) => { },
+            Err(ureq::Error::Transport(_)) => {
+                is_reachable = false;
+                remaining.push(submission);
+            },
+        }
+    }
+
+    let top_scores = agent.get(&format!("{}/top", url))
+        .call()
+        .map_err(|error| error.to_string())
+        .and_then(|response| response.into_json().map_err(|error| error.to_string()));
+    SyncResult {
+        remaining,
+        top_scores,
+    }
+}
+
+// FNV-1a of the replay text, in hex
+fn get_replay_hash(replay: &str) -> String {
+    let hash = replay.bytes().fold(0xcbf29ce484222325u64, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3));
+    format!("{:016x}", hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        net::TcpListener,
+        sync::{Arc, Mutex},
+    };
+
+    // A leaderboard server that keeps the results it gets and lists them as the top scores
+    fn start_mock_server() -> (String, Arc<Mutex<Vec<Submission>>>) {
+        let server = tiny_http::Server::http("127.0.0.1:0").expect("server expect");
+        let port = server.server_addr().to_ip().expect("address expect").port();
+        let results: Arc<Mutex<Vec<Submission>>> = Arc::new(Mutex::new(vec![]));
+        let server_results = Arc::clone(&results);
+        thread::spawn(move || {
+            for mut request in server.incoming_requests() {
+                let body = match (request.method(), request.url()) {
+                    (tiny_http::Method::Post, "/results") => {
+                        let submission = serde_json::from_reader(request.as_reader()).expect("submission expect");
+                        server_results.lock().expect("lock expect").push(submission);
+                        "{}".to_string()
+                    },
+                    _ => {
+                        let results = server_results.lock().expect("lock expect");
+                        let entries: Vec<serde_json::Value> = results.iter()
+                            .map(|result| serde_json::json!({ "name": result.name, "difficulty": result.difficulty, "time": result.time }))
+                            .collect();
+                        serde_json::to_string(&entries).expect("json expect")
+                    },
+                };
+                let _ = request.respond(tiny_http::Response::from_string(body));
+            }
+        });
+        (format!("http://127.0.0.1:{}/", port), results)
+    }
+
+    fn wait_for_sync(leaderboard: &mut Leaderboard) {
+        let start = Instant::now();
+        leaderboard.update().expect("update expect");
+        while leaderboard.syncing.is_some() {
+            assert!(start.elapsed() < Duration::from_secs(10), "timed out");
+            thread::sleep(Duration::from_millis(10));
+            leaderboard.update().expect("update expect");
+        }
+    }
+
+    #[test]
+    fn test_submit(){
+        let (url, results) = start_mock_server();
+        let mut leaderboard = Leaderboard::new(&url, "ada".to_string(), None);
+        leaderboard.submit(Difficulty::Medium, 42, 61.5, "2024-01-01".to_string(), "replay").expect("submit expect");
+        wait_for_sync(&mut leaderboard);
+
+        assert_eq!(leaderboard.get_pending_count(), 0);
+        assert_eq!(leaderboard.get_error(), None);
+        let results = results.lock().expect("lock expect");
+        assert_eq!((results[0].seed, results[0].replay_hash.clone()), (42, get_replay_hash("replay")));
+        let top_scores = leaderboard.get_top_scores(Difficulty::Medium, 3);
+        assert_eq!((top_scores[0].get_name(), top_scores[0].get_time()), ("ada", 61.5));
+        assert!(leaderboard.get_top_scores(Difficulty::Easy, 3).is_empty());
+    }
+
+    #[test]
+    fn test_offline_queue(){
+        let path = std::env::temp_dir().join(format!("minesweeper-tui-leaderboard-{}.json", std::process::id()));
+        let closed_port = TcpListener::bind("127.0.0.1:0").expect("bind expect").local_addr().expect("address expect").port();
+        let mut leaderboard = Leaderboard::new(&format!("http://127.0.0.1:{}", closed_port), "ada".to_string(), Some(path.clone()));
+        leaderboard.submit(Difficulty::Easy, 1, 9.0, "2024-01-01".to_string(), "replay").expect("submit expect");
+        wait_for_sync(&mut leaderboard);
+        assert_eq!(leaderboard.get_pending_count(), 1);
+        assert!(leaderboard.get_error().is_some());
+
+        // The queue outlives the game and is sent once the server can be reached
+        let (url, results) = start_mock_server();
+        let mut leaderboard = Leaderboard::new(&url, "ada".to_string(), Some(path.clone()));
+        assert_eq!(leaderboard.get_pending_count(), 1);
+        wait_for_sync(&mut leaderboard);
+        assert_eq!(leaderboard.get_pending_count(), 0);
+        assert_eq!(results.lock().expect("lock expect").len(), 1);
+        fs::remove_file(path).expect("remove expect");
+    }
+}
@@ -1,27 +1,498 @@
 use crossterm::terminal::{disable_raw_mode, enable_raw_mode};
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use std::{
+    fs,
     io,
+    net::TcpListener,
+    path::PathBuf,
     sync::mpsc::{self, Receiver, Sender},
     thread,
+    time::Duration,
 };
 
 mod app;
+mod bench;
+mod bot;
+mod broadcast;
 mod ui;
 mod cell;
+mod coop;
 mod input_listener;
 mod board;
+mod daily;
+mod flags;
+mod flags_bot;
+mod interop;
+mod layout;
+#[cfg(feature = "leaderboard")]
+mod leaderboard;
+mod neighborhood;
+mod net;
+mod protocol;
+mod race;
 mod scoreboard;
+#[cfg(feature = "ssh")]
+mod server;
+mod solver;
+#[cfg(feature = "ssh")]
+mod ssh;
+mod stats;
+mod topology;
+mod world;
 
-#[derive(Parser)]
+#[derive(Parser, Clone)]
 pub struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Run in debug mode
     #[arg(short, long, default_value_t = false)]
     debug: bool,
+
+    /// Seconds added to the game time for every hint used
+    #[arg(long, default_value_t = 10)]
+    hint_penalty: u64,
+
+    /// Width and height of a cell in characters, probabilities are shown as text from 6
+    #[arg(long, default_value_t = 3)]
+    cell_size: u16,
+
+    /// Let the built-in solver play the game
+    #[arg(long, default_value_t = false)]
+    autoplay: bool,
+
+    /// Milliseconds between the moves of the autoplay bot
+    #[arg(long, default_value_t = 150)]
+    autoplay_delay: u64,
+
+    /// Play the board stored in this file, either a text layout or a .mbf or .rawvf file
+    #[arg(long)]
+    board: Option<PathBuf>,
+
+    /// Play back the RawVF replay stored in this file
+    #[arg(long)]
+    replay: Option<PathBuf>,
+
+    /// Play the daily challenge, everyone gets the same board for a difficulty on the same day
+    #[arg(long, default_value_t = false)]
+    daily: bool,
+
+    /// File to keep the scoreboard in, defaults to ~/.minesweeper-tui/scoreboard.json
+    #[arg(long)]
+    scoreboard: Option<PathBuf>,
+
+    /// Play against the clock, the game is lost when the time runs out
+    #[arg(long, default_value_t = false)]
+    time_attack: bool,
+
+    /// Seconds on the clock in time attack, defaults to 60, 240 and 600 depending on the difficulty
+    #[arg(long)]
+    time_limit: Option<u64>,
+
+    /// Seconds added to the clock in time attack for every opening cleared
+    #[arg(long, default_value_t = 0)]
+    opening_bonus: u64,
+
+    /// Number of mines that can be revealed before the game is lost
+    #[arg(long, default_value_t = 1)]
+    lives: u32,
+
+    /// Most mines a single cell can hold, flags cycle up to this count
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u8).range(1..=9))]
+    max_mines_per_cell: u8,
+
+    /// Which cells count as neighbours of a cell
+    #[arg(long, value_enum, default_value_t = neighborhood::Neighborhood::Moore)]
+    neighborhood: neighborhood::Neighborhood,
+
+    /// Shape of the cells, hex cells always have the six touching cells as neighbours
+    #[arg(long, value_enum, default_value_t = topology::Grid::Square)]
+    grid: topology::Grid,
+
+    /// Join the opposite edges of the board, so every cell has neighbours on all sides
+    #[arg(long, default_value_t = false)]
+    wrap: bool,
+
+    /// Number of layers of the board, cells touch the 26 cells around them in 3D
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u8).range(1..=8))]
+    depth: u8,
+
+    /// Shape of the board, one of the built-in masks (circle, donut, heart) or a mask file
+    #[arg(long)]
+    mask: Option<String>,
+
+    /// Play on a board without edges, the score is the number of cells opened before the first mine
+    #[arg(long, default_value_t = false)]
+    endless: bool,
+
+    /// World file of the endless mode, it is resumed when it exists and written on export
+    #[arg(long)]
+    world: Option<PathBuf>,
+
+    /// Race a second player on the same board side by side, with wasd, space, f and c against the arrows, enter, '.' and ','
+    #[arg(long, default_value_t = false)]
+    split_screen: bool,
+
+    /// Stream the game to viewers on this address, e.g. 127.0.0.1:7879, they follow it with the watch command
+    #[arg(long)]
+    broadcast: Option<String>,
+
+    /// Send ranked wins to a team leaderboard at this URL and show its best times on the scoreboard
+    #[cfg(feature = "leaderboard")]
+    #[arg(long)]
+    leaderboard: Option<String>,
+
+    /// Name to send to the leaderboard, the user name by default
+    #[cfg(feature = "leaderboard")]
+    #[arg(long)]
+    leaderboard_name: Option<String>,
+
+    /// Play without the ui, reading commands from stdin and writing the board to stdout, for programs that play the game
+    #[arg(long, default_value_t = false)]
+    protocol: bool,
+
+    /// Play Minesweeper Flags, two players take turns to find the most mines on one board
+    #[arg(long, default_value_t = false)]
+    flags: bool,
+
+    /// Play Minesweeper Flags against the computer, as the first player
+    #[arg(long, value_enum)]
+    flags_ai: Option<flags_bot::Strength>,
+
+    /// Milliseconds the computer takes at least for a move in Minesweeper Flags
+    #[arg(long, default_value_t = 800)]
+    flags_ai_delay: u64,
+}
+
+#[derive(Subcommand, Clone)]
+enum Command {
+    /// Let the solver play seeded games without the ui and report how it did
+    Bench {
+        /// Number of games to play per difficulty
+        #[arg(short, long, default_value_t = 100)]
+        games: usize,
+
+        /// Seed of the first game, the following games use the next seeds
+        #[arg(short, long, default_value_t = 0)]
+        seed: u64,
+
+        /// Difficulties to play, all of them by default
+        #[arg(short, long, value_enum)]
+        difficulty: Vec<app::Difficulty>,
+
+        /// Number of threads to play on
+        #[arg(short, long, default_value_t = thread::available_parallelism().map_or(1, |threads| threads.get()))]
+        threads: usize,
+
+        /// Print the report as JSON instead of a table
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
+    /// Wait for another player to join and race them on the same board
+    Host {
+        /// Address to listen on
+        #[arg(default_value = "127.0.0.1:7878")]
+        address: String,
+
+        /// Difficulty of the race
+        #[arg(short, long, value_enum, default_value_t = app::Difficulty::Easy)]
+        difficulty: app::Difficulty,
+
+        /// Seed of the board, a random one by default
+        #[arg(short, long)]
+        seed: Option<u64>,
+
+        /// Play one board together instead of racing, any number of players can join while it runs
+        #[arg(long, default_value_t = false)]
+        coop: bool,
+    },
+    /// Join a race hosted by another player, the host picks the board and the rules
+    Join {
+        /// Address of the host
+        #[arg(default_value = "127.0.0.1:7878")]
+        address: String,
+
+        /// Join a co-op game instead of a race
+        #[arg(long, default_value_t = false)]
+        coop: bool,
+    },
+    /// Follow a game broadcast by another player without playing
+    Watch {
+        /// Address the game is broadcast on
+        #[arg(default_value = "127.0.0.1:7879")]
+        address: String,
+
+        /// Seconds to show the game behind the player, so it can not be used to help them
+        #[arg(long, default_value_t = 0)]
+        delay: u64,
+    },
+    /// Serve the game over SSH, every player who connects gets their own game and all of them share the scoreboard
+    #[cfg(feature = "ssh")]
+    Serve {
+        /// Address to listen on
+        #[arg(default_value = "127.0.0.1:2222")]
+        address: String,
+
+        /// Private key the server proves itself with, made on the first start
+        #[arg(long)]
+        host_key: Option<PathBuf>,
+    },
 }
 
 fn main() -> Result<(), io::Error> {
-    let args = Args::parse();
+    let mut args = Args::parse();
+
+    // The solver only understands cells with a single mine
+    if args.max_mines_per_cell > 1 && (args.autoplay || args.flags_ai.is_some() || matches!(args.command, Some(Command::Bench { .. }))) {
+        eprintln!("The bot can not play with more than one mine per cell");
+        std::process::exit(1);
+    }
+    if args.grid == topology::Grid::Hex && args.neighborhood != neighborhood::Neighborhood::Moore {
+        eprintln!("Hex boards always use the six touching cells as neighbours");
+        std::process::exit(1);
+    }
+    if args.endless && args.autoplay {
+        eprintln!("The bot can not play the endless mode");
+        std::process::exit(1);
+    }
+    if args.endless && (args.grid != topology::Grid::Square || args.wrap || args.depth > 1 || args.mask.is_some() ||
+        args.lives > 1 || args.max_mines_per_cell > 1) {
+        eprintln!("The endless mode is played on flat square cells with single mines and one life, without wrap-around or a mask");
+        std::process::exit(1);
+    }
+    if args.daily && args.time_attack {
+        eprintln!("The daily challenge is played without the time attack clock");
+        std::process::exit(1);
+    }
+    // Everyone plays the same daily board, only the neighbourhood rule has its own challenge
+    if args.daily && (args.grid != topology::Grid::Square || args.wrap || args.mask.is_some() || args.depth > 1 ||
+        args.max_mines_per_cell > 1 || args.lives > 1) {
+        eprintln!("The daily challenge is played on flat square cells with single mines and one life, without wrap-around or a mask");
+        std::process::exit(1);
+    }
+    if args.depth > 1 && (args.grid != topology::Grid::Square || args.neighborhood != neighborhood::Neighborhood::Moore) {
+        eprintln!("Layered boards only support square cells with the 8 surrounding neighbours");
+        std::process::exit(1);
+    }
+    if args.split_screen && (args.endless || args.flags || args.autoplay || args.daily || args.board.is_some() || args.replay.is_some() || args.command.is_some()) {
+        eprintln!("Split screen races are played on generated boards, without the bot, daily, endless, Flags or network modes");
+        std::process::exit(1);
+    }
+    if args.flags_ai.is_some() && !args.flags {
+        eprintln!("The computer only plays Minesweeper Flags, add --flags");
+        std::process::exit(1);
+    }
+    if args.flags && (args.endless || args.daily || args.time_attack || args.autoplay || args.lives > 1 || args.command.is_some()) {
+        eprintln!("Minesweeper Flags is played by two players in one terminal, without lives, a clock or the daily and endless modes");
+        std::process::exit(1);
+    }
+    if args.protocol && (args.endless || args.daily || args.time_attack || args.autoplay || args.flags || args.split_screen ||
+        args.broadcast.is_some() || args.board.is_some() || args.replay.is_some() || args.command.is_some()) {
+        eprintln!("The protocol mode plays seeded boards only, without the clock, the bot or another game mode");
+        std::process::exit(1);
+    }
+    #[cfg(feature = "leaderboard")]
+    if args.protocol && args.leaderboard.is_some() {
+        eprintln!("Games played through the protocol mode are not sent to the leaderboard");
+        std::process::exit(1);
+    }
+    if args.grid == topology::Grid::Hex && args.wrap {
+        eprintln!("Only square boards can wrap around");
+        std::process::exit(1);
+    }
+
+    // Viewers rebuild the board from its seed and follow the actions on it
+    if args.broadcast.is_some() && (args.endless || args.flags || args.split_screen || args.mask.is_some() || args.board.is_some() ||
+        args.replay.is_some() || matches!(args.command, Some(Command::Watch { .. }))) {
+        eprintln!("Only generated boards without a mask can be broadcast, not endless, Flags, split screen or replayed games");
+        std::process::exit(1);
+    }
+
+    #[cfg(feature = "ssh")]
+    if matches!(args.command, Some(Command::Serve { .. })) && (args.endless || args.broadcast.is_some() || args.board.is_some() ||
+        args.replay.is_some()) {
+        eprintln!("Games served over SSH are played on generated boards, not endless, broadcast or loaded from a file");
+        std::process::exit(1);
+    }
+
+    let is_network_game = matches!(args.command, Some(Command::Host { .. } | Command::Join { .. } | Command::Watch { .. }));
+    if is_network_game && (args.endless || args.daily || args.mask.is_some() || args.board.is_some() || args.replay.is_some()) {
+        eprintln!("Network games are played on a generated board without a mask, daily or endless mode");
+        std::process::exit(1);
+    }
+
+    if let Some(Command::Bench { games, seed, difficulty, threads, json }) = &args.command {
+        let difficulties = if difficulty.is_empty() {
+            vec![app::Difficulty::Easy, app::Difficulty::Medium, app::Difficulty::Hard]
+        }
+        else {
+            difficulty.clone()
+        };
+        bench::run_bench(&args, *games, *seed, &difficulties, *threads, *json);
+        return Ok(());
+    }
+
+    let coop = match &args.command {
+        Some(Command::Host { address, difficulty, seed, coop: true }) => {
+            let rules = race::Rules::new(&args, *difficulty, seed.unwrap_or_else(rand::random));
+            match TcpListener::bind(address) {
+                Ok(listener) => Some(coop::Coop::host(listener, rules)),
+                Err(error) => {
+                    eprintln!("Failed to host a co-op game on {}: {}", address, error);
+                    std::process::exit(1);
+                },
+            }
+        },
+        Some(Command::Join { address, coop: true }) => match coop::Coop::join(address.as_str()) {
+            Ok(coop) => Some(coop),
+            Err(error) => {
+                eprintln!("Failed to join the co-op game on {}: {}", address, error);
+                std::process::exit(1);
+            },
+        },
+        _ => None,
+    };
+
+    let race = match &args.command {
+        Some(Command::Host { address, difficulty, seed, coop: false }) => {
+            let rules = race::Rules::new(&args, *difficulty, seed.unwrap_or_else(rand::random));
+            let result = TcpListener::bind(address).and_then(|listener| {
+                eprintln!("Waiting for a player to join on {}", listener.local_addr()?);
+                race::Race::host(&listener, rules)
+            });
+            match result {
+                Ok(race) => Some(race),
+                Err(error) => {
+                    eprintln!("Failed to host a race on {}: {}", address, error);
+                    std::process::exit(1);
+                },
+            }
+        },
+        Some(Command::Join { address, coop: false }) => match race::Race::join(address.as_str()) {
+            Ok(race) => Some(race),
+            Err(error) => {
+                eprintln!("Failed to join the race on {}: {}", address, error);
+                std::process::exit(1);
+            },
+        },
+        _ => None,
+    };
+
+    let watch = match &args.command {
+        Some(Command::Watch { address, delay }) => match broadcast::Watch::join(address.as_str(), Duration::from_secs(*delay)) {
+            Ok(watch) => Some(watch),
+            Err(error) => {
+                eprintln!("Failed to watch the game on {}: {}", address, error);
+                std::process::exit(1);
+            },
+        },
+        _ => None,
+    };
+    if let Some(watch) = &watch {
+        watch.get_rules().apply_to(&mut args);
+    }
+
+    // Both players need the same rules to get the same board
+    if let Some(race) = &race {
+        race.get_rules().apply_to(&mut args);
+    }
+    if let Some(coop) = &coop {
+        coop.get_rules().apply_to(&mut args);
+    }
+
+    let broadcast = match &args.broadcast {
+        // The board of every game comes with its snapshot, the difficulty and seed of these rules are not used
+        Some(address) => match TcpListener::bind(address) {
+            Ok(listener) => Some(broadcast::Broadcast::start(listener, race::Rules::new(&args, app::Difficulty::Easy, 0))),
+            Err(error) => {
+                eprintln!("Failed to broadcast on {}: {}", address, error);
+                std::process::exit(1);
+            },
+        },
+        None => None,
+    };
+
+    let layout = match &args.board {
+        Some(path) => match layout::read_layout(path) {
+            Ok(layout) => Some(layout),
+            Err(error) => {
+                eprintln!("Failed to load board {}: {}", path.display(), error);
+                std::process::exit(1);
+            },
+        },
+        None => None,
+    };
+
+    let mask = match &args.mask {
+        Some(name) => match layout::read_mask(name) {
+            Ok(mask) => Some(mask),
+            Err(error) => {
+                eprintln!("Failed to load mask {}: {} (built-in masks: {})", name, error, layout::BUILT_IN_MASKS.join(", "));
+                std::process::exit(1);
+            },
+        },
+        None => None,
+    };
+
+    let (width, height) = match &mask {
+        Some(mask) => (mask.get_width(), mask.get_height()),
+        None => board::Board::new().get_largest_size(),
+    };
+    if !layout::is_playable_size(width, height, args.depth as usize) {
+        eprintln!("A board of {}x{} cells with {} layers is too large, at most {} cells are supported", width, height, args.depth, layout::MAX_CELLS);
+        std::process::exit(1);
+    }
+
+    let world = match &args.world {
+        Some(path) if args.endless && path.exists() => match world::World::load(path) {
+            Ok(world) => Some(world),
+            Err(error) => {
+                eprintln!("Failed to load world {}: {}", path.display(), error);
+                std::process::exit(1);
+            },
+        },
+        _ => None,
+    };
+
+    let replay = match &args.replay {
+        Some(path) => match fs::read_to_string(path).map_err(|error| error.to_string())
+            .and_then(|text| interop::Replay::parse(&text).map_err(|error| error.to_string())) {
+            Ok(replay) => Some(replay),
+            Err(error) => {
+                eprintln!("Failed to load replay {}: {}", path.display(), error);
+                std::process::exit(1);
+            },
+        },
+        None => None,
+    };
+
+    #[cfg(feature = "ssh")]
+    if let Some(Command::Serve { address, host_key }) = &args.command {
+        let Some(path) = host_key.clone().or_else(server::get_default_host_key_path) else {
+            eprintln!("No place to keep the host key, pass --host-key");
+            std::process::exit(1);
+        };
+        let host_key = match server::load_host_key(&path) {
+            Ok(host_key) => host_key,
+            Err(error) => {
+                eprintln!("Failed to load host key {}: {}", path.display(), error);
+                std::process::exit(1);
+            },
+        };
+        let listener = match TcpListener::bind(address) {
+            Ok(listener) => listener,
+            Err(error) => {
+                eprintln!("Failed to serve on {}: {}", address, error);
+                std::process::exit(1);
+            },
+        };
+        server::serve(listener, host_key, args.clone(), mask);
+        return Ok(());
+    }
+
+    if args.protocol {
+        return protocol::run_protocol(args, mask, io::stdin().lock(), io::stdout().lock());
+    }
 
     enable_raw_mode().expect("Enable raw mode expect");
 
@@ -29,7 +500,29 @@ fn main() -> Result<(), io::Error> {
     thread::spawn(move || input_listener::listen_for_key_input(&tx));
 
     let mut app: app::App = app::App::new(args);
-    app.run(&rx).expect("Run expected");
+    app.set_mask(mask);
+    if let Some(world) = world {
+        app.start_world(world);
+    }
+    if let Some(layout) = layout {
+        app.start_layout_game(&layout);
+    }
+    if let Some(replay) = replay {
+        app.start_replay(replay);
+    }
+    if let Some(race) = race {
+        app.start_race(race);
+    }
+    if let Some(broadcast) = broadcast {
+        app.start_broadcast(broadcast);
+    }
+    if let Some(coop) = coop {
+        app.start_coop(coop);
+    }
+    if let Some(watch) = watch {
+        app.start_watch(watch);
+    }
+    app.run(&rx, io::stdout(), None).expect("Run expected");
 
     disable_raw_mode()?;
 
@@ -0,0 +1,59 @@
+use serde::{Deserialize, Serialize};
+
+// Which cells count as the neighbours of a cell, as offsets from it
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Neighborhood {
+    // The 8 surrounding cells
+    #[default]
+    Moore,
+    // Only the 4 cells sharing an edge
+    Orthogonal,
+    // The 8 cells a chess knight can jump to
+    Knight,
+    // The 24 cells within two steps in both directions
+    Radius2,
+}
+
+const MOORE_OFFSETS: [(i16, i16); 8] = [
+    (-1, -1), (0, -1), (1, -1),
+    (-1, 0), (1, 0),
+    (-1, 1), (0, 1), (1, 1),
+];
+
+const ORTHOGONAL_OFFSETS: [(i16, i16); 4] = [(0, -1), (-1, 0), (1, 0), (0, 1)];
+
+const KNIGHT_OFFSETS: [(i16, i16); 8] = [
+    (-1, -2), (1, -2),
+    (-2, -1), (2, -1),
+    (-2, 1), (2, 1),
+    (-1, 2), (1, 2),
+];
+
+const RADIUS_2_OFFSETS: [(i16, i16); 24] = [
+    (-2, -2), (-1, -2), (0, -2), (1, -2), (2, -2),
+    (-2, -1), (-1, -1), (0, -1), (1, -1), (2, -1),
+    (-2, 0), (-1, 0), (1, 0), (2, 0),
+    (-2, 1), (-1, 1), (0, 1), (1, 1), (2, 1),
+    (-2, 2), (-1, 2), (0, 2), (1, 2), (2, 2),
+];
+
+impl Neighborhood {
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            Neighborhood::Moore => "moore",
+            Neighborhood::Orthogonal => "orthogonal",
+            Neighborhood::Knight => "knight",
+            Neighborhood::Radius2 => "radius2",
+        }
+    }
+
+    pub fn get_offsets(&self) -> &'static [(i16, i16)] {
+        match self {
+            Neighborhood::Moore => &MOORE_OFFSETS,
+            Neighborhood::Orthogonal => &ORTHOGONAL_OFFSETS,
+            Neighborhood::Knight => &KNIGHT_OFFSETS,
+            Neighborhood::Radius2 => &RADIUS_2_OFFSETS,
+        }
+    }
+}
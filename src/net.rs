@@ -0,0 +1,25 @@
+use std::{
+    io::{self, BufRead, Write},
+    time::Duration,
+};
+use serde::{de::DeserializeOwned, Serialize};
+
+// Network games send one JSON message per line
+
+// Messages are sent from the game loop, a peer that does not read them in time is dropped
+pub const WRITE_TIMEOUT: Duration = Duration::from_millis(200);
+
+pub fn write_message<W: Write, M: Serialize>(mut writer: W, message: &M) -> io::Result<()> {
+    let mut line = serde_json::to_string(message)?;
+    line.push('\n');
+    writer.write_all(line.as_bytes())
+}
+
+// Lines that are not a known message fail with InvalidData, a closed connection with UnexpectedEof
+pub fn read_message<R: BufRead, M: DeserializeOwned>(reader: &mut R) -> io::Result<M> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Connection closed"));
+    }
+    Ok(serde_json::from_str(&line)?)
+}
@@ -0,0 +1,184 @@
+use std::io::{self, BufRead, Write};
+
+use crate::{
+    Args,
+    app::{App, Difficulty},
+    interop::ReplayAction,
+    layout::Mask,
+};
+
+// External programs play through stdin and stdout, one command per line. Coordinates count from
+// 0, x is the column, y the row and the optional z the layer of a layered board:
+//   new DIFFICULTY [SEED]   start a game on the board of the seed, 0 by default
+//   open X Y [Z]            open a cell
+//   flag X Y [Z]            add a flag to a cell, or take the flags off once a cell holds the most it can
+//   chord X Y [Z]           open around a number whose mines are flagged
+//   state                   show the game again
+//   quit
+// Every command other than quit is answered with a single error line or with the game:
+//   error REASON
+//   status playing|won|lost
+//   size WIDTH HEIGHT DEPTH
+//   mines COUNT             mines left to flag, can be negative
+//   lives COUNT
+//   HEIGHT rows of WIDTH characters for every layer, '#' a closed cell, 'F' a flag, '*' an open
+//   mine, '.' an empty cell, '1'-'9' then 'a'-'z' the number of mines around a cell, '+' for more
+//   than 35 of them, and ' ' a cell that a mask left out of the board
+//   end
+
+pub fn run_protocol<R: BufRead, W: Write>(args: Args, mask: Option<Mask>, input: R, mut output: W) -> io::Result<()> {
+    let mut app: App = App::new(args);
+    app.set_mask(mask);
+    let mut is_started = false;
+
+    for line in input.lines() {
+        let line = line?;
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let result = match words.as_slice() {
+            [] => continue,
+            ["quit"] => break,
+            ["new", arguments @ ..] => start_game(&mut app, arguments).map(|()| is_started = true),
+            _ if !is_started => Err("No game is running, start one with new".to_string()),
+            ["state"] => Ok(()),
+            [command, arguments @ ..] => match get_action(command) {
+                Some(action) => get_index(&app, arguments)
+                    .and_then(|index| app.play_action_at(action, index).map_err(str::to_string)),
+                None => Err(format!("Unknown command {}", command)),
+            },
+        };
+
+        match result {
+            Ok(()) => write_state(&mut output, &app)?,
+            Err(error) => writeln!(output, "error {}", error)?,
+        }
+        output.flush()?;
+    }
+    Ok(())
+}
+
+fn start_game(app: &mut App, arguments: &[&str]) -> Result<(), String> {
+    let (difficulty, seed) = match arguments {
+        [difficulty] => (*difficulty, "0"),
+        [difficulty, seed] => (*difficulty, *seed),
+        _ => return Err("Expected new DIFFICULTY [SEED]".to_string()),
+    };
+    let difficulty = match difficulty {
+        "easy" => Difficulty::Easy,
+        "medium" => Difficulty::Medium,
+        "hard" => Difficulty::Hard,
+        _ => return Err(format!("Unknown difficulty {}, expected easy, medium or hard", difficulty)),
+    };
+    let seed = seed.parse().map_err(|_| format!("Invalid seed {}", seed))?;
+
+    app.start_seeded_game(difficulty, seed);
+    Ok(())
+}
+
+fn get_action(command: &str) -> Option<ReplayAction> {
+    match command {
+        "open" => Some(ReplayAction::Open),
+        "flag" => Some(ReplayAction::Flag),
+        "chord" => Some(ReplayAction::Chord),
+        _ => None,
+    }
+}
+
+fn get_index(app: &App, arguments: &[&str]) -> Result<usize, String> {
+    let board = app.get_board();
+    let coordinates = arguments.iter()
+        .map(|argument| argument.parse::<usize>().map_err(|_| format!("Invalid coordinate {}", argument)))
+        .collect::<Result<Vec<usize>, String>>()?;
+    let (x, y, z) = match coordinates.as_slice() {
+        [x, y] => (*x, *y, 0),
+        [x, y, z] => (*x, *y, *z),
+        _ => return Err("Expected the coordinates X Y [Z]".to_string()),
+    };
+
+    if x >= board.get_board_width() || y >= board.get_board_height() || z >= board.get_depth() {
+        return Err(format!("{} {} {} is outside of the board", x, y, z));
+    }
+    Ok(z * board.get_layer_size() + y * board.get_board_width() + x)
+}
+
+fn write_state<W: Write>(output: &mut W, app: &App) -> io::Result<()> {
+    let board = app.get_board();
+    let status = if app.get_is_victory() {
+        "won"
+    }
+    else if app.get_is_game_over() {
+        "lost"
+    }
+    else {
+        "playing"
+    };
+    writeln!(output, "status {}", status)?;
+    writeln!(output, "size {} {} {}", board.get_board_width(), board.get_board_height(), board.get_depth())?;
+    writeln!(output, "mines {}", board.get_bomb_count() as i64 - board.get_exploded_count() as i64 - board.get_flag_count() as i64)?;
+    writeln!(output, "lives {}", app.get_lives().unwrap_or(if app.get_is_game_over() { 0 } else { 1 }))?;
+
+    for row in board.get_cells().chunks(board.get_board_width().max(1)) {
+        let row: String = row.iter()
+            .map(|cell| {
+                if cell.is_disabled() {
+                    ' '
+                }
+                else if !cell.is_open() {
+                    if cell.is_flagged() { 'F' } else { '#' }
+                }
+                else if cell.is_bomb() {
+                    '*'
+                }
+                else if cell.get_value() == 0 {
+                    '.'
+                }
+                else {
+                    char::from_digit(cell.get_value() as u32, 36).unwrap_or('+')
+                }
+            })
+            .collect();
+        writeln!(output, "{}", row)?;
+    }
+    writeln!(output, "end")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    fn run(commands: &str) -> String {
+        let args = Args::parse_from(["minesweeper-tui", "--protocol"]);
+        let mut output: Vec<u8> = vec![];
+        run_protocol(args, None, commands.as_bytes(), &mut output).expect("protocol expect");
+        String::from_utf8(output).expect("utf8 expect")
+    }
+
+    #[test]
+    fn test_protocol(){
+        let output = run("state\nnew easy 42\nflag 0 0\nstate\nquit\nstate\n");
+        let replies: Vec<&str> = output.split_inclusive("end\n").collect();
+        assert_eq!(replies.len(), 3);
+        assert!(replies[0].starts_with("error No game is running"));
+        assert!(replies[0].contains("status playing\nsize 9 9 1\nmines 10\n"));
+        assert!(replies[1].starts_with("status playing\nsize 9 9 1\nmines 9\nlives 1\nF########\n"));
+        assert_eq!(replies[1], replies[2]);
+
+        // The same seed always gives the same board
+        assert_eq!(run("new medium 7\nopen 3 4\n"), run("new medium 7\nopen 3 4\n"));
+    }
+
+    #[test]
+    fn test_protocol_errors(){
+        let output = run("new easy 1\nopen 9 0\nopen 1\nopen a b\ndig 1 1\nnew easy x\nchord 0 0\nflag 0 0\nopen 0 0\n");
+        let errors: Vec<&str> = output.lines().filter(|line| line.starts_with("error")).collect();
+        assert_eq!(errors, [
+            "error 9 0 0 is outside of the board",
+            "error Expected the coordinates X Y [Z]",
+            "error Invalid coordinate a",
+            "error Unknown command dig",
+            "error Invalid seed x",
+            "error Only open cells can be chorded",
+            "error That cell is flagged",
+        ]);
+    }
+}
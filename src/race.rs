@@ -0,0 +1,399 @@
+use std::{
+    io::{self, BufReader},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    sync::mpsc::{self, Receiver},
+    thread,
+    time::{Duration, Instant},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Args,
+    app::Difficulty,
+    net::{read_message, write_message},
+    neighborhood::Neighborhood,
+    topology::Grid,
+};
+
+// Both players of a race send one JSON message per line:
+//   client -> host  {"type":"hello","version":2}
+//   host -> client  {"type":"start","version":2,"rules":{...}} or {"type":"reject","reason":"..."}
+//   both ways       {"type":"progress","percent":40,"lives":1,"state":"playing"} whenever it changes
+//   host -> client  {"type":"winner","host":true} once the race is decided
+// The host decides the winner so both sides always agree on it. Messages of an unknown type are
+// skipped, so later versions can add messages without breaking the handshake.
+pub const PROTOCOL_VERSION: u32 = 2;
+
+// Time between the handshake and the start of both games
+const COUNTDOWN: Duration = Duration::from_secs(3);
+// A player that does not finish the handshake in time is dropped
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(3);
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlayerState {
+    Playing,
+    Won,
+    Lost,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct Progress {
+    // Share of the safe cells opened
+    percent: u8,
+    lives: u32,
+    state: PlayerState,
+}
+
+impl Progress {
+    pub fn new(percent: u8, lives: u32, state: PlayerState) -> Self {
+        Self {
+            percent,
+            lives,
+            state,
+        }
+    }
+
+    pub fn get_percent(&self) -> u8 {
+        self.percent
+    }
+
+    pub fn get_lives(&self) -> u32 {
+        self.lives
+    }
+
+    pub fn get_state(&self) -> PlayerState {
+        self.state
+    }
+}
+
+// Everything the joining player needs to generate the same board as the host
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Rules {
+    difficulty: Difficulty,
+    seed: u64,
+    lives: u32,
+    max_mines_per_cell: u8,
+    neighborhood: Neighborhood,
+    grid: Grid,
+    wrap: bool,
+    depth: u8,
+    time_attack: bool,
+    time_limit: Option<u64>,
+    hint_penalty: u64,
+    opening_bonus: u64,
+}
+
+impl Rules {
+    pub fn new(args: &Args, difficulty: Difficulty, seed: u64) -> Self {
+        Self {
+            difficulty,
+            seed,
+            lives: args.lives,
+            max_mines_per_cell: args.max_mines_per_cell,
+            neighborhood: args.neighborhood,
+            grid: args.grid,
+            wrap: args.wrap,
+            depth: args.depth,
+            time_attack: args.time_attack,
+            time_limit: args.time_limit,
+            hint_penalty: args.hint_penalty,
+            opening_bonus: args.opening_bonus,
+        }
+    }
+
+    pub fn apply_to(&self, args: &mut Args) {
+        args.lives = self.lives;
+        args.max_mines_per_cell = self.max_mines_per_cell;
+        args.neighborhood = self.neighborhood;
+        args.grid = self.grid;
+        args.wrap = self.wrap;
+        args.depth = self.depth;
+        args.time_attack = self.time_attack;
+        args.time_limit = self.time_limit;
+        args.hint_penalty = self.hint_penalty;
+        args.opening_bonus = self.opening_bonus;
+    }
+
+    pub fn get_difficulty(&self) -> Difficulty {
+        self.difficulty
+    }
+
+    pub fn get_seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum Message {
+    Hello { version: u32 },
+    Start { version: u32, rules: Rules },
+    Reject { reason: String },
+    Progress(Progress),
+    Winner { host: bool },
+}
+
+pub struct Race {
+    stream: TcpStream,
+    // Messages of the other player, None once the connection is lost
+    rx: Receiver<Option<Message>>,
+    is_host: bool,
+    rules: Rules,
+    start_at: Instant,
+    sent_progress: Option<Progress>,
+    opponent: Progress,
+    is_connected: bool,
+    // Whether this player won, once the race is decided
+    winner: Option<bool>,
+}
+
+impl Race {
+    // Wait for a player with the same protocol version to join and send them the rules
+    pub fn host(listener: &TcpListener, rules: Rules) -> io::Result<Self> {
+        loop {
+            let (stream, _) = listener.accept()?;
+            let mut reader = BufReader::new(stream.try_clone()?);
+            stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+            let reply = match read_message(&mut reader) {
+                Ok(Message::Hello { version }) if version == PROTOCOL_VERSION => {
+                    write_message(&stream, &Message::Start { version, rules: rules.clone() })?;
+                    stream.set_read_timeout(None)?;
+                    return Ok(Self::new(stream, reader, true, rules));
+                },
+                Ok(Message::Hello { version }) => Message::Reject {
+                    reason: format!("Protocol version {} is not supported, the host uses version {}", version, PROTOCOL_VERSION),
+                },
+                _ => Message::Reject { reason: "Expected a hello message".to_string() },
+            };
+            // A failed handshake only drops that connection, the host keeps waiting
+            let _ = write_message(&stream, &reply);
+        }
+    }
+
+    pub fn join<A: ToSocketAddrs>(address: A) -> io::Result<Self> {
+        let stream = TcpStream::connect(address)?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        // The host may still be waiting on a silent client before it answers, so allow for one timeout
+        stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT * 2))?;
+        write_message(&stream, &Message::Hello { version: PROTOCOL_VERSION })?;
+
+        match read_message(&mut reader)? {
+            Message::Start { version, rules } if version == PROTOCOL_VERSION => {
+                stream.set_read_timeout(None)?;
+                Ok(Self::new(stream, reader, false, rules))
+            },
+            Message::Start { version, .. } => Err(io::Error::new(io::ErrorKind::InvalidData,
+                                                                 format!("The host uses protocol version {}", version))),
+            Message::Reject { reason } => Err(io::Error::new(io::ErrorKind::ConnectionRefused, reason)),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidData, "Expected a start message")),
+        }
+    }
+
+    fn new(stream: TcpStream, mut reader: BufReader<TcpStream>, is_host: bool, rules: Rules) -> Self {
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || loop {
+            match read_message(&mut reader) {
+                Ok(message) => {
+                    if tx.send(Some(message)).is_err() {
+                        return;
+                    }
+                },
+                Err(error) if error.kind() == io::ErrorKind::InvalidData => { },
+                Err(_) => {
+                    let _ = tx.send(None);
+                    return;
+                },
+            }
+        });
+
+        Self {
+            stream,
+            rx,
+            is_host,
+            start_at: Instant::now() + COUNTDOWN,
+            sent_progress: None,
+            opponent: Progress::new(0, rules.lives, PlayerState::Playing),
+            rules,
+            is_connected: true,
+            winner: None,
+        }
+    }
+
+    // Send the progress of this player when it changed and read what the other player sent
+    pub fn update(&mut self, progress: Progress) {
+        while let Ok(message) = self.rx.try_recv() {
+            match message {
+                Some(Message::Progress(opponent)) => self.opponent = opponent,
+                Some(Message::Winner { host }) if self.winner.is_none() => self.winner = Some(host == self.is_host),
+                Some(_) => { },
+                None => self.is_connected = false,
+            }
+        }
+
+        if self.is_connected && self.sent_progress != Some(progress) {
+            match write_message(&self.stream, &Message::Progress(progress)) {
+                Ok(()) => self.sent_progress = Some(progress),
+                Err(_) => self.is_connected = false,
+            }
+        }
+
+        if self.winner.is_some() {
+            return;
+        }
+        if !self.is_connected {
+            // Nobody is left to race against
+            self.winner = Some(progress.state != PlayerState::Lost);
+        }
+        else if self.is_host {
+            self.winner = decide_winner(progress, self.opponent);
+            if let Some(host) = self.winner {
+                let _ = write_message(&self.stream, &Message::Winner { host });
+            }
+        }
+    }
+
+    pub fn get_rules(&self) -> &Rules {
+        &self.rules
+    }
+
+    // Time left before both games start
+    pub fn get_countdown(&self) -> Duration {
+        self.start_at.saturating_duration_since(Instant::now())
+    }
+
+    pub fn get_opponent(&self) -> Progress {
+        self.opponent
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.is_connected
+    }
+
+    pub fn get_winner(&self) -> Option<bool> {
+        self.winner
+    }
+}
+
+// Clearing the board wins the race, losing it hands the race to the other player.
+// Returns whether the first player won, once one of them is done
+pub fn decide_winner(first: Progress, second: Progress) -> Option<bool> {
+    match (first.state, second.state) {
+        (PlayerState::Won, _) => Some(true),
+        (_, PlayerState::Won) => Some(false),
+        (PlayerState::Lost, _) => Some(false),
+        (_, PlayerState::Lost) => Some(true),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    fn wait_for<F: FnMut() -> bool>(mut condition: F) {
+        let start = Instant::now();
+        while !condition() {
+            assert!(start.elapsed() < Duration::from_secs(5), "timed out");
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    fn start_race() -> (Race, Race) {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind expect");
+        let address = listener.local_addr().expect("address expect");
+        let client = thread::spawn(move || Race::join(address).expect("join expect"));
+        let args = Args::parse_from(["minesweeper-tui", "--lives", "2", "--time-attack", "--time-limit", "90"]);
+        let host = Race::host(&listener, Rules::new(&args, Difficulty::Medium, 42)).expect("host expect");
+        (host, client.join().expect("client expect"))
+    }
+
+    #[test]
+    fn test_race(){
+        let (mut host, mut client) = start_race();
+        assert_eq!(client.get_rules().get_seed(), 42);
+        assert!(client.get_rules().get_difficulty() == Difficulty::Medium);
+        let mut args = Args::parse_from(["minesweeper-tui"]);
+        client.get_rules().apply_to(&mut args);
+        assert_eq!(args.lives, 2);
+        assert!(args.time_attack);
+        assert_eq!(args.time_limit, Some(90));
+
+        client.update(Progress::new(40, 1, PlayerState::Playing));
+        wait_for(|| {
+            host.update(Progress::new(10, 2, PlayerState::Playing));
+            host.get_opponent().get_percent() == 40
+        });
+        assert_eq!(host.get_opponent().get_lives(), 1);
+        assert_eq!(host.get_winner(), None);
+
+        // The client clears its board first, the host declares it the winner on both sides
+        client.update(Progress::new(100, 1, PlayerState::Won));
+        wait_for(|| {
+            host.update(Progress::new(10, 2, PlayerState::Playing));
+            host.get_winner().is_some()
+        });
+        assert_eq!(host.get_winner(), Some(false));
+        wait_for(|| {
+            client.update(Progress::new(100, 1, PlayerState::Won));
+            client.get_winner().is_some()
+        });
+        assert_eq!(client.get_winner(), Some(true));
+    }
+
+    #[test]
+    fn test_race_disconnect(){
+        let (host, mut client) = start_race();
+        drop(host);
+        wait_for(|| {
+            client.update(Progress::new(0, 2, PlayerState::Playing));
+            !client.is_connected()
+        });
+        assert_eq!(client.get_winner(), Some(true));
+    }
+
+    #[test]
+    fn test_protocol_version(){
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind expect");
+        let address = listener.local_addr().expect("address expect");
+        let args = Args::parse_from(["minesweeper-tui"]);
+        let host = thread::spawn(move || Race::host(&listener, Rules::new(&args, Difficulty::Easy, 1)).map(|_| ()));
+
+        // An old client is turned away and the host keeps waiting for another player
+        let stream = TcpStream::connect(address).expect("connect expect");
+        write_message(&stream, &Message::Hello { version: 0 }).expect("write expect");
+        let reply: Message = read_message(&mut BufReader::new(&stream)).expect("read expect");
+        assert!(matches!(reply, Message::Reject { .. }));
+
+        let client = Race::join(address).expect("join expect");
+        assert_eq!(client.get_rules().get_seed(), 1);
+        host.join().expect("host expect").expect("race expect");
+    }
+
+    #[test]
+    fn test_silent_client(){
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind expect");
+        let address = listener.local_addr().expect("address expect");
+        let args = Args::parse_from(["minesweeper-tui"]);
+        let host = thread::spawn(move || Race::host(&listener, Rules::new(&args, Difficulty::Easy, 1)).map(|_| ()));
+
+        // A client that never says hello does not keep the host from the next player
+        let _stream = TcpStream::connect(address).expect("connect expect");
+        let client = Race::join(address).expect("join expect");
+        assert_eq!(client.get_rules().get_seed(), 1);
+        host.join().expect("host expect").expect("race expect");
+    }
+
+    #[test]
+    fn test_silent_host(){
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind expect");
+        let address = listener.local_addr().expect("address expect");
+
+        // A host that never answers the hello does not leave the player waiting forever
+        let start = Instant::now();
+        assert!(Race::join(address).is_err());
+        assert!(start.elapsed() < HANDSHAKE_TIMEOUT * 3);
+        drop(listener);
+    }
+}
@@ -1,26 +1,264 @@
-use std::env;
+use std::{
+    fs,
+    io,
+    path::PathBuf,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app::Difficulty,
+    neighborhood::Neighborhood,
+    stats::GameStats,
+};
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ScoreRecord {
+    difficulty: Difficulty,
+    // Game time in seconds, including hint penalties
+    time: f64,
+    date: String,
+    seed: Option<u64>,
+    hints: u32,
+    ranked: bool,
+    #[serde(default)]
+    neighborhood: Neighborhood,
+}
+
+impl ScoreRecord {
+    pub fn new(difficulty: Difficulty, time: f64, date: String, seed: Option<u64>, hints: u32, ranked: bool, neighborhood: Neighborhood) -> Self {
+        Self { difficulty, time, date, seed, hints, ranked, neighborhood }
+    }
+
+    pub fn get_time(&self) -> f64 {
+        self.time
+    }
+
+    pub fn get_date(&self) -> &str {
+        &self.date
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct DailyRecord {
+    date: String,
+    difficulty: Difficulty,
+    victory: bool,
+    time: f64,
+    // Played again after an earlier attempt on the same day
+    retry: bool,
+    #[serde(default)]
+    neighborhood: Neighborhood,
+    #[serde(default)]
+    hints: u32,
+    #[serde(default = "is_ranked_by_default")]
+    ranked: bool,
+}
+
+// Daily results saved before the ranking was recorded were all played without help
+fn is_ranked_by_default() -> bool {
+    true
+}
+
+impl DailyRecord {
+    pub fn new(date: String, difficulty: Difficulty, victory: bool, time: f64, retry: bool, neighborhood: Neighborhood, stats: &GameStats) -> Self {
+        Self { date, difficulty, victory, time, retry, neighborhood, hints: stats.get_hint_count(), ranked: stats.is_ranked() }
+    }
+
+    pub fn is_victory(&self) -> bool {
+        self.victory
+    }
+
+    pub fn get_time(&self) -> f64 {
+        self.time
+    }
+
+    pub fn is_retry(&self) -> bool {
+        self.retry
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct ScoreboardData {
+    #[serde(default)]
+    scores: Vec<ScoreRecord>,
+    #[serde(default)]
+    daily: Vec<DailyRecord>,
+    #[serde(default)]
+    time_attack: Vec<ScoreRecord>,
+}
 
 pub struct Scoreboard {
-    
+    path: Option<PathBuf>,
+    data: ScoreboardData,
 }
 
 impl Scoreboard {
     pub fn new() -> Self {
         Self {
-            
+            path: None,
+            data: ScoreboardData::default(),
+        }
+    }
+
+    // A missing or unreadable file gives an empty scoreboard that is written on the first save
+    pub fn load(path: PathBuf) -> Self {
+        let data = fs::read_to_string(&path)
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default();
+
+        Self {
+            path: Some(path),
+            data,
+        }
+    }
+
+    pub fn get_default_path() -> Option<PathBuf> {
+        dirs::home_dir().map(|home| home.join(".minesweeper-tui").join("scoreboard.json"))
+    }
+
+    pub fn save_score(&mut self, record: ScoreRecord) -> io::Result<()> {
+        self.data.scores.push(record);
+        self.save()
+    }
+
+    pub fn save_time_attack_score(&mut self, record: ScoreRecord) -> io::Result<()> {
+        self.data.time_attack.push(record);
+        self.save()
+    }
+
+    pub fn save_daily_result(&mut self, record: DailyRecord) -> io::Result<()> {
+        self.data.daily.push(record);
+        self.save()
+    }
+
+    fn save(&self) -> io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory)?;
         }
+        let text = serde_json::to_string_pretty(&self.data)?;
+        fs::write(path, text)
+    }
+
+    // Fastest ranked wins of a difficulty played with a neighbourhood rule
+    pub fn get_best_scores(&self, difficulty: Difficulty, neighborhood: Neighborhood, count: usize) -> Vec<&ScoreRecord> {
+        get_best_records(&self.data.scores, difficulty, neighborhood, count)
     }
 
-    pub fn save_score() {
-        
+    pub fn get_best_time_attack_scores(&self, difficulty: Difficulty, neighborhood: Neighborhood, count: usize) -> Vec<&ScoreRecord> {
+        get_best_records(&self.data.time_attack, difficulty, neighborhood, count)
     }
 
-    fn get_home_path() -> String {
-        // TODO Change from deprecated function to a crate
-        // match env::home_dir() {
-        //     Some(path) => return path.display().to_string(),
-        //     None => return "".to_string()
-        // }
-        "".to_string()
+    // Daily results of a day, first attempts before retries and wins by time. Games played with help are left out
+    pub fn get_daily_results(&self, date: &str, difficulty: Difficulty, neighborhood: Neighborhood) -> Vec<&DailyRecord> {
+        let mut results: Vec<&DailyRecord> = self.get_daily_attempts(date, difficulty, neighborhood).into_iter()
+            .filter(|result| result.ranked)
+            .collect();
+        results.sort_by(|a, b| (a.retry, !a.victory).cmp(&(b.retry, !b.victory)).then(a.time.total_cmp(&b.time)));
+        results
+    }
+
+    // Any attempt makes the next one a retry, ranked or not
+    pub fn has_played_daily(&self, date: &str, difficulty: Difficulty, neighborhood: Neighborhood) -> bool {
+        !self.get_daily_attempts(date, difficulty, neighborhood).is_empty()
+    }
+
+    pub fn is_daily_completed(&self, date: &str, difficulty: Difficulty, neighborhood: Neighborhood) -> bool {
+        self.get_daily_attempts(date, difficulty, neighborhood).iter().any(|result| result.victory)
+    }
+
+    fn get_daily_attempts(&self, date: &str, difficulty: Difficulty, neighborhood: Neighborhood) -> Vec<&DailyRecord> {
+        self.data.daily.iter()
+            .filter(|result| result.date == date && result.difficulty == difficulty && result.neighborhood == neighborhood)
+            .collect()
+    }
+}
+
+fn get_best_records(records: &[ScoreRecord], difficulty: Difficulty, neighborhood: Neighborhood, count: usize) -> Vec<&ScoreRecord> {
+    let mut scores: Vec<&ScoreRecord> = records.iter()
+        .filter(|score| score.difficulty == difficulty && score.neighborhood == neighborhood && score.ranked)
+        .collect();
+    scores.sort_by(|a, b| a.time.total_cmp(&b.time));
+    scores.truncate(count);
+    scores
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn score(difficulty: Difficulty, time: f64, ranked: bool, neighborhood: Neighborhood) -> ScoreRecord {
+        ScoreRecord::new(difficulty, time, "2024-01-01".to_string(), None, 0, ranked, neighborhood)
+    }
+
+    #[test]
+    fn test_best_scores(){
+        let mut scoreboard: Scoreboard = Scoreboard::new();
+        scoreboard.save_score(score(Difficulty::Easy, 30.0, true, Neighborhood::Moore)).unwrap();
+        scoreboard.save_score(score(Difficulty::Easy, 10.0, false, Neighborhood::Moore)).unwrap();
+        scoreboard.save_score(score(Difficulty::Easy, 20.0, true, Neighborhood::Moore)).unwrap();
+        scoreboard.save_score(score(Difficulty::Hard, 5.0, true, Neighborhood::Moore)).unwrap();
+
+        let scores = scoreboard.get_best_scores(Difficulty::Easy, Neighborhood::Moore, 5);
+        assert_eq!(scores.len(), 2);
+        assert_eq!(scores[0].get_time(), 20.0);
+        assert_eq!(scores[1].get_time(), 30.0);
+
+        // Time attack games are kept apart from normal games
+        assert!(scoreboard.get_best_time_attack_scores(Difficulty::Easy, Neighborhood::Moore, 5).is_empty());
+        scoreboard.save_time_attack_score(score(Difficulty::Easy, 45.0, true, Neighborhood::Moore)).unwrap();
+        assert_eq!(scoreboard.get_best_time_attack_scores(Difficulty::Easy, Neighborhood::Moore, 5)[0].get_time(), 45.0);
+        assert_eq!(scoreboard.get_best_scores(Difficulty::Easy, Neighborhood::Moore, 5).len(), 2);
+
+        // So are games played with other neighbourhood rules
+        scoreboard.save_score(score(Difficulty::Easy, 8.0, true, Neighborhood::Knight)).unwrap();
+        assert_eq!(scoreboard.get_best_scores(Difficulty::Easy, Neighborhood::Moore, 5)[0].get_time(), 20.0);
+        assert_eq!(scoreboard.get_best_scores(Difficulty::Easy, Neighborhood::Knight, 5)[0].get_time(), 8.0);
+    }
+
+    #[test]
+    fn test_daily_results(){
+        let mut scoreboard: Scoreboard = Scoreboard::new();
+        let date = "2024-03-04".to_string();
+        let daily = |victory: bool, time: f64, retry: bool| DailyRecord::new(date.clone(), Difficulty::Medium, victory, time, retry, Neighborhood::Moore, &GameStats::new());
+        assert!(!scoreboard.has_played_daily(&date, Difficulty::Medium, Neighborhood::Moore));
+
+        scoreboard.save_daily_result(daily(false, 12.0, false)).unwrap();
+        assert!(scoreboard.has_played_daily(&date, Difficulty::Medium, Neighborhood::Moore));
+        assert!(!scoreboard.is_daily_completed(&date, Difficulty::Medium, Neighborhood::Moore));
+
+        scoreboard.save_daily_result(daily(true, 40.0, true)).unwrap();
+        assert!(scoreboard.is_daily_completed(&date, Difficulty::Medium, Neighborhood::Moore));
+        assert!(!scoreboard.has_played_daily(&date, Difficulty::Easy, Neighborhood::Moore));
+        assert!(!scoreboard.has_played_daily(&date, Difficulty::Medium, Neighborhood::Knight));
+
+        let results = scoreboard.get_daily_results(&date, Difficulty::Medium, Neighborhood::Moore);
+        assert!(!results[0].is_retry());
+        assert!(results[1].is_retry() && results[1].is_victory());
+
+        // A game won with hints counts as played, but not in the standings
+        let mut stats = GameStats::new();
+        stats.add_hint(Duration::from_secs(5));
+        stats.set_unranked();
+        scoreboard.save_daily_result(DailyRecord::new(date.clone(), Difficulty::Easy, true, 5.0, false, Neighborhood::Moore, &stats)).unwrap();
+        assert!(scoreboard.has_played_daily(&date, Difficulty::Easy, Neighborhood::Moore));
+        assert!(scoreboard.is_daily_completed(&date, Difficulty::Easy, Neighborhood::Moore));
+        assert!(scoreboard.get_daily_results(&date, Difficulty::Easy, Neighborhood::Moore).is_empty());
+    }
+
+    #[test]
+    fn test_save_and_load(){
+        let path = std::env::temp_dir().join(format!("minesweeper-tui-scoreboard-{}.json", std::process::id()));
+        let mut scoreboard: Scoreboard = Scoreboard::load(path.clone());
+        scoreboard.save_score(score(Difficulty::Medium, 99.5, true, Neighborhood::Moore)).unwrap();
+
+        let loaded: Scoreboard = Scoreboard::load(path.clone());
+        assert_eq!(loaded.get_best_scores(Difficulty::Medium, Neighborhood::Moore, 1)[0].get_time(), 99.5);
+        fs::remove_file(path).unwrap();
     }
 }
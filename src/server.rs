@@ -0,0 +1,161 @@
+use std::{
+    fs,
+    io,
+    net::TcpListener,
+    path::{Path, PathBuf},
+    sync::{mpsc, Arc, Mutex},
+    thread,
+};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ed25519_dalek::SigningKey;
+use rand::rngs::OsRng;
+use tui::layout::Rect;
+
+use crate::{
+    Args,
+    app::App,
+    input_listener::{self, InputEvent},
+    layout::Mask,
+    scoreboard::Scoreboard,
+    ssh::{self, Session, SessionEvent},
+};
+
+pub fn get_default_host_key_path() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.join(".minesweeper-tui").join("ssh_host_key"))
+}
+
+// The key is made on the first start and kept, so players can tell they reach the same server
+pub fn load_host_key(path: &Path) -> io::Result<SigningKey> {
+    match fs::read(path) {
+        Ok(bytes) => bytes.try_into()
+            .map(|bytes: [u8; 32]| SigningKey::from_bytes(&bytes))
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "host key is not 32 bytes long")),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => {
+            let host_key = SigningKey::generate(&mut OsRng);
+            if let Some(directory) = path.parent() {
+                fs::create_dir_all(directory)?;
+            }
+            fs::write(path, host_key.to_bytes())?;
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+            }
+            Ok(host_key)
+        },
+        Err(error) => Err(error),
+    }
+}
+
+// Every connection plays its own game, and all of them save to one scoreboard
+pub fn serve(listener: TcpListener, host_key: SigningKey, args: Args, mask: Option<Mask>) {
+    let scoreboard = match args.scoreboard.clone().or_else(Scoreboard::get_default_path) {
+        Some(path) => Scoreboard::load(path),
+        None => Scoreboard::new(),
+    };
+    let scoreboard = Arc::new(Mutex::new(scoreboard));
+    let host_key = Arc::new(host_key);
+
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else {
+            continue;
+        };
+        let (host_key, args, mask, scoreboard) = (host_key.clone(), args.clone(), mask.clone(), scoreboard.clone());
+        // A client that fails the handshake only loses its own connection
+        thread::spawn(move || {
+            if let Ok(session) = ssh::accept(stream, &host_key) {
+                run_session(session, args, mask, scoreboard);
+            }
+        });
+    }
+}
+
+// The game runs on its own thread while this one passes on what the client types
+fn run_session(mut session: Session, args: Args, mask: Option<Mask>, scoreboard: Arc<Mutex<Scoreboard>>) {
+    let (tx, rx) = mpsc::channel();
+    let (width, height) = session.get_size();
+    let mut output = session.get_output();
+    let game = thread::spawn(move || {
+        let mut app: App = App::new(args);
+        app.set_mask(mask);
+        app.set_scoreboard(scoreboard);
+        let _ = app.run(&rx, &mut output, Some(Rect::new(0, 0, width, height)));
+        output.close();
+    });
+
+    // Events for a game that already ended are dropped
+    loop {
+        match session.read_event() {
+            SessionEvent::Input(bytes) => {
+                for key in parse_terminal_keys(&bytes) {
+                    let _ = tx.send(input_listener::get_key_input_event(key));
+                }
+            },
+            SessionEvent::Resize(width, height) => {
+                let _ = tx.send(InputEvent::Resize(width, height));
+            },
+            SessionEvent::Closed => break,
+        }
+    }
+    let _ = tx.send(InputEvent::Quit);
+    let _ = game.join();
+    session.finish();
+}
+
+// Keys arrive as the bytes the terminal of the client sends, with escape sequences for the arrows.
+// Other escape sequences, like the mouse reports asked for by the game, are skipped.
+fn parse_terminal_keys(bytes: &[u8]) -> Vec<KeyEvent> {
+    let text = String::from_utf8_lossy(bytes);
+    let mut chars = text.chars().peekable();
+    let mut keys = vec![];
+    while let Some(c) = chars.next() {
+        let key = match c {
+            '\x1b' if matches!(chars.peek(), Some('[' | 'O')) => {
+                chars.next();
+                // Parameters come first, a letter or symbol ends the sequence
+                let code = match chars.by_ref().find(|c| ('\x40'..='\x7e').contains(c)) {
+                    Some('A') => KeyCode::Up,
+                    Some('B') => KeyCode::Down,
+                    Some('C') => KeyCode::Right,
+                    Some('D') => KeyCode::Left,
+                    _ => continue,
+                };
+                KeyEvent::new(code, KeyModifiers::NONE)
+            },
+            '\x1b' => KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE),
+            '\r' | '\n' => KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE),
+            '\t' => KeyEvent::new(KeyCode::Tab, KeyModifiers::NONE),
+            '\x7f' => KeyEvent::new(KeyCode::Backspace, KeyModifiers::NONE),
+            '\x01'..='\x1a' => KeyEvent::new(KeyCode::Char((c as u8 - 1 + b'a') as char), KeyModifiers::CONTROL),
+            c if c.is_control() => continue,
+            c => KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE),
+        };
+        keys.push(key);
+    }
+    keys
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_terminal_keys(){
+        let keys = parse_terminal_keys(b"e\r\x1b[A\x1bOD\x1b[<0;12;5M\x03q");
+        let codes: Vec<KeyCode> = keys.iter().map(|key| key.code).collect();
+        assert_eq!(codes, [KeyCode::Char('e'), KeyCode::Enter, KeyCode::Up, KeyCode::Left, KeyCode::Char('c'), KeyCode::Char('q')]);
+        assert_eq!(keys[4].modifiers, KeyModifiers::CONTROL);
+        assert!(matches!(input_listener::get_key_input_event(keys[5]), InputEvent::Quit));
+    }
+
+    #[test]
+    fn test_host_key(){
+        let path = std::env::temp_dir().join(format!("minesweeper-tui-host-key-{}", std::process::id()));
+        let host_key = load_host_key(&path).expect("host key expect");
+        assert_eq!(load_host_key(&path).expect("host key expect").to_bytes(), host_key.to_bytes());
+
+        fs::write(&path, b"short").expect("write expect");
+        assert!(load_host_key(&path).is_err());
+        fs::remove_file(path).expect("remove expect");
+    }
+}
@@ -0,0 +1,544 @@
+use std::collections::{BTreeSet, HashSet};
+
+use crate::board::Board;
+
+// Largest frontier component that is enumerated exhaustively
+const MAX_ENUMERATION_CELLS: usize = 20;
+// Largest frontier component enumerated for the probability overlay, larger ones are approximated
+const MAX_PROBABILITY_CELLS: usize = 24;
+
+pub struct Deduction {
+    index: usize,
+    is_mine: bool,
+    explanation: String,
+}
+
+impl Deduction {
+    pub fn get_index(&self) -> usize {
+        self.index
+    }
+
+    pub fn is_mine(&self) -> bool {
+        self.is_mine
+    }
+
+    pub fn get_explanation(&self) -> &str {
+        &self.explanation
+    }
+}
+
+// The unopened cells around an open number and how many mines are still hidden among them
+struct Constraint {
+    source: usize,
+    cells: BTreeSet<usize>,
+    mines: i16,
+}
+
+// All mine arrangements of one connected part of the frontier
+struct ComponentSolutions {
+    cells: Vec<usize>,
+    // Number of arrangements using k mines, indexed by k
+    solution_counts: Vec<f64>,
+    // Per cell, number of arrangements using k mines where the cell is a mine
+    mine_counts: Vec<Vec<f64>>,
+}
+
+impl ComponentSolutions {
+    fn get_total(&self) -> f64 {
+        self.solution_counts.iter().sum()
+    }
+
+    fn get_cell_mine_total(&self, cell: usize) -> f64 {
+        self.mine_counts[cell].iter().sum()
+    }
+}
+
+/// Deduces provably safe cells and mines from what is visible on the board.
+pub struct Solver<'a> {
+    board: &'a Board,
+    known_mines: HashSet<usize>,
+    known_safe: HashSet<usize>,
+    deductions: Vec<Deduction>,
+}
+
+impl<'a> Solver<'a> {
+    pub fn new(board: &'a Board) -> Self {
+        // Opened mines are visible, so they are known
+        let known_mines = board.get_cells().iter()
+            .enumerate()
+            .filter(|(_, cell)| cell.is_open() && cell.is_bomb())
+            .map(|(i, _)| i)
+            .collect();
+
+        Self {
+            board,
+            known_mines,
+            known_safe: HashSet::new(),
+            deductions: Vec::new(),
+        }
+    }
+
+    pub fn solve(mut self) -> Vec<Deduction> {
+        self.deduce();
+        self.deductions
+    }
+
+    fn deduce(&mut self) {
+        while self.apply_single_cell_rule() || self.apply_subset_rule() || self.apply_enumeration() { }
+    }
+
+    // Chance of every unopened cell being a mine, weighted by the number of mines left on the board
+    pub fn get_mine_probabilities(mut self) -> Vec<Option<f64>> {
+        self.deduce();
+
+        let cells = self.board.get_cells();
+        let mut probabilities: Vec<Option<f64>> = cells.iter()
+            .map(|cell| if cell.is_open() || cell.is_disabled() { None } else { Some(0.0) })
+            .collect();
+        for &index in self.known_mines.iter() {
+            if !cells[index].is_open() {
+                probabilities[index] = Some(1.0);
+            }
+        }
+
+        let constraints = self.get_constraints();
+        let mut exact: Vec<ComponentSolutions> = vec![];
+        let mut approximated_mines = 0.0;
+        for component in get_components(&constraints) {
+            if component.len() <= MAX_PROBABILITY_CELLS {
+                exact.push(enumerate_component(&component, &constraints));
+                continue;
+            }
+
+            // Too many arrangements, use the average density of the numbers around each cell
+            for cell in component {
+                let densities: Vec<f64> = constraints.iter()
+                    .filter(|constraint| constraint.cells.contains(&cell))
+                    .map(|constraint| constraint.mines as f64 / constraint.cells.len() as f64)
+                    .collect();
+                let probability = densities.iter().sum::<f64>() / densities.len() as f64;
+                approximated_mines += probability;
+                probabilities[cell] = Some(probability);
+            }
+        }
+
+        let unconstrained: Vec<usize> = (0..cells.len())
+            .filter(|&i| probabilities[i] == Some(0.0) && !self.known_safe.contains(&i) &&
+                    !constraints.iter().any(|constraint| constraint.cells.contains(&i)))
+            .collect();
+        let remaining_mines = self.board.get_bomb_count() as f64 - self.known_mines.len() as f64 - approximated_mines;
+        let remaining_mines = remaining_mines.round().max(0.0) as usize;
+
+        // Weight of every total number of mines placed on the frontier
+        let rest_weight = |frontier_mines: usize| -> f64 {
+            if frontier_mines > remaining_mines || remaining_mines - frontier_mines > unconstrained.len() {
+                return 0.0;
+            }
+            (ln_binomial(unconstrained.len(), remaining_mines - frontier_mines) -
+             ln_binomial(unconstrained.len(), remaining_mines.min(unconstrained.len()))).exp()
+        };
+
+        let all_counts = convolve_components(&exact, None);
+        let total: f64 = all_counts.iter().enumerate().map(|(k, count)| count * rest_weight(k)).sum();
+        if total == 0.0 {
+            // The numbers contradict the mine count, fall back to each component on its own
+            for solutions in exact.iter() {
+                let solution_total = solutions.get_total();
+                for (i, &cell) in solutions.cells.iter().enumerate() {
+                    probabilities[cell] = Some(solutions.get_cell_mine_total(i) / solution_total.max(1.0));
+                }
+            }
+            return probabilities;
+        }
+
+        let mut expected_frontier_mines = 0.0;
+        for (c, solutions) in exact.iter().enumerate() {
+            let other_counts = convolve_components(&exact, Some(c));
+            for (i, &cell) in solutions.cells.iter().enumerate() {
+                let mut weight = 0.0;
+                for (k, count) in solutions.mine_counts[i].iter().enumerate() {
+                    for (r, other) in other_counts.iter().enumerate() {
+                        weight += count * other * rest_weight(k + r);
+                    }
+                }
+                expected_frontier_mines += weight / total;
+                probabilities[cell] = Some(weight / total);
+            }
+        }
+
+        if !unconstrained.is_empty() {
+            let expected_rest = (remaining_mines as f64 - expected_frontier_mines).max(0.0);
+            for &cell in unconstrained.iter() {
+                probabilities[cell] = Some(expected_rest / unconstrained.len() as f64);
+            }
+        }
+        probabilities
+    }
+
+    // The first deduction the player has not acted on yet
+    pub fn get_hint(self) -> Option<Deduction> {
+        let board = self.board;
+        self.solve()
+            .into_iter()
+            .find(|deduction| !(deduction.is_mine && board.get_cells()[deduction.index].is_flagged()))
+    }
+
+    fn apply_single_cell_rule(&mut self) -> bool {
+        let mut found = false;
+        for constraint in self.get_constraints() {
+            let source = self.describe(constraint.source);
+            if constraint.mines == 0 {
+                for &cell in constraint.cells.iter() {
+                    let explanation = format!("{} is safe: {} already touches all of its mines",
+                                              self.describe(cell), source);
+                    found |= self.mark(cell, false, explanation);
+                }
+            }
+            else if constraint.mines as usize == constraint.cells.len() {
+                for &cell in constraint.cells.iter() {
+                    let explanation = format!("{} is a mine: {} needs {} more mine(s) and has only {} unopened neighbour(s) left",
+                                              self.describe(cell), source, constraint.mines, constraint.cells.len());
+                    found |= self.mark(cell, true, explanation);
+                }
+            }
+        }
+        found
+    }
+
+    fn apply_subset_rule(&mut self) -> bool {
+        let constraints = self.get_constraints();
+        let mut found = false;
+        for small in constraints.iter() {
+            for large in constraints.iter() {
+                if small.cells.len() >= large.cells.len() || !small.cells.is_subset(&large.cells) {
+                    continue;
+                }
+
+                let difference: Vec<usize> = large.cells.difference(&small.cells).copied().collect();
+                let mines = large.mines - small.mines;
+                let is_mine = if mines == 0 {
+                    false
+                }
+                else if mines as usize == difference.len() {
+                    true
+                }
+                else {
+                    continue;
+                };
+
+                for cell in difference {
+                    let explanation = format!("{} is {}: the {} mine(s) left around {} are all shared with {}, which needs {}",
+                                              self.describe(cell),
+                                              if is_mine { "a mine" } else { "safe" },
+                                              small.mines,
+                                              self.describe(small.source),
+                                              self.describe(large.source),
+                                              large.mines);
+                    found |= self.mark(cell, is_mine, explanation);
+                }
+            }
+        }
+        found
+    }
+
+    fn apply_enumeration(&mut self) -> bool {
+        let constraints = self.get_constraints();
+        let mut found = false;
+        for component in get_components(&constraints) {
+            if component.len() > MAX_ENUMERATION_CELLS {
+                continue;
+            }
+
+            let solutions = enumerate_component(&component, &constraints);
+            let total = solutions.get_total();
+            if total == 0.0 {
+                continue;
+            }
+
+            for (i, &cell) in solutions.cells.iter().enumerate() {
+                let mine_total = solutions.get_cell_mine_total(i);
+                if mine_total != 0.0 && mine_total != total {
+                    continue;
+                }
+
+                let is_mine = mine_total == total;
+                let explanation = format!("{} is {} in all {} mine arrangements that fit the numbers around this {}-cell frontier",
+                                          self.describe(cell),
+                                          if is_mine { "a mine" } else { "safe" },
+                                          total,
+                                          component.len());
+                found |= self.mark(cell, is_mine, explanation);
+            }
+        }
+        found
+    }
+
+    fn mark(&mut self, index: usize, is_mine: bool, explanation: String) -> bool {
+        let is_new = if is_mine {
+            self.known_mines.insert(index)
+        }
+        else {
+            self.known_safe.insert(index)
+        };
+
+        if is_new {
+            self.deductions.push(Deduction { index, is_mine, explanation });
+        }
+        is_new
+    }
+
+    fn get_constraints(&self) -> Vec<Constraint> {
+        let mut constraints = vec![];
+        for (i, cell) in self.board.get_cells().iter().enumerate() {
+            if !cell.is_open() || cell.is_bomb() {
+                continue;
+            }
+
+            let mut cells = BTreeSet::new();
+            let mut mines = cell.get_value();
+            for neighbor in self.board.get_cell_neighbors_indices(i as i16) {
+                if self.known_mines.contains(&neighbor) {
+                    mines -= 1;
+                }
+                else if !self.board.get_cells()[neighbor].is_open() && !self.known_safe.contains(&neighbor) {
+                    cells.insert(neighbor);
+                }
+            }
+
+            if !cells.is_empty() {
+                constraints.push(Constraint { source: i, cells, mines });
+            }
+        }
+        constraints
+    }
+
+    fn describe(&self, index: usize) -> String {
+        let (x, y) = self.board.get_pos_from_index(index as i16);
+        if self.board.get_depth() > 1 {
+            return format!("({}, {}, layer {})", x + 1, y + 1, index / self.board.get_layer_size() + 1);
+        }
+        format!("({}, {})", x + 1, y + 1)
+    }
+}
+
+// Group the frontier cells into sets that share at least one constraint
+fn get_components(constraints: &[Constraint]) -> Vec<Vec<usize>> {
+    let mut components: Vec<BTreeSet<usize>> = vec![];
+    for constraint in constraints.iter() {
+        let mut merged: BTreeSet<usize> = constraint.cells.clone();
+        components.retain(|component| {
+            if component.is_disjoint(&merged) {
+                return true;
+            }
+            merged.extend(component.iter().copied());
+            false
+        });
+        components.push(merged);
+    }
+    components.into_iter().map(|component| component.into_iter().collect()).collect()
+}
+
+// Number of arrangements for every total mine count over all components, optionally leaving one out
+fn convolve_components(components: &[ComponentSolutions], skip: Option<usize>) -> Vec<f64> {
+    let mut counts = vec![1.0];
+    for (c, solutions) in components.iter().enumerate() {
+        if Some(c) == skip {
+            continue;
+        }
+
+        let mut combined = vec![0.0; counts.len() + solutions.solution_counts.len() - 1];
+        for (i, count) in counts.iter().enumerate() {
+            for (j, solution_count) in solutions.solution_counts.iter().enumerate() {
+                combined[i + j] += count * solution_count;
+            }
+        }
+        counts = combined;
+    }
+    counts
+}
+
+fn ln_binomial(n: usize, k: usize) -> f64 {
+    (0..k).map(|i| ((n - i) as f64).ln() - ((i + 1) as f64).ln()).sum()
+}
+
+fn enumerate_component(cells: &[usize], constraints: &[Constraint]) -> ComponentSolutions {
+    let relevant: Vec<&Constraint> = constraints.iter()
+        .filter(|constraint| constraint.cells.iter().any(|cell| cells.contains(cell)))
+        .collect();
+
+    // For every cell, the constraints it takes part in
+    let cell_constraints: Vec<Vec<usize>> = cells.iter()
+        .map(|cell| relevant.iter()
+             .enumerate()
+             .filter(|(_, constraint)| constraint.cells.contains(cell))
+             .map(|(i, _)| i)
+             .collect())
+        .collect();
+
+    let mut search = Search {
+        targets: relevant.iter().map(|constraint| constraint.mines).collect(),
+        assigned_mines: vec![0; relevant.len()],
+        unassigned: relevant.iter().map(|constraint| constraint.cells.len() as i16).collect(),
+        cell_constraints,
+        assignment: vec![false; cells.len()],
+        solutions: ComponentSolutions {
+            cells: cells.to_vec(),
+            solution_counts: vec![0.0; cells.len() + 1],
+            mine_counts: vec![vec![0.0; cells.len() + 1]; cells.len()],
+        },
+    };
+    search.run(0, 0);
+    search.solutions
+}
+
+struct Search {
+    targets: Vec<i16>,
+    assigned_mines: Vec<i16>,
+    unassigned: Vec<i16>,
+    cell_constraints: Vec<Vec<usize>>,
+    assignment: Vec<bool>,
+    solutions: ComponentSolutions,
+}
+
+impl Search {
+    fn run(&mut self, depth: usize, mine_count: usize) {
+        if depth == self.assignment.len() {
+            self.solutions.solution_counts[mine_count] += 1.0;
+            for (i, &is_mine) in self.assignment.iter().enumerate() {
+                if is_mine {
+                    self.solutions.mine_counts[i][mine_count] += 1.0;
+                }
+            }
+            return;
+        }
+
+        for is_mine in [false, true] {
+            if self.assign(depth, is_mine) {
+                self.run(depth + 1, mine_count + is_mine as usize);
+            }
+            self.unassign(depth, is_mine);
+        }
+    }
+
+    // Returns whether the assignment still fits every constraint touching the cell
+    fn assign(&mut self, depth: usize, is_mine: bool) -> bool {
+        self.assignment[depth] = is_mine;
+        let mut is_valid = true;
+        for &c in self.cell_constraints[depth].iter() {
+            self.unassigned[c] -= 1;
+            self.assigned_mines[c] += is_mine as i16;
+            if self.assigned_mines[c] > self.targets[c] ||
+                self.assigned_mines[c] + self.unassigned[c] < self.targets[c] {
+                    is_valid = false;
+                }
+        }
+        is_valid
+    }
+
+    fn unassign(&mut self, depth: usize, is_mine: bool) {
+        self.assignment[depth] = false;
+        for &c in self.cell_constraints[depth].iter() {
+            self.unassigned[c] += 1;
+            self.assigned_mines[c] -= is_mine as i16;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::Difficulty;
+
+    #[test]
+    fn test_single_cell_mine(){
+        let mut board: Board = Board::new();
+        board.initiate_board_from_layout(3, 1, &[0]);
+        board.open_cell(2);
+
+        let hint = Solver::new(&board).get_hint().unwrap();
+        assert_eq!(hint.get_index(), 0);
+        assert!(hint.is_mine());
+    }
+
+    #[test]
+    fn test_single_cell_safe(){
+        let mut board: Board = Board::new();
+        board.initiate_board_from_layout(3, 1, &[0]);
+        board.open_cell(1);
+        assert!(Solver::new(&board).get_hint().is_none());
+
+        // An exploded mine is known, so the last neighbour of the 1 is safe
+        board.open_cell(0);
+        let hint = Solver::new(&board).get_hint().unwrap();
+        assert_eq!(hint.get_index(), 2);
+        assert!(!hint.is_mine());
+    }
+
+    #[test]
+    fn test_subset_rule(){
+        let mut board: Board = Board::new();
+        board.initiate_board_from_layout(3, 2, &[0, 2]);
+        board.open_cell(3);
+        board.open_cell(4);
+        board.open_cell(5);
+
+        let deductions = Solver::new(&board).solve();
+        assert_eq!(deductions[0].get_index(), 2);
+        assert!(deductions[0].is_mine());
+        assert!(deductions.iter().any(|deduction| deduction.get_index() == 1 && !deduction.is_mine()));
+    }
+
+    #[test]
+    fn test_hint_skips_flagged_mines(){
+        let mut board: Board = Board::new();
+        board.initiate_board_from_layout(3, 1, &[0]);
+        board.open_cell(2);
+        board.toggle_cell_flag(board.get_selected_cell_index());
+        assert!(Solver::new(&board).get_hint().is_none());
+    }
+
+    #[test]
+    fn test_mine_probabilities(){
+        let mut board: Board = Board::new();
+        board.initiate_board_from_layout(3, 1, &[0]);
+        board.open_cell(1);
+
+        // One mine left between the two neighbours of the 1
+        let probabilities = Solver::new(&board).get_mine_probabilities();
+        assert_eq!(probabilities[1], None);
+        assert!((probabilities[0].unwrap() - 0.5).abs() < 1e-9);
+        assert!((probabilities[2].unwrap() - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_mine_probabilities_use_mine_count(){
+        // Two mines, one of them next to the 1 and one somewhere in the unconstrained column
+        let mut board: Board = Board::new();
+        board.initiate_board_from_layout(4, 1, &[0, 3]);
+        board.open_cell(1);
+
+        let probabilities = Solver::new(&board).get_mine_probabilities();
+        assert!((probabilities[0].unwrap() - 0.5).abs() < 1e-9);
+        assert!((probabilities[2].unwrap() - 0.5).abs() < 1e-9);
+        assert!((probabilities[3].unwrap() - 1.0).abs() < 1e-9);
+
+        let total: f64 = probabilities.iter().flatten().sum();
+        assert!((total - 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_deductions_are_correct(){
+        for _ in 0..20 {
+            let mut board: Board = Board::new();
+            board.initiate_board(Difficulty::Medium);
+            let start = board.get_cells().iter().position(|cell| !cell.is_bomb() && cell.get_value() == 0);
+            if let Some(start) = start {
+                board.open_cell(start);
+            }
+
+            for deduction in Solver::new(&board).solve() {
+                assert_eq!(board.get_cells()[deduction.get_index()].is_bomb(), deduction.is_mine());
+            }
+        }
+    }
+}
@@ -0,0 +1,1029 @@
+use std::{
+    io::{self, BufRead, BufReader, Read, Write},
+    net::TcpStream,
+    sync::{Arc, Condvar, Mutex},
+    time::Duration,
+};
+use chacha20::{
+    cipher::{KeyIvInit, StreamCipher, StreamCipherSeek},
+    ChaCha20Legacy,
+};
+use ed25519_dalek::{Signer, SigningKey};
+use poly1305::{universal_hash::KeyInit, Poly1305};
+use rand::{rngs::OsRng, RngCore};
+use sha2::{Digest, Sha256};
+use x25519_dalek::{EphemeralSecret, PublicKey};
+
+// Just enough of SSH 2 (RFC 4253, 4252 and 4254) to serve a game to a terminal:
+//   key exchange   curve25519-sha256 with an ssh-ed25519 host key, done once per connection
+//   cipher         chacha20-poly1305@openssh.com in both directions, no compression
+//   login          every user name and method is accepted, there are no accounts to protect
+//   channels       one session with a pty, its size changes and a shell that runs the game
+// Clients that need anything else are turned away during the handshake.
+
+const SERVER_VERSION: &str = "SSH-2.0-minesweeper_tui";
+const KEX_ALGORITHMS: [&str; 2] = ["curve25519-sha256", "curve25519-sha256@libssh.org"];
+// Listed by both sides, the strict key exchange makes them count packets from zero after the exchange
+const SERVER_STRICT_KEX: &str = "kex-strict-s-v00@openssh.com";
+const CLIENT_STRICT_KEX: &str = "kex-strict-c-v00@openssh.com";
+const HOST_KEY_ALGORITHM: &str = "ssh-ed25519";
+const CIPHER: &str = "chacha20-poly1305@openssh.com";
+// The cipher authenticates the packets itself, a MAC is only listed because the handshake needs one
+const MAC: &str = "hmac-sha2-256";
+const COMPRESSION: &str = "none";
+
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+// A client that stops reading is dropped instead of holding up its game
+const WRITE_TIMEOUT: Duration = Duration::from_secs(10);
+// Time the client gets to answer the close of its session
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(2);
+// RFC 4253 asks for packets of at least 35000 bytes to be accepted
+const MAX_PACKET_LENGTH: usize = 35000;
+// Input the client may send before it has to wait for more window, topped up once half of it is used
+const LOCAL_WINDOW: u32 = 1 << 20;
+const MAX_DATA_LENGTH: u32 = 32768;
+// Terminal size of a client that did not ask for a pty
+const DEFAULT_SIZE: (u16, u16) = (80, 24);
+
+const MSG_DISCONNECT: u8 = 1;
+const MSG_IGNORE: u8 = 2;
+const MSG_UNIMPLEMENTED: u8 = 3;
+const MSG_DEBUG: u8 = 4;
+const MSG_SERVICE_REQUEST: u8 = 5;
+const MSG_SERVICE_ACCEPT: u8 = 6;
+const MSG_KEXINIT: u8 = 20;
+const MSG_NEWKEYS: u8 = 21;
+const MSG_KEX_ECDH_INIT: u8 = 30;
+const MSG_KEX_ECDH_REPLY: u8 = 31;
+const MSG_USERAUTH_REQUEST: u8 = 50;
+const MSG_USERAUTH_SUCCESS: u8 = 52;
+const MSG_GLOBAL_REQUEST: u8 = 80;
+const MSG_REQUEST_FAILURE: u8 = 82;
+const MSG_CHANNEL_OPEN: u8 = 90;
+const MSG_CHANNEL_OPEN_CONFIRMATION: u8 = 91;
+const MSG_CHANNEL_OPEN_FAILURE: u8 = 92;
+const MSG_CHANNEL_WINDOW_ADJUST: u8 = 93;
+const MSG_CHANNEL_DATA: u8 = 94;
+const MSG_CHANNEL_EOF: u8 = 96;
+const MSG_CHANNEL_CLOSE: u8 = 97;
+const MSG_CHANNEL_REQUEST: u8 = 98;
+const MSG_CHANNEL_SUCCESS: u8 = 99;
+const MSG_CHANNEL_FAILURE: u8 = 100;
+
+// Reason given to a client that opens anything but a single session
+const OPEN_ADMINISTRATIVELY_PROHIBITED: u32 = 1;
+const DISCONNECT_PROTOCOL_ERROR: u32 = 2;
+
+pub enum SessionEvent {
+    // Bytes typed in the terminal of the client
+    Input(Vec<u8>),
+    // The terminal of the client was resized to the given columns and rows
+    Resize(u16, u16),
+    // The client closed its input or the connection
+    Closed,
+}
+
+// A connection that went through the handshake and started a shell
+pub struct Session {
+    reader: PacketReader,
+    output: Arc<Output>,
+    size: (u16, u16),
+    // Input the client may still send before the window is topped up
+    local_window: u32,
+    // The client closed the channel or the connection is gone, nothing more will be read
+    is_finished: bool,
+}
+
+// Sending side of a session, shared by the game that draws on it and the session that answers the client
+struct Output {
+    state: Mutex<OutputState>,
+    window_changed: Condvar,
+}
+
+struct OutputState {
+    writer: PacketWriter,
+    remote_channel: u32,
+    // Output the client is ready to take
+    window: u32,
+    max_data_length: u32,
+    // The close was sent or the connection failed
+    is_closed: bool,
+    // The client closed the channel and takes no more data, but still wants the close answered
+    is_client_closed: bool,
+}
+
+// Output of a game, sent to the client as channel data when it is flushed
+pub struct ChannelOutput {
+    output: Arc<Output>,
+    buffer: Vec<u8>,
+}
+
+pub fn accept(stream: TcpStream, host_key: &SigningKey) -> io::Result<Session> {
+    stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+    stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+    let mut writer = PacketWriter::new(stream.try_clone()?);
+    let mut reader = PacketReader::new(stream);
+
+    writer.stream.write_all(format!("{}\r\n", SERVER_VERSION).as_bytes())?;
+    let client_version = reader.read_version()?;
+    exchange_keys(&mut writer, &mut reader, &client_version, host_key)?;
+    let (state, size) = open_session(writer, &mut reader)?;
+    reader.reader.get_ref().set_read_timeout(None)?;
+
+    Ok(Session {
+        reader,
+        output: Arc::new(Output { state: Mutex::new(state), window_changed: Condvar::new() }),
+        size,
+        local_window: LOCAL_WINDOW,
+        is_finished: false,
+    })
+}
+
+fn exchange_keys(writer: &mut PacketWriter, reader: &mut PacketReader, client_version: &[u8], host_key: &SigningKey) -> io::Result<()> {
+    let server_kexinit = get_kexinit(SERVER_STRICT_KEX);
+    writer.send(&server_kexinit)?;
+    let client_kexinit = reader.read_message()?;
+    if client_kexinit.first() != Some(&MSG_KEXINIT) {
+        return Err(invalid("expected the key exchange to start"));
+    }
+    let is_strict = check_algorithms(&client_kexinit)?;
+
+    let init = reader.read_message()?;
+    let mut fields = Fields::new(&init);
+    if fields.get_u8()? != MSG_KEX_ECDH_INIT {
+        return Err(invalid("expected the client key"));
+    }
+    let client_public: [u8; 32] = fields.get_string()?.try_into().map_err(|_| invalid("client key is not a curve25519 key"))?;
+    let secret = EphemeralSecret::random_from_rng(OsRng);
+    let server_public = PublicKey::from(&secret);
+    let shared = secret.diffie_hellman(&PublicKey::from(client_public));
+    if !shared.was_contributory() {
+        return Err(invalid("client key gives no shared secret"));
+    }
+    let mut shared_secret = vec![];
+    put_mpint(&mut shared_secret, shared.as_bytes());
+
+    let host_key_blob = get_host_key_blob(host_key);
+    let mut exchange = vec![];
+    put_string(&mut exchange, client_version);
+    put_string(&mut exchange, SERVER_VERSION.as_bytes());
+    put_string(&mut exchange, &client_kexinit);
+    put_string(&mut exchange, &server_kexinit);
+    put_string(&mut exchange, &host_key_blob);
+    put_string(&mut exchange, &client_public);
+    put_string(&mut exchange, server_public.as_bytes());
+    exchange.extend_from_slice(&shared_secret);
+    // Keys are only exchanged once, so the hash is the session id as well
+    let hash = Sha256::digest(&exchange);
+
+    let mut signature = vec![];
+    put_string(&mut signature, HOST_KEY_ALGORITHM.as_bytes());
+    put_string(&mut signature, &host_key.sign(&hash).to_bytes());
+    let mut reply = vec![MSG_KEX_ECDH_REPLY];
+    put_string(&mut reply, &host_key_blob);
+    put_string(&mut reply, server_public.as_bytes());
+    put_string(&mut reply, &signature);
+    writer.send(&reply)?;
+
+    writer.send(&[MSG_NEWKEYS])?;
+    writer.cipher = Some(Cipher::new(&derive_key(&shared_secret, &hash, b'D')));
+    if is_strict {
+        writer.seq = 0;
+    }
+    if reader.read_message()? != [MSG_NEWKEYS] {
+        return Err(invalid("expected the client to take the new keys"));
+    }
+    reader.cipher = Some(Cipher::new(&derive_key(&shared_secret, &hash, b'C')));
+    if is_strict {
+        reader.seq = 0;
+    }
+    Ok(())
+}
+
+fn get_kexinit(strict_kex: &str) -> Vec<u8> {
+    let mut kexinit = vec![MSG_KEXINIT];
+    let mut cookie = [0; 16];
+    OsRng.fill_bytes(&mut cookie);
+    kexinit.extend_from_slice(&cookie);
+    let kex_algorithms = format!("{},{}", KEX_ALGORITHMS.join(","), strict_kex);
+    for list in [kex_algorithms.as_str(), HOST_KEY_ALGORITHM, CIPHER, CIPHER, MAC, MAC, COMPRESSION, COMPRESSION, "", ""] {
+        put_string(&mut kexinit, list.as_bytes());
+    }
+    // No guessed key exchange packet follows, and a reserved field
+    kexinit.push(0);
+    put_u32(&mut kexinit, 0);
+    kexinit
+}
+
+// Whether the client supports what the server offers and asked for the strict key exchange
+fn check_algorithms(kexinit: &[u8]) -> io::Result<bool> {
+    let mut fields = Fields::new(kexinit);
+    fields.skip(17)?;
+    let kex_algorithms = fields.get_name_list()?;
+    let host_key_algorithms = fields.get_name_list()?;
+    let ciphers = [fields.get_name_list()?, fields.get_name_list()?];
+    let _macs = [fields.get_name_list()?, fields.get_name_list()?];
+    let compressions = [fields.get_name_list()?, fields.get_name_list()?];
+
+    let is_supported = kex_algorithms.iter().any(|algorithm| KEX_ALGORITHMS.contains(algorithm)) &&
+        host_key_algorithms.contains(&HOST_KEY_ALGORITHM) &&
+        ciphers.iter().all(|list| list.contains(&CIPHER)) &&
+        compressions.iter().all(|list| list.contains(&COMPRESSION));
+    if !is_supported {
+        return Err(invalid("client does not support curve25519-sha256, ssh-ed25519 and chacha20-poly1305@openssh.com"));
+    }
+    Ok(kex_algorithms.contains(&CLIENT_STRICT_KEX))
+}
+
+fn get_host_key_blob(host_key: &SigningKey) -> Vec<u8> {
+    let mut blob = vec![];
+    put_string(&mut blob, HOST_KEY_ALGORITHM.as_bytes());
+    put_string(&mut blob, host_key.verifying_key().as_bytes());
+    blob
+}
+
+// Keys are hashed from the shared secret, the exchange hash, a letter for their use and the session id
+fn derive_key(shared_secret: &[u8], hash: &[u8], letter: u8) -> Vec<u8> {
+    let mut key = Sha256::new().chain_update(shared_secret).chain_update(hash).chain_update([letter]).chain_update(hash).finalize().to_vec();
+    while key.len() < 64 {
+        let more = Sha256::new().chain_update(shared_secret).chain_update(hash).chain_update(&key).finalize();
+        key.extend_from_slice(&more);
+    }
+    key.truncate(64);
+    key
+}
+
+// Log in and wait for the client to start a shell on its session
+fn open_session(mut writer: PacketWriter, reader: &mut PacketReader) -> io::Result<(OutputState, (u16, u16))> {
+    let mut is_authenticated = false;
+    let mut channel: Option<(u32, u32, u32)> = None;
+    let mut size = DEFAULT_SIZE;
+    loop {
+        let message = reader.read_message()?;
+        let mut fields = Fields::new(&message);
+        match fields.get_u8()? {
+            MSG_SERVICE_REQUEST => {
+                let service = fields.get_string()?;
+                if service != b"ssh-userauth" {
+                    writer.disconnect("only ssh-userauth is served");
+                    return Err(invalid("client asked for an unknown service"));
+                }
+                let mut accept = vec![MSG_SERVICE_ACCEPT];
+                put_string(&mut accept, service);
+                writer.send(&accept)?;
+            },
+            MSG_USERAUTH_REQUEST => {
+                is_authenticated = true;
+                writer.send(&[MSG_USERAUTH_SUCCESS])?;
+            },
+            MSG_GLOBAL_REQUEST => {
+                fields.get_string()?;
+                if fields.get_bool()? {
+                    writer.send(&[MSG_REQUEST_FAILURE])?;
+                }
+            },
+            MSG_CHANNEL_OPEN if is_authenticated => {
+                let kind = fields.get_string()?;
+                let remote_channel = fields.get_u32()?;
+                let window = fields.get_u32()?;
+                let max_data_length = fields.get_u32()?.clamp(1, MAX_DATA_LENGTH);
+                if kind != b"session" || channel.is_some() {
+                    let mut failure = vec![MSG_CHANNEL_OPEN_FAILURE];
+                    put_u32(&mut failure, remote_channel);
+                    put_u32(&mut failure, OPEN_ADMINISTRATIVELY_PROHIBITED);
+                    put_string(&mut failure, b"only one session is served");
+                    put_string(&mut failure, b"");
+                    writer.send(&failure)?;
+                    continue;
+                }
+                channel = Some((remote_channel, window, max_data_length));
+                let mut confirmation = vec![MSG_CHANNEL_OPEN_CONFIRMATION];
+                put_u32(&mut confirmation, remote_channel);
+                put_u32(&mut confirmation, 0);
+                put_u32(&mut confirmation, LOCAL_WINDOW);
+                put_u32(&mut confirmation, MAX_DATA_LENGTH);
+                writer.send(&confirmation)?;
+            },
+            MSG_CHANNEL_WINDOW_ADJUST => {
+                fields.get_u32()?;
+                let bytes = fields.get_u32()?;
+                if let Some((_, window, _)) = channel.as_mut() {
+                    *window = window.saturating_add(bytes);
+                }
+            },
+            MSG_CHANNEL_REQUEST => {
+                let Some((remote_channel, window, max_data_length)) = channel else {
+                    return Err(invalid("client made a request without a session"));
+                };
+                fields.get_u32()?;
+                let kind = fields.get_string()?;
+                let want_reply = fields.get_bool()?;
+                let is_accepted = match kind {
+                    b"pty-req" => {
+                        fields.get_string()?;
+                        size = get_size(fields.get_u32()?, fields.get_u32()?);
+                        true
+                    },
+                    b"env" | b"shell" => true,
+                    _ => false,
+                };
+                if want_reply {
+                    let mut reply = vec![if is_accepted { MSG_CHANNEL_SUCCESS } else { MSG_CHANNEL_FAILURE }];
+                    put_u32(&mut reply, remote_channel);
+                    writer.send(&reply)?;
+                }
+                if kind == b"shell" {
+                    let state = OutputState {
+                        writer,
+                        remote_channel,
+                        window,
+                        max_data_length,
+                        is_closed: false,
+                        is_client_closed: false,
+                    };
+                    return Ok((state, size));
+                }
+            },
+            MSG_DISCONNECT => return Err(io::Error::new(io::ErrorKind::ConnectionAborted, "client disconnected")),
+            _ => writer.send_unimplemented(reader.seq.wrapping_sub(1))?,
+        }
+    }
+}
+
+// A pty of no size leaves it to the server
+fn get_size(columns: u32, rows: u32) -> (u16, u16) {
+    if columns == 0 || rows == 0 {
+        return DEFAULT_SIZE;
+    }
+    (columns.min(u16::MAX as u32) as u16, rows.min(u16::MAX as u32) as u16)
+}
+
+impl Session {
+    pub fn get_size(&self) -> (u16, u16) {
+        self.size
+    }
+
+    pub fn get_output(&self) -> ChannelOutput {
+        ChannelOutput { output: self.output.clone(), buffer: vec![] }
+    }
+
+    pub fn read_event(&mut self) -> SessionEvent {
+        while !self.is_finished {
+            match self.handle_message() {
+                Ok(Some(event)) => return event,
+                Ok(None) => { },
+                Err(_) => {
+                    self.is_finished = true;
+                    self.output.set_closed(false);
+                },
+            }
+        }
+        SessionEvent::Closed
+    }
+
+    // Wait for the client to answer the close of the session, so the last output is not cut off
+    pub fn finish(&mut self) {
+        while !self.is_finished {
+            self.read_event();
+        }
+    }
+
+    fn handle_message(&mut self) -> io::Result<Option<SessionEvent>> {
+        let message = self.reader.read_message()?;
+        let mut fields = Fields::new(&message);
+        match fields.get_u8()? {
+            MSG_CHANNEL_DATA => {
+                fields.get_u32()?;
+                let data = fields.get_string()?.to_vec();
+                self.local_window = self.local_window.saturating_sub(data.len() as u32);
+                if self.local_window < LOCAL_WINDOW / 2 {
+                    self.output.send_window_adjust(LOCAL_WINDOW - self.local_window)?;
+                    self.local_window = LOCAL_WINDOW;
+                }
+                Ok(Some(SessionEvent::Input(data)))
+            },
+            MSG_CHANNEL_WINDOW_ADJUST => {
+                fields.get_u32()?;
+                self.output.add_window(fields.get_u32()?);
+                Ok(None)
+            },
+            MSG_CHANNEL_REQUEST => {
+                fields.get_u32()?;
+                let kind = fields.get_string()?;
+                let want_reply = fields.get_bool()?;
+                let event = if kind == b"window-change" {
+                    let (columns, rows) = get_size(fields.get_u32()?, fields.get_u32()?);
+                    Some(SessionEvent::Resize(columns, rows))
+                }
+                else {
+                    None
+                };
+                if want_reply {
+                    self.output.send_request_reply(event.is_some())?;
+                }
+                Ok(event)
+            },
+            MSG_GLOBAL_REQUEST => {
+                fields.get_string()?;
+                if fields.get_bool()? {
+                    self.output.send(&[MSG_REQUEST_FAILURE])?;
+                }
+                Ok(None)
+            },
+            // Without input the game can not go on, but its last output is still sent
+            MSG_CHANNEL_EOF => Ok(Some(SessionEvent::Closed)),
+            MSG_CHANNEL_CLOSE => {
+                self.is_finished = true;
+                self.output.set_closed(true);
+                Ok(Some(SessionEvent::Closed))
+            },
+            MSG_DISCONNECT => Err(io::Error::new(io::ErrorKind::ConnectionAborted, "client disconnected")),
+            // Keys are only exchanged once, a client that asks for new ones is disconnected
+            MSG_KEXINIT => {
+                self.output.disconnect("key re-exchange is not supported");
+                Err(invalid("client asked for a key re-exchange"))
+            },
+            _ => {
+                self.output.send_unimplemented(self.reader.seq.wrapping_sub(1))?;
+                Ok(None)
+            },
+        }
+    }
+}
+
+impl Output {
+    fn lock(&self) -> std::sync::MutexGuard<'_, OutputState> {
+        self.state.lock().expect("output lock expect")
+    }
+
+    fn send(&self, message: &[u8]) -> io::Result<()> {
+        self.lock().writer.send(message)
+    }
+
+    fn send_unimplemented(&self, seq: u32) -> io::Result<()> {
+        self.lock().writer.send_unimplemented(seq)
+    }
+
+    fn disconnect(&self, reason: &str) {
+        self.lock().writer.disconnect(reason);
+    }
+
+    fn send_window_adjust(&self, bytes: u32) -> io::Result<()> {
+        let mut state = self.lock();
+        let mut adjust = vec![MSG_CHANNEL_WINDOW_ADJUST];
+        put_u32(&mut adjust, state.remote_channel);
+        put_u32(&mut adjust, bytes);
+        state.writer.send(&adjust)
+    }
+
+    fn send_request_reply(&self, is_accepted: bool) -> io::Result<()> {
+        let mut state = self.lock();
+        let mut reply = vec![if is_accepted { MSG_CHANNEL_SUCCESS } else { MSG_CHANNEL_FAILURE }];
+        put_u32(&mut reply, state.remote_channel);
+        state.writer.send(&reply)
+    }
+
+    fn add_window(&self, bytes: u32) {
+        let mut state = self.lock();
+        state.window = state.window.saturating_add(bytes);
+        self.window_changed.notify_all();
+    }
+
+    // A client that closed the channel still gets the close answered, a lost connection gets nothing
+    fn set_closed(&self, is_client_closed: bool) {
+        let mut state = self.lock();
+        if is_client_closed {
+            state.is_client_closed = true;
+        }
+        else {
+            state.is_closed = true;
+        }
+        self.window_changed.notify_all();
+    }
+
+    // Data that can not be sent is dropped, the session tells the game to quit once it notices
+    fn send_data(&self, mut data: &[u8]) {
+        let mut state = self.lock();
+        while !data.is_empty() {
+            while state.window == 0 && state.is_open() {
+                state = self.window_changed.wait(state).expect("output lock expect");
+            }
+            if !state.is_open() {
+                return;
+            }
+
+            let length = data.len().min(state.window as usize).min(state.max_data_length as usize);
+            let mut message = vec![MSG_CHANNEL_DATA];
+            put_u32(&mut message, state.remote_channel);
+            put_string(&mut message, &data[..length]);
+            if state.writer.send(&message).is_err() {
+                state.is_closed = true;
+                return;
+            }
+            state.window -= length as u32;
+            data = &data[length..];
+        }
+    }
+
+    fn close(&self) {
+        let mut state = self.lock();
+        if state.is_closed {
+            return;
+        }
+        let remote_channel = state.remote_channel;
+        if !state.is_client_closed {
+            let mut exit_status = vec![MSG_CHANNEL_REQUEST];
+            put_u32(&mut exit_status, remote_channel);
+            put_string(&mut exit_status, b"exit-status");
+            exit_status.push(0);
+            put_u32(&mut exit_status, 0);
+            let mut eof = vec![MSG_CHANNEL_EOF];
+            put_u32(&mut eof, remote_channel);
+            let _ = state.writer.send(&exit_status).and_then(|_| state.writer.send(&eof));
+        }
+        let mut close = vec![MSG_CHANNEL_CLOSE];
+        put_u32(&mut close, remote_channel);
+        let _ = state.writer.send(&close);
+        let _ = state.writer.stream.set_read_timeout(Some(CLOSE_TIMEOUT));
+        state.is_closed = true;
+        self.window_changed.notify_all();
+    }
+}
+
+impl OutputState {
+    fn is_open(&self) -> bool {
+        !self.is_closed && !self.is_client_closed
+    }
+}
+
+impl ChannelOutput {
+    // Ends the session with a zero exit status once the game is over
+    pub fn close(&mut self) {
+        let _ = self.flush();
+        self.output.close();
+    }
+}
+
+impl Write for ChannelOutput {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.output.send_data(&self.buffer);
+        self.buffer.clear();
+        Ok(())
+    }
+}
+
+// chacha20-poly1305@openssh.com: the second half of the key encrypts the packet length so packets can be
+// framed, the first half the rest of the packet and the poly1305 key, and the tag covers both
+struct Cipher {
+    main_key: [u8; 32],
+    length_key: [u8; 32],
+}
+
+impl Cipher {
+    fn new(key: &[u8]) -> Self {
+        Self {
+            main_key: key[..32].try_into().expect("key expect"),
+            length_key: key[32..64].try_into().expect("key expect"),
+        }
+    }
+
+    fn seal(&self, seq: u32, packet: &mut Vec<u8>) {
+        let nonce = (seq as u64).to_be_bytes();
+        ChaCha20Legacy::new(&self.length_key.into(), &nonce.into()).apply_keystream(&mut packet[..4]);
+        let (mut main, poly_key) = self.get_main_cipher(seq);
+        main.apply_keystream(&mut packet[4..]);
+        let tag = Poly1305::new(&poly_key.into()).compute_unpadded(packet);
+        packet.extend_from_slice(&tag);
+    }
+
+    fn open_length(&self, seq: u32, mut length: [u8; 4]) -> usize {
+        let nonce = (seq as u64).to_be_bytes();
+        ChaCha20Legacy::new(&self.length_key.into(), &nonce.into()).apply_keystream(&mut length);
+        u32::from_be_bytes(length) as usize
+    }
+
+    // The packet still starts with its encrypted length, the tag is checked before anything is decrypted
+    fn open(&self, seq: u32, packet: &mut [u8], tag: &[u8]) -> io::Result<()> {
+        let (mut main, poly_key) = self.get_main_cipher(seq);
+        let expected = Poly1305::new(&poly_key.into()).compute_unpadded(packet);
+        if expected.iter().zip(tag).fold(0, |difference, (a, b)| difference | (a ^ b)) != 0 {
+            return Err(invalid("packet failed authentication"));
+        }
+        main.apply_keystream(&mut packet[4..]);
+        Ok(())
+    }
+
+    // The first block of the main cipher gives the poly1305 key, the packet is encrypted from the second one on
+    fn get_main_cipher(&self, seq: u32) -> (ChaCha20Legacy, [u8; 32]) {
+        let nonce = (seq as u64).to_be_bytes();
+        let mut main = ChaCha20Legacy::new(&self.main_key.into(), &nonce.into());
+        let mut poly_key = [0; 32];
+        main.apply_keystream(&mut poly_key);
+        main.seek(64);
+        (main, poly_key)
+    }
+}
+
+struct PacketWriter {
+    stream: TcpStream,
+    cipher: Option<Cipher>,
+    seq: u32,
+}
+
+impl PacketWriter {
+    fn new(stream: TcpStream) -> Self {
+        Self { stream, cipher: None, seq: 0 }
+    }
+
+    fn send(&mut self, payload: &[u8]) -> io::Result<()> {
+        // The encrypted length is not counted when the packet is padded to whole blocks
+        let unpadded = if self.cipher.is_some() { 1 + payload.len() } else { 5 + payload.len() };
+        let mut padding = 8 - unpadded % 8;
+        if padding < 4 {
+            padding += 8;
+        }
+
+        let mut packet = Vec::with_capacity(5 + payload.len() + padding + 16);
+        put_u32(&mut packet, (1 + payload.len() + padding) as u32);
+        packet.push(padding as u8);
+        packet.extend_from_slice(payload);
+        let mut padding_bytes = [0; 16];
+        OsRng.fill_bytes(&mut padding_bytes[..padding]);
+        packet.extend_from_slice(&padding_bytes[..padding]);
+        if let Some(cipher) = &self.cipher {
+            cipher.seal(self.seq, &mut packet);
+        }
+
+        self.stream.write_all(&packet)?;
+        self.seq = self.seq.wrapping_add(1);
+        Ok(())
+    }
+
+    fn send_unimplemented(&mut self, seq: u32) -> io::Result<()> {
+        let mut unimplemented = vec![MSG_UNIMPLEMENTED];
+        put_u32(&mut unimplemented, seq);
+        self.send(&unimplemented)
+    }
+
+    // The connection is dropped anyway, so a failed send does not matter
+    fn disconnect(&mut self, reason: &str) {
+        let mut disconnect = vec![MSG_DISCONNECT];
+        put_u32(&mut disconnect, DISCONNECT_PROTOCOL_ERROR);
+        put_string(&mut disconnect, reason.as_bytes());
+        put_string(&mut disconnect, b"");
+        let _ = self.send(&disconnect);
+    }
+}
+
+struct PacketReader {
+    reader: BufReader<TcpStream>,
+    cipher: Option<Cipher>,
+    seq: u32,
+}
+
+impl PacketReader {
+    fn new(stream: TcpStream) -> Self {
+        Self { reader: BufReader::new(stream), cipher: None, seq: 0 }
+    }
+
+    // Servers may send other lines before their version, clients are held to the version line
+    fn read_version(&mut self) -> io::Result<Vec<u8>> {
+        let mut line = vec![];
+        (&mut self.reader).take(256).read_until(b'\n', &mut line)?;
+        if !line.starts_with(b"SSH-2.0-") || !line.ends_with(b"\n") {
+            return Err(invalid("client does not speak SSH 2"));
+        }
+        while line.last().is_some_and(|&byte| byte == b'\n' || byte == b'\r') {
+            line.pop();
+        }
+        Ok(line)
+    }
+
+    fn read_packet(&mut self) -> io::Result<Vec<u8>> {
+        let mut length = [0; 4];
+        self.reader.read_exact(&mut length)?;
+        let packet_length = match &self.cipher {
+            Some(cipher) => cipher.open_length(self.seq, length),
+            None => u32::from_be_bytes(length) as usize,
+        };
+        if !(5..=MAX_PACKET_LENGTH).contains(&packet_length) {
+            return Err(invalid("packet length out of range"));
+        }
+
+        let mut packet = length.to_vec();
+        packet.resize(4 + packet_length, 0);
+        self.reader.read_exact(&mut packet[4..])?;
+        if let Some(cipher) = &self.cipher {
+            let mut tag = [0; 16];
+            self.reader.read_exact(&mut tag)?;
+            cipher.open(self.seq, &mut packet, &tag)?;
+        }
+        self.seq = self.seq.wrapping_add(1);
+
+        let padding = packet[4] as usize;
+        if padding + 1 > packet_length {
+            return Err(invalid("padding longer than the packet"));
+        }
+        Ok(packet[5..4 + packet_length - padding].to_vec())
+    }
+
+    // Messages that carry nothing for the server are skipped
+    fn read_message(&mut self) -> io::Result<Vec<u8>> {
+        loop {
+            let message = self.read_packet()?;
+            match message.first() {
+                None => return Err(invalid("empty message")),
+                Some(&MSG_IGNORE | &MSG_DEBUG | &MSG_UNIMPLEMENTED) => { },
+                Some(_) => return Ok(message),
+            }
+        }
+    }
+}
+
+fn put_u32(buffer: &mut Vec<u8>, value: u32) {
+    buffer.extend_from_slice(&value.to_be_bytes());
+}
+
+fn put_string(buffer: &mut Vec<u8>, value: &[u8]) {
+    put_u32(buffer, value.len() as u32);
+    buffer.extend_from_slice(value);
+}
+
+// An unsigned big-endian number, with a zero byte in front when the top bit would make it negative
+fn put_mpint(buffer: &mut Vec<u8>, value: &[u8]) {
+    let start = value.iter().position(|&byte| byte != 0).unwrap_or(value.len());
+    let value = &value[start..];
+    if value.first().is_some_and(|&byte| byte & 0x80 != 0) {
+        put_u32(buffer, value.len() as u32 + 1);
+        buffer.push(0);
+        buffer.extend_from_slice(value);
+    }
+    else {
+        put_string(buffer, value);
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+// Reads the fields of a message in order
+struct Fields<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Fields<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    fn skip(&mut self, length: usize) -> io::Result<&'a [u8]> {
+        if self.data.len() < length {
+            return Err(invalid("message is cut short"));
+        }
+        let (field, rest) = self.data.split_at(length);
+        self.data = rest;
+        Ok(field)
+    }
+
+    fn get_u8(&mut self) -> io::Result<u8> {
+        Ok(self.skip(1)?[0])
+    }
+
+    fn get_bool(&mut self) -> io::Result<bool> {
+        Ok(self.get_u8()? != 0)
+    }
+
+    fn get_u32(&mut self) -> io::Result<u32> {
+        Ok(u32::from_be_bytes(self.skip(4)?.try_into().expect("u32 expect")))
+    }
+
+    fn get_string(&mut self) -> io::Result<&'a [u8]> {
+        let length = self.get_u32()? as usize;
+        self.skip(length)
+    }
+
+    fn get_name_list(&mut self) -> io::Result<Vec<&'a str>> {
+        let list = std::str::from_utf8(self.get_string()?).map_err(|_| invalid("name list is not text"))?;
+        Ok(list.split(',').filter(|name| !name.is_empty()).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{net::TcpListener, thread};
+    use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+
+    struct Client {
+        writer: PacketWriter,
+        reader: PacketReader,
+    }
+
+    impl Client {
+        fn send(&mut self, message: &[u8]) {
+            self.writer.send(message).expect("send expect");
+        }
+
+        fn read(&mut self) -> Vec<u8> {
+            self.reader.read_message().expect("read expect")
+        }
+
+        fn read_data(&mut self) -> Vec<u8> {
+            let message = self.read();
+            let mut fields = Fields::new(&message);
+            assert_eq!(fields.get_u8().expect("type expect"), MSG_CHANNEL_DATA);
+            assert_eq!(fields.get_u32().expect("channel expect"), 7);
+            fields.get_string().expect("data expect").to_vec()
+        }
+    }
+
+    // The client side of the key exchange, written out so it checks the server from the other end
+    fn connect(listener: &TcpListener, host_key: &VerifyingKey) -> Client {
+        let stream = TcpStream::connect(listener.local_addr().expect("address expect")).expect("connect expect");
+        let mut client = Client { writer: PacketWriter::new(stream.try_clone().expect("clone expect")), reader: PacketReader::new(stream) };
+        client.writer.stream.write_all(b"SSH-2.0-test\r\n").expect("version expect");
+        let mut server_version = vec![];
+        client.reader.reader.read_until(b'\n', &mut server_version).expect("version expect");
+        assert_eq!(server_version, b"SSH-2.0-minesweeper_tui\r\n");
+
+        let client_kexinit = get_kexinit(CLIENT_STRICT_KEX);
+        client.send(&client_kexinit);
+        let server_kexinit = client.read();
+        let secret = EphemeralSecret::random_from_rng(OsRng);
+        let client_public = PublicKey::from(&secret);
+        let mut init = vec![MSG_KEX_ECDH_INIT];
+        put_string(&mut init, client_public.as_bytes());
+        client.send(&init);
+
+        let reply = client.read();
+        let mut fields = Fields::new(&reply);
+        assert_eq!(fields.get_u8().expect("type expect"), MSG_KEX_ECDH_REPLY);
+        let host_key_blob = fields.get_string().expect("host key expect");
+        let server_public: [u8; 32] = fields.get_string().expect("key expect").try_into().expect("key expect");
+        let mut signature = Fields::new(fields.get_string().expect("signature expect"));
+        assert_eq!(signature.get_string().expect("algorithm expect"), HOST_KEY_ALGORITHM.as_bytes());
+        assert!(host_key_blob.ends_with(host_key.as_bytes()));
+
+        let mut shared_secret = vec![];
+        put_mpint(&mut shared_secret, secret.diffie_hellman(&PublicKey::from(server_public)).as_bytes());
+        let mut exchange = vec![];
+        for field in [&b"SSH-2.0-test"[..], SERVER_VERSION.as_bytes(), &client_kexinit, &server_kexinit, host_key_blob, client_public.as_bytes(), &server_public] {
+            put_string(&mut exchange, field);
+        }
+        exchange.extend_from_slice(&shared_secret);
+        let hash = Sha256::digest(&exchange);
+        let signature = Signature::from_slice(signature.get_string().expect("signature expect")).expect("signature expect");
+        host_key.verify(&hash, &signature).expect("the server signs the exchange with its host key");
+
+        assert_eq!(client.read(), [MSG_NEWKEYS]);
+        client.reader.cipher = Some(Cipher::new(&derive_key(&shared_secret, &hash, b'D')));
+        client.reader.seq = 0;
+        client.send(&[MSG_NEWKEYS]);
+        client.writer.cipher = Some(Cipher::new(&derive_key(&shared_secret, &hash, b'C')));
+        client.writer.seq = 0;
+        client
+    }
+
+    fn channel_message(kind: u8) -> Vec<u8> {
+        let mut message = vec![kind];
+        put_u32(&mut message, 0);
+        message
+    }
+
+    fn channel_request(kind: &str, want_reply: bool) -> Vec<u8> {
+        let mut request = channel_message(MSG_CHANNEL_REQUEST);
+        put_string(&mut request, kind.as_bytes());
+        request.push(want_reply as u8);
+        request
+    }
+
+    #[test]
+    fn test_session(){
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind expect");
+        let host_key = SigningKey::generate(&mut OsRng);
+        let verifying_key = host_key.verifying_key();
+        let server_listener = listener.try_clone().expect("clone expect");
+        let server = thread::spawn(move || accept(server_listener.accept().expect("accept expect").0, &host_key).expect("session expect"));
+        let mut client = connect(&listener, &verifying_key);
+
+        let mut service = vec![MSG_SERVICE_REQUEST];
+        put_string(&mut service, b"ssh-userauth");
+        client.send(&service);
+        assert_eq!(client.read()[0], MSG_SERVICE_ACCEPT);
+        // Anyone gets in without a password
+        let mut login = vec![MSG_USERAUTH_REQUEST];
+        for field in ["player", "ssh-connection", "none"] {
+            put_string(&mut login, field.as_bytes());
+        }
+        client.send(&login);
+        assert_eq!(client.read(), [MSG_USERAUTH_SUCCESS]);
+
+        // A window of four bytes holds the output back until the client makes room
+        let mut open = vec![MSG_CHANNEL_OPEN];
+        put_string(&mut open, b"session");
+        for field in [7, 4, 32768] {
+            put_u32(&mut open, field);
+        }
+        client.send(&open);
+        assert_eq!(client.read()[0], MSG_CHANNEL_OPEN_CONFIRMATION);
+        let mut pty = channel_request("pty-req", true);
+        put_string(&mut pty, b"xterm");
+        for field in [100, 40, 0, 0] {
+            put_u32(&mut pty, field);
+        }
+        put_string(&mut pty, b"");
+        client.send(&pty);
+        assert_eq!(client.read()[0], MSG_CHANNEL_SUCCESS);
+        client.send(&channel_request("shell", true));
+        assert_eq!(client.read()[0], MSG_CHANNEL_SUCCESS);
+
+        let mut session = server.join().expect("server expect");
+        assert_eq!(session.get_size(), (100, 40));
+        let mut output = session.get_output();
+        let game = thread::spawn(move || {
+            output.write_all(b"Minesweeper").expect("write expect");
+            output.flush().expect("flush expect");
+            output
+        });
+        assert_eq!(client.read_data(), b"Mine");
+
+        let mut adjust = channel_message(MSG_CHANNEL_WINDOW_ADJUST);
+        put_u32(&mut adjust, 1000);
+        client.send(&adjust);
+        let mut data = channel_message(MSG_CHANNEL_DATA);
+        put_string(&mut data, b"q");
+        client.send(&data);
+        assert!(matches!(session.read_event(), SessionEvent::Input(input) if input == b"q"));
+        assert_eq!(client.read_data(), b"sweeper");
+
+        let mut window_change = channel_request("window-change", false);
+        for field in [120, 50, 0, 0] {
+            put_u32(&mut window_change, field);
+        }
+        client.send(&window_change);
+        assert!(matches!(session.read_event(), SessionEvent::Resize(120, 50)));
+
+        // The game ends the session with an exit status, and the client answers the close
+        game.join().expect("game expect").close();
+        assert_eq!(client.read()[0], MSG_CHANNEL_REQUEST);
+        assert_eq!(client.read()[0], MSG_CHANNEL_EOF);
+        assert_eq!(client.read()[0], MSG_CHANNEL_CLOSE);
+        client.send(&channel_message(MSG_CHANNEL_CLOSE));
+        assert!(matches!(session.read_event(), SessionEvent::Closed));
+        session.finish();
+    }
+
+    #[test]
+    fn test_unsupported_client(){
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind expect");
+        let host_key = SigningKey::generate(&mut OsRng);
+        let server_listener = listener.try_clone().expect("clone expect");
+        let server = thread::spawn(move || accept(server_listener.accept().expect("accept expect").0, &host_key).is_err());
+
+        let stream = TcpStream::connect(listener.local_addr().expect("address expect")).expect("connect expect");
+        let mut writer = PacketWriter::new(stream.try_clone().expect("clone expect"));
+        writer.stream.write_all(b"SSH-2.0-test\r\n").expect("version expect");
+        let mut kexinit = vec![MSG_KEXINIT];
+        kexinit.extend_from_slice(&[0; 16]);
+        for list in ["diffie-hellman-group14-sha1", "ssh-rsa", "aes128-ctr", "aes128-ctr", "hmac-sha1", "hmac-sha1", "none", "none", "", ""] {
+            put_string(&mut kexinit, list.as_bytes());
+        }
+        kexinit.extend_from_slice(&[0; 5]);
+        writer.send(&kexinit).expect("send expect");
+        assert!(server.join().expect("server expect"));
+    }
+
+    #[test]
+    fn test_cipher(){
+        let cipher = Cipher::new(&[3; 64]);
+        let mut packet = vec![0, 0, 0, 12, 4, 1, 2, 3, 4, 5, 6, 7, 0, 0, 0, 0];
+        let plain = packet.clone();
+        cipher.seal(5, &mut packet);
+        let tag = packet.split_off(16);
+        assert_ne!(packet, plain);
+        assert_eq!(cipher.open_length(5, packet[..4].try_into().expect("length expect")), 12);
+
+        // A packet that was changed on the way, or is read with the wrong sequence number, is refused
+        let mut tampered = packet.clone();
+        tampered[6] ^= 1;
+        assert!(cipher.open(5, &mut tampered, &tag).is_err());
+        assert!(cipher.open(6, &mut packet.clone(), &tag).is_err());
+        cipher.open(5, &mut packet, &tag).expect("open expect");
+        assert_eq!(packet[4..], plain[4..]);
+    }
+
+    #[test]
+    fn test_mpint(){
+        let mut buffer = vec![];
+        put_mpint(&mut buffer, &[0, 0, 0x7f, 1]);
+        put_mpint(&mut buffer, &[0x80]);
+        put_mpint(&mut buffer, &[0, 0]);
+        assert_eq!(buffer, [0, 0, 0, 2, 0x7f, 1, 0, 0, 0, 2, 0, 0x80, 0, 0, 0, 0]);
+    }
+}
@@ -0,0 +1,38 @@
+use std::time::Duration;
+
+pub struct GameStats {
+    hint_count: u32,
+    time_penalty: Duration,
+    is_ranked: bool,
+}
+
+impl GameStats {
+    pub fn new() -> Self {
+        Self {
+            hint_count: 0,
+            time_penalty: Duration::ZERO,
+            is_ranked: true,
+        }
+    }
+
+    pub fn add_hint(&mut self, penalty: Duration) {
+        self.hint_count += 1;
+        self.time_penalty += penalty;
+    }
+
+    pub fn get_hint_count(&self) -> u32 {
+        self.hint_count
+    }
+
+    pub fn get_time_penalty(&self) -> Duration {
+        self.time_penalty
+    }
+
+    pub fn set_unranked(&mut self) {
+        self.is_ranked = false;
+    }
+
+    pub fn is_ranked(&self) -> bool {
+        self.is_ranked
+    }
+}
@@ -0,0 +1,272 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    input_listener::Direction,
+    neighborhood::Neighborhood,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Grid {
+    #[default]
+    Square,
+    // Rows of hexagons where every odd row is shifted half a cell to the right
+    Hex,
+}
+
+impl Grid {
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            Grid::Square => "square",
+            Grid::Hex => "hex",
+        }
+    }
+}
+
+// Six touching hexagons of a cell, these depend on whether the row is shifted
+const HEX_EVEN_ROW_OFFSETS: [(i16, i16); 6] = [(-1, -1), (0, -1), (-1, 0), (1, 0), (-1, 1), (0, 1)];
+const HEX_ODD_ROW_OFFSETS: [(i16, i16); 6] = [(0, -1), (1, -1), (-1, 0), (1, 0), (0, 1), (1, 1)];
+
+// Offsets of the cells in the layers above and below that touch a cell
+const LAYER_OFFSETS: [(i16, i16); 9] = [
+    (-1, -1), (0, -1), (1, -1),
+    (-1, 0), (0, 0), (1, 0),
+    (-1, 1), (0, 1), (1, 1),
+];
+
+// Shape of a board, cells are stored row by row and then layer by layer whatever the grid
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Topology {
+    grid: Grid,
+    width: usize,
+    height: usize,
+    // Number of layers, boards with more than one are played one layer at a time
+    depth: usize,
+    // The left and right edges and the top and bottom edges are joined into a torus
+    wrap: bool,
+}
+
+impl Topology {
+    pub fn new(grid: Grid, width: usize, height: usize, depth: usize, wrap: bool) -> Self {
+        Self {
+            grid,
+            width,
+            height,
+            depth,
+            wrap,
+        }
+    }
+
+    pub fn get_grid(&self) -> Grid {
+        self.grid
+    }
+
+    pub fn is_wrapping(&self) -> bool {
+        self.wrap
+    }
+
+    pub fn get_width(&self) -> usize {
+        self.width
+    }
+
+    pub fn get_height(&self) -> usize {
+        self.height
+    }
+
+    pub fn get_depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn get_layer_size(&self) -> usize {
+        self.width * self.height
+    }
+
+    pub fn get_cell_count(&self) -> usize {
+        self.width * self.height * self.depth
+    }
+
+    pub fn get_index_from_pos(&self, x: i16, y: i16) -> Option<usize> {
+        self.get_index_from_layer_pos(x, y, 0)
+    }
+
+    pub fn get_index_from_layer_pos(&self, x: i16, y: i16, z: i16) -> Option<usize> {
+        if z < 0 || z >= self.depth as i16 {
+            return None;
+        }
+        let layer_start = z * self.get_layer_size() as i16;
+
+        if self.wrap && self.width > 0 && self.height > 0 {
+            let x = x.rem_euclid(self.width as i16);
+            let y = y.rem_euclid(self.height as i16);
+            return Some((layer_start + x + (y * self.width as i16)) as usize);
+        }
+        if x < 0 || x >= self.width as i16 ||
+            y < 0 || y >= self.height as i16 {
+                return None;
+            }
+
+        Some((layer_start + x + (y * self.width as i16)) as usize)
+    }
+
+    // Position of a cell inside its layer
+    pub fn get_pos_from_index(&self, index: i16) -> (i16, i16) {
+        let index = index % self.get_layer_size().max(1) as i16;
+        let y = index / (self.width as i16);
+        let x = index - (y * (self.width as i16));
+        (x, y)
+    }
+
+    pub fn get_layer_from_index(&self, index: usize) -> usize {
+        index / self.get_layer_size().max(1)
+    }
+
+    pub fn get_neighbors(&self, index: usize, neighborhood: Neighborhood) -> Vec<usize> {
+        let (x, y) = self.get_pos_from_index(index as i16);
+        let z = self.get_layer_from_index(index) as i16;
+        let mut neighbors = vec![];
+        let layer_offsets = self.get_offsets(y, neighborhood).iter().map(|&(dx, dy)| (dx, dy, 0));
+        let other_layer_offsets = [-1, 1].into_iter()
+            .filter(|_| self.depth > 1)
+            .flat_map(|dz| LAYER_OFFSETS.iter().map(move |&(dx, dy)| (dx, dy, dz)));
+        for (dx, dy, dz) in layer_offsets.chain(other_layer_offsets) {
+            // On small wrapping boards an offset can lead back to the cell itself or to a neighbour twice
+            if let Some(neighbor) = self.get_index_from_layer_pos(x + dx, y + dy, z + dz) {
+                if neighbor != index && !neighbors.contains(&neighbor) {
+                    neighbors.push(neighbor);
+                }
+            }
+        }
+        neighbors
+    }
+
+    // Hex boards always count the six touching cells, the neighbourhood rule only applies to squares
+    fn get_offsets(&self, y: i16, neighborhood: Neighborhood) -> &'static [(i16, i16)] {
+        match self.grid {
+            Grid::Square => neighborhood.get_offsets(),
+            Grid::Hex if y % 2 == 0 => &HEX_EVEN_ROW_OFFSETS,
+            Grid::Hex => &HEX_ODD_ROW_OFFSETS,
+        }
+    }
+
+    // The cell reached by moving one step from a cell, left and right run on into the next row
+    // unless the board wraps or has layers, then they stay in the same row
+    pub fn step(&self, index: usize, direction: Direction) -> Option<usize> {
+        let (x, y) = self.get_pos_from_index(index as i16);
+        let z = self.get_layer_from_index(index) as i16;
+        if self.wrap || self.depth > 1 {
+            let (dx, dy, dz) = match direction {
+                Direction::Up => (0, -1, 0),
+                Direction::Down => (0, 1, 0),
+                Direction::Left => (-1, 0, 0),
+                Direction::Right => (1, 0, 0),
+                Direction::LayerUp => (0, 0, -1),
+                Direction::LayerDown => (0, 0, 1),
+                _ => {
+                    let (dx, dy) = self.get_diagonal_offset(y, direction);
+                    (dx, dy, 0)
+                },
+            };
+            return self.get_index_from_layer_pos(x + dx, y + dy, z + dz);
+        }
+
+        let index = index as i16;
+        let width = self.width as i16;
+        let next_index = match direction {
+            Direction::Up => index - width,
+            Direction::Down => index + width,
+            Direction::Left => index - 1,
+            Direction::Right => index + 1,
+            Direction::LayerUp | Direction::LayerDown => return None,
+            Direction::UpLeft | Direction::UpRight | Direction::DownLeft | Direction::DownRight => {
+                let (dx, dy) = self.get_diagonal_offset(y, direction);
+                return self.get_index_from_pos(x + dx, y + dy);
+            },
+        };
+
+        if next_index < 0 || next_index >= self.get_cell_count() as i16 {
+            return None;
+        }
+        Some(next_index as usize)
+    }
+
+    fn get_diagonal_offset(&self, y: i16, direction: Direction) -> (i16, i16) {
+        let dy = if matches!(direction, Direction::UpLeft | Direction::UpRight) { -1 } else { 1 };
+        let is_left = matches!(direction, Direction::UpLeft | Direction::DownLeft);
+        let dx = match (self.grid, is_left) {
+            (Grid::Square, true) => -1,
+            (Grid::Square, false) => 1,
+            // Shifted rows reach the cell straight above or below on the left, others on the right
+            (Grid::Hex, true) => if y % 2 == 0 { -1 } else { 0 },
+            (Grid::Hex, false) => if y % 2 == 0 { 0 } else { 1 },
+        };
+        (dx, dy)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hex_neighbors(){
+        let topology = Topology::new(Grid::Hex, 4, 4, 1, false);
+        assert_eq!(topology.get_neighbors(5, Neighborhood::Moore), vec![1, 2, 4, 6, 9, 10]);
+        assert_eq!(topology.get_neighbors(10, Neighborhood::Moore), vec![5, 6, 9, 11, 13, 14]);
+        assert_eq!(topology.get_neighbors(0, Neighborhood::Moore), vec![1, 4]);
+
+        // Every neighbour relation goes both ways
+        for index in 0..topology.get_cell_count() {
+            for neighbor in topology.get_neighbors(index, Neighborhood::Moore) {
+                assert!(topology.get_neighbors(neighbor, Neighborhood::Moore).contains(&index));
+            }
+        }
+    }
+
+    #[test]
+    fn test_hex_step(){
+        let topology = Topology::new(Grid::Hex, 4, 4, 1, false);
+        assert_eq!(topology.step(5, Direction::UpLeft), Some(1));
+        assert_eq!(topology.step(5, Direction::UpRight), Some(2));
+        assert_eq!(topology.step(5, Direction::DownRight), Some(10));
+        assert_eq!(topology.step(10, Direction::DownLeft), Some(13));
+        assert_eq!(topology.step(8, Direction::UpLeft), None);
+
+        let topology = Topology::new(Grid::Square, 4, 4, 1, false);
+        assert_eq!(topology.step(5, Direction::UpLeft), Some(0));
+        assert_eq!(topology.step(3, Direction::Right), Some(4));
+    }
+
+    #[test]
+    fn test_wrapping(){
+        let topology = Topology::new(Grid::Square, 4, 3, 1, true);
+        for index in 0..topology.get_cell_count() {
+            assert_eq!(topology.get_neighbors(index, Neighborhood::Moore).len(), 8);
+        }
+        assert_eq!(topology.get_neighbors(0, Neighborhood::Moore), vec![11, 8, 9, 3, 1, 7, 4, 5]);
+
+        assert_eq!(topology.step(3, Direction::Right), Some(0));
+        assert_eq!(topology.step(0, Direction::Left), Some(3));
+        assert_eq!(topology.step(1, Direction::Up), Some(9));
+        assert_eq!(topology.step(0, Direction::UpLeft), Some(11));
+
+        // Offsets that wrap onto the same cell only count once
+        let topology = Topology::new(Grid::Square, 2, 2, 1, true);
+        assert_eq!(topology.get_neighbors(0, Neighborhood::Moore), vec![3, 2, 1]);
+    }
+
+    #[test]
+    fn test_layers(){
+        let topology = Topology::new(Grid::Square, 3, 3, 3, false);
+        assert_eq!(topology.get_neighbors(13, Neighborhood::Moore).len(), 26);
+        assert_eq!(topology.get_neighbors(0, Neighborhood::Moore).len(), 7);
+        assert_eq!(topology.get_pos_from_index(14), (2, 1));
+        assert_eq!(topology.get_layer_from_index(14), 1);
+
+        assert_eq!(topology.step(13, Direction::LayerUp), Some(4));
+        assert_eq!(topology.step(13, Direction::LayerDown), Some(22));
+        assert_eq!(topology.step(22, Direction::LayerDown), None);
+        // Moving stays inside the layer
+        assert_eq!(topology.step(11, Direction::Right), None);
+        assert_eq!(topology.step(9, Direction::Up), None);
+    }
+}
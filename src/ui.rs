@@ -1,7 +1,7 @@
 use tui::{
     backend::Backend,
     layout::{Alignment, Constraint, Direction, Layout, Rect},
-    widgets::{Block, Borders, Paragraph, Wrap, Clear},
+    widgets::{Block, BorderType, Borders, Paragraph, Wrap, Clear},
     style::{Style, Color},
     text::{Spans, Span, Text},
     Frame,
@@ -15,8 +15,15 @@ use std::{
 };
 
 use crate::{
-    app::App,
+    app::{App, Difficulty},
     board::Board,
+    coop::Coop,
+    flags::{FlagsGame, PLAYER_COUNT},
+    daily,
+    neighborhood::Neighborhood,
+    race::{PlayerState, Race},
+    topology::Grid,
+    world::World,
 };
 
 pub struct Screen{
@@ -28,9 +35,9 @@ pub trait Draw<B: Backend>{
 }
 
 impl Screen{
-    pub fn new() -> Self{
+    pub fn new(cell_size: u16) -> Self{
         Self {
-            cell_size: 3
+            cell_size
         }
     }
 
@@ -47,35 +54,203 @@ impl Screen{
                 .constraints(
                     [
                         Constraint::Length(3),
-                        Constraint::Min(board.get_board_height() as u16 * self.cell_size),
-                        Constraint::Length(5),
+                        Constraint::Min(board.get_board_height() as u16 * self.cell_size + if board.is_wrapping() { 2 } else { 0 } +
+                                        if app.get_player_two().is_some() { 2 } else { 0 }),
+                        Constraint::Length(3),
+                        Constraint::Length(12),
                     ].as_ref())
                 .margin(1)
                 .split(f.size());
 
-            self.draw_top_menu(f, board, time, chunks[0]);
-            self.draw_board(f, chunks[1], board, debug);
-            self.draw_popup_windows(f, app, chunks[1]);
-            self.draw_bottom_help_bar(f, chunks[2]);
+            if app.get_player_two().is_some() {
+                self.draw_split_screen(f, app, time, chunks[0], chunks[1], debug);
+                self.draw_status_bar(f, app, chunks[2]);
+                self.draw_bottom_help_bar(f, chunks[3], true);
+                return;
+            }
+
+            self.draw_top_menu(f, app, board, time, chunks[0]);
+            // The opponent of a race and the scores of a Flags game are shown next to the board
+            let side_chunks = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Min(0), Constraint::Length(24)])
+                .split(chunks[1]);
+            let board_chunk = match (app.get_race(), app.get_flags()) {
+                (Some(race), _) => {
+                    self.draw_race_panel(f, race, side_chunks[1]);
+                    side_chunks[0]
+                },
+                (None, Some(flags)) => {
+                    self.draw_flags_panel(f, app, flags, side_chunks[1]);
+                    side_chunks[0]
+                },
+                (None, None) => chunks[1],
+            };
+            // Other co-op cursors and claimed Flags mines are outlined in the colour of their player
+            let mut highlights = app.get_coop().map_or_else(Vec::new, |coop| self.get_other_cursors(coop));
+            if let Some(flags) = app.get_flags() {
+                highlights.extend(flags.get_claims().iter().map(|(&index, &player)| (index, self.get_player_color(player as u8))));
+            }
+            match app.get_world() {
+                Some(world) => self.draw_world(f, board_chunk, world, debug),
+                None => self.draw_board(f, board_chunk, board, &highlights, debug),
+            }
+            self.draw_popup_windows(f, app, board_chunk);
+            self.draw_status_bar(f, app, chunks[2]);
+            self.draw_bottom_help_bar(f, chunks[3], false);
         })?;
 
         Ok(())
     }
 
+    // Both players of a split screen race get half of the screen for their menu and board
+    fn draw_split_screen<B: Backend>(&self, frame: &mut Frame<B>, app: &App, time: Duration, menu_chunk: Rect, board_chunk: Rect, debug: bool) {
+        let Some(player_two) = app.get_player_two() else {
+            return;
+        };
+        let halves = [Constraint::Percentage(50), Constraint::Percentage(50)];
+        let menu_chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints(halves.as_ref())
+            .split(menu_chunk);
+        let board_chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints(halves.as_ref())
+            .split(board_chunk);
+
+        for (player, (player_app, time)) in [(app, time), (player_two, player_two.get_game_duration())].into_iter().enumerate() {
+            self.draw_top_menu(frame, player_app, player_app.get_board(), time, menu_chunks[player]);
+            let block = Block::default()
+                .title(format!("Player {}", player + 1))
+                .borders(Borders::ALL)
+                .style(Style::default().fg(self.get_player_color(player as u8)));
+            let inner_chunk = block.inner(board_chunks[player]);
+            frame.render_widget(block, board_chunks[player]);
+            self.draw_board(frame, inner_chunk, player_app.get_board(), &[], debug);
+        }
+
+        match app.get_split_screen_winner() {
+            Some(player) if !app.is_showing_scoreboard() => self.draw_popup_window(frame, board_chunk, format!("Player {} wins", player + 1), true),
+            _ => self.draw_popup_windows(frame, app, board_chunk),
+        }
+    }
+
     fn draw_popup_windows<B: Backend>(&self, frame: &mut Frame<B>, app: &App, chunk: Rect) {
-        if app.is_start_up() {
-            self.draw_popup_window(frame, chunk, "Welcome".to_string()); 
+        if app.is_showing_scoreboard() {
+            self.draw_scoreboard_window(frame, app, chunk);
+        }
+        else if let (Some(flags), true) = (app.get_flags(), app.get_is_victory()) {
+            let text = match flags.get_winner() {
+                Some(player) => format!("{} wins", app.get_flags_player_name(player)),
+                None => "Draw".to_string(),
+            };
+            self.draw_popup_window(frame, chunk, text, true);
+        }
+        else if let Some(race) = app.get_race() {
+            // A race is a single game, so there is no new game to choose
+            if !app.is_playing() {
+                self.draw_popup_window(frame, chunk, self.get_race_text(app, race), false);
+            }
+        }
+        else if app.is_start_up() && app.is_daily_mode() {
+            self.draw_popup_window(frame, chunk, format!("Welcome to the daily challenge\n{}", self.get_rules_text(app)), true);
+        }
+        else if app.is_start_up() && app.get_watch().is_some() {
+            self.draw_popup_window(frame, chunk, "Waiting for the game".to_string(), false);
+        }
+        else if app.is_start_up() {
+            self.draw_popup_window(frame, chunk, format!("Welcome\n{}", self.get_rules_text(app)), true);
         }
         else if app.get_is_game_over() {
-            self.draw_popup_window(frame, chunk, "Game over".to_string()); 
+            self.draw_popup_window(frame, chunk, "Game over".to_string(), app.get_watch().is_none());
         }
         else if app.get_is_victory() {
-            self.draw_popup_window(frame, chunk, "Victory".to_string());
+            self.draw_popup_window(frame, chunk, "Victory".to_string(), app.get_watch().is_none());
         }
     }
 
-    fn draw_popup_window<B: Backend>(&self, frame: &mut Frame<B>, chunk: Rect, text_str: String) {
-        let chunk = self.get_cell_center_chunk(chunk, 30, 6);
+    fn get_race_text(&self, app: &App, race: &Race) -> String {
+        match race.get_winner() {
+            Some(true) if !race.is_connected() => "The opponent left\nYou win the race".to_string(),
+            Some(true) => "You win the race".to_string(),
+            Some(false) => "The opponent wins the race".to_string(),
+            None if app.is_start_up() => format!("Race\n{}\nStarting in {}", self.get_rules_text(app), race.get_countdown().as_secs() + 1),
+            None => "Waiting for the opponent".to_string(),
+        }
+    }
+
+    fn draw_flags_panel<B: Backend>(&self, frame: &mut Frame<B>, app: &App, flags: &FlagsGame, chunk: Rect) {
+        let text_style: Style = self.get_text_style();
+        let mut text: Text = Text::styled(format!("Mines to win: {}", flags.get_mine_count() / 2 + 1), text_style);
+        for player in 0..PLAYER_COUNT {
+            let player_style = Style::default().fg(self.get_player_color(player as u8));
+            let turn = if player == flags.get_turn() && !flags.is_over() { " <" } else { "" };
+            text.extend(Text::styled(format!("{}: {}{}", app.get_flags_player_name(player), flags.get_score(player), turn), player_style));
+            if flags.can_bomb(player) {
+                text.extend(Text::styled("o: Bomb 5x5", player_style));
+            }
+        }
+
+        let block = Block::default()
+            .title("Flags")
+            .borders(Borders::ALL)
+            .style(Style::default().fg(Color::Gray));
+        let paragraph = Paragraph::new(text)
+            .block(block)
+            .alignment(Alignment::Center);
+        frame.render_widget(paragraph, chunk);
+    }
+
+    fn draw_race_panel<B: Backend>(&self, frame: &mut Frame<B>, race: &Race, chunk: Rect) {
+        let opponent = race.get_opponent();
+        let text_style: Style = self.get_text_style();
+        let state = if !race.is_connected() {
+            "Disconnected"
+        }
+        else {
+            match opponent.get_state() {
+                PlayerState::Playing => "Playing",
+                PlayerState::Won => "Finished",
+                PlayerState::Lost => "Lost",
+            }
+        };
+
+        let mut text: Text = Text::styled(format!("Opened: {}%", opponent.get_percent()), text_style);
+        text.extend(Text::styled(format!("Lives: {}", opponent.get_lives()), text_style));
+        text.extend(Text::styled(state, text_style));
+        match race.get_winner() {
+            Some(true) => text.extend(Text::styled("You win", text_style.fg(Color::Yellow))),
+            Some(false) => text.extend(Text::styled("Opponent wins", text_style.fg(Color::Yellow))),
+            None => { },
+        }
+
+        let block = Block::default()
+            .title("Opponent")
+            .borders(Borders::ALL)
+            .style(Style::default().fg(Color::Gray));
+        let paragraph = Paragraph::new(text)
+            .block(block)
+            .alignment(Alignment::Center);
+        frame.render_widget(paragraph, chunk);
+    }
+
+    fn get_rules_text(&self, app: &App) -> String {
+        let board = app.get_board();
+        match board.get_grid() {
+            Grid::Square => {
+                let neighborhood = board.get_neighborhood();
+                format!("Neighbours: {} ({} cells){}{}",
+                        neighborhood.get_name(),
+                        neighborhood.get_offsets().len(),
+                        if board.is_wrapping() { ", wrapping" } else { "" },
+                        if board.is_masked() { ", shaped" } else { "" })
+            },
+            grid => format!("Grid: {} (6 neighbours)", grid.get_name()),
+        }
+    }
+
+    fn draw_popup_window<B: Backend>(&self, frame: &mut Frame<B>, chunk: Rect, text_str: String, show_restart: bool) {
+        let chunk = self.get_cell_center_chunk(chunk, 32, 7);
         let block = Block::default()
             .style(Style::default().fg(Color::Blue).bg(Color::Red))
             .borders(Borders::ALL)
@@ -83,7 +258,9 @@ impl Screen{
 
         let text_style: Style = self.get_text_style();
         let mut text: Text = Text::styled(text_str, text_style);
-        text.extend(self.get_restart_game_text());
+        if show_restart {
+            text.extend(self.get_restart_game_text());
+        }
 
         let paragraph = Paragraph::new(text)
             .block(block)
@@ -93,7 +270,78 @@ impl Screen{
         frame.render_widget(paragraph, chunk);
     }
 
-    fn get_restart_game_text(&self) -> Text{
+    fn draw_scoreboard_window<B: Backend>(&self, frame: &mut Frame<B>, app: &App, chunk: Rect) {
+        let difficulties = [Difficulty::Easy, Difficulty::Medium, Difficulty::Hard];
+        let scoreboard = app.get_scoreboard();
+        let neighborhood = app.get_board().get_neighborhood();
+        let today = daily::get_today();
+        let text_style: Style = self.get_text_style();
+        let title = match neighborhood {
+            Neighborhood::Moore => "Best times".to_string(),
+            _ => format!("Best times ({} neighbours)", neighborhood.get_name()),
+        };
+        let mut text: Text = Text::styled(title, text_style.fg(Color::Yellow));
+
+        for difficulty in difficulties {
+            let times: Vec<String> = scoreboard.get_best_scores(difficulty, neighborhood, 3).iter()
+                .map(|score| format!("{:.0}s ({})", score.get_time(), score.get_date()))
+                .collect();
+            text.extend(Text::styled(format!("{}: {}", difficulty.get_name(), times.join(", ")), text_style));
+        }
+
+        text.extend(Text::styled("Time attack", text_style.fg(Color::Yellow)));
+        for difficulty in difficulties {
+            let times: Vec<String> = scoreboard.get_best_time_attack_scores(difficulty, neighborhood, 3).iter()
+                .map(|score| format!("{:.0}s ({})", score.get_time(), score.get_date()))
+                .collect();
+            text.extend(Text::styled(format!("{}: {}", difficulty.get_name(), times.join(", ")), text_style));
+        }
+
+        text.extend(Text::styled(format!("Daily {}", today), text_style.fg(Color::Yellow)));
+        for difficulty in difficulties {
+            let results: Vec<String> = scoreboard.get_daily_results(&today, difficulty, neighborhood).iter()
+                .map(|result| format!("{}{}",
+                                      if result.is_victory() { format!("{:.0}s", result.get_time()) } else { "lost".to_string() },
+                                      if result.is_retry() { " (retry)" } else { "" }))
+                .collect();
+            text.extend(Text::styled(format!("{}: {}", difficulty.get_name(), results.join(", ")), text_style));
+        }
+
+        #[cfg(feature = "leaderboard")]
+        if let Some(leaderboard) = app.get_leaderboard() {
+            text.extend(Text::styled("Team leaderboard", text_style.fg(Color::Yellow)));
+            for difficulty in difficulties {
+                let times: Vec<String> = leaderboard.get_top_scores(difficulty, 3).iter()
+                    .map(|entry| format!("{:.0}s ({})", entry.get_time(), entry.get_name()))
+                    .collect();
+                text.extend(Text::styled(format!("{}: {}", difficulty.get_name(), times.join(", ")), text_style));
+            }
+            match (leaderboard.get_error(), leaderboard.get_pending_count()) {
+                (Some(_), pending) => text.extend(Text::styled(format!("Offline, {} results waiting to be sent", pending), text_style.fg(Color::Red))),
+                (None, 0) => { },
+                (None, pending) => text.extend(Text::styled(format!("{} results waiting to be sent", pending), text_style)),
+            }
+        }
+
+        let block = Block::default()
+            .title("Scoreboard")
+            .borders(Borders::ALL)
+            .style(Style::default().fg(Color::Gray));
+        let paragraph = Paragraph::new(text)
+            .block(block)
+            .alignment(Alignment::Center)
+            .wrap(Wrap { trim: true });
+
+        #[cfg(feature = "leaderboard")]
+        let height = if app.get_leaderboard().is_some() { 19 } else { 14 };
+        #[cfg(not(feature = "leaderboard"))]
+        let height = 14;
+        let chunk = self.get_cell_center_chunk(chunk, 60, height);
+        frame.render_widget(Clear, chunk);
+        frame.render_widget(paragraph, chunk);
+    }
+
+    fn get_restart_game_text(&self) -> Text<'_>{
         let key_bindings = BTreeMap::from([
             ("e", "Easy"),
             ("m", "Medium"),
@@ -112,39 +360,81 @@ impl Screen{
         text
     }
 
-    fn draw_top_menu<B: Backend>(&self, frame: &mut Frame<B>, board: &Board, time: Duration, root_chunk: Rect){
-        let mine_count = board.get_bomb_count();
+    fn draw_top_menu<B: Backend>(&self, frame: &mut Frame<B>, app: &App, board: &Board, time: Duration, root_chunk: Rect){
+        let mine_count = board.get_bomb_count() - board.get_exploded_count();
         let flag_count = board.get_flag_count();
         let text_style = self.get_text_style();
 
         // Create the constraints
-        let mut constraints = vec![];
-        constraints.push(Constraint::Percentage(100));
+        let constraints = vec![Constraint::Percentage(100)];
 
         let chunks = Layout::default()
             .direction(Direction::Horizontal)
-            .constraints(constraints.as_ref())
+            .constraints(constraints.as_slice())
             .split(root_chunk);
 
         let block = Block::default()
             .borders(Borders::ALL)
             .style(Style::default().fg(Color::Gray));
 
-        let span_vec = vec![
+        let mut span_vec = vec![
+            match app.get_world() {
+                Some(world) => Span::styled(format!("Score: {}", world.get_score()), text_style),
+                None => Span::styled(
+                    format!("# mines: {}", mine_count as i16 - flag_count as i16),
+                    text_style
+                ),
+            },
             Span::styled(
-                format!("# mines: {}", mine_count as i16 - flag_count as i16),
+                " - ",
                 text_style
             ),
+            match app.get_time_left() {
+                Some(time_left) => Span::styled(
+                    format!("Time left: {}", time_left.as_secs()),
+                    if time_left <= Duration::from_secs(10) { text_style.fg(Color::Red) } else { text_style }
+                ),
+                None => Span::styled(
+                    format!("Time: {}", time.as_secs()),
+                    text_style
+                ),
+            },
             Span::styled(
                 " - ",
                 text_style
             ),
             Span::styled(
-                format!("Time: {}", time.as_secs().to_string()),
+                format!("Hints: {}", app.get_stats().get_hint_count()),
                 text_style
             ),
         ];
 
+        if board.get_depth() > 1 {
+            span_vec.push(Span::styled(format!(" - Layer: {}/{}", board.get_selected_layer() + 1, board.get_depth()), text_style));
+        }
+        if let Some(coop) = app.get_coop() {
+            let player_style = Style::default().fg(self.get_player_color(coop.get_player()));
+            span_vec.push(Span::styled(format!(" - Player {} of {}", coop.get_player() + 1, coop.get_cursors().len().max(1)), player_style));
+            if !coop.is_connected() {
+                span_vec.push(Span::styled(" - Disconnected", Style::default().fg(Color::Red)));
+            }
+        }
+        if let Some(broadcast) = app.get_broadcast() {
+            span_vec.push(Span::styled(format!(" - {} watching", broadcast.get_viewer_count()), text_style));
+        }
+        if let Some(watch) = app.get_watch() {
+            span_vec.push(Span::styled(format!(" - Watching, {}s behind", watch.get_delay().as_secs()), text_style));
+        }
+        if let Some(lives) = app.get_lives() {
+            span_vec.push(Span::styled(format!(" - Lives: {}", lives), text_style));
+        }
+        if let Some(date) = app.get_daily_date() {
+            span_vec.push(Span::styled(format!(" - Daily {}", date), text_style));
+        }
+        if !app.get_stats().is_ranked() {
+            span_vec.push(Span::styled(" - Unranked", Style::default().fg(Color::Yellow)));
+        }
+
         let spans = Spans::from(span_vec);
         let paragraph = Paragraph::new(spans)
             .block(block)
@@ -154,12 +444,40 @@ impl Screen{
         frame.render_widget(paragraph, chunks[0]);
     }
 
-    fn draw_bottom_help_bar<B: Backend>(&self, frame: &mut Frame<B>, chunk: Rect) {
-        let key_bindings = BTreeMap::from([
+    fn draw_status_bar<B: Backend>(&self, frame: &mut Frame<B>, app: &App, chunk: Rect) {
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .style(Style::default().fg(Color::Gray));
+
+        let paragraph = Paragraph::new(Span::styled(app.get_status(), self.get_text_style()))
+            .block(block)
+            .alignment(Alignment::Center)
+            .wrap(Wrap { trim: true });
+        frame.render_widget(paragraph, chunk);
+    }
+
+    fn draw_bottom_help_bar<B: Backend>(&self, frame: &mut Frame<B>, chunk: Rect, is_split_screen: bool) {
+        // Player one plays on the left of the keyboard, player two on the arrows
+        let split_screen_key_bindings = BTreeMap::from([
+            ("q", "Quit"),
+            ("wasd / Arrows", "Move"),
+            ("Space / Enter", "Select cell"),
+            ("f / .", "Toggle flag"),
+            ("c / ,", "Open around number"),
+            ("l", "Scoreboard"),
+        ]);
+        let key_bindings = if is_split_screen { split_screen_key_bindings } else { BTreeMap::from([
             ("q", "Quit"),
             ("f", "Toggle flag"),
+            ("[ ]", "Change layer"),
+            ("c", "Open around number"),
+            ("o", "Bomb (Flags)"),
+            ("i", "Hint"),
+            ("p", "Mine probabilities"),
+            ("x", "Export board"),
+            ("l", "Scoreboard"),
             ("Enter", "Select cell")
-        ]);
+        ]) };
 
         let block = Block::default()
             .borders(Borders::ALL)
@@ -180,17 +498,69 @@ impl Screen{
         frame.render_widget(paragraph, chunk);
     }
 
-    fn draw_board<B: Backend>(&self, frame: &mut Frame<B>, chunk: Rect, board: &Board, debug: bool) {
+    // Cursors of the other co-op players with their colours
+    fn get_other_cursors(&self, coop: &Coop) -> Vec<(usize, Color)> {
+        coop.get_cursors().iter()
+            .filter(|(&player, _)| player != coop.get_player())
+            .map(|(&player, &index)| (index, self.get_player_color(player)))
+            .collect()
+    }
+
+    fn get_player_color(&self, player: u8) -> Color {
+        const PLAYER_COLORS: [Color; 6] = [Color::Yellow, Color::Magenta, Color::Green, Color::LightRed, Color::LightBlue, Color::White];
+        PLAYER_COLORS[player as usize % PLAYER_COLORS.len()]
+    }
+
+    fn draw_board<B: Backend>(&self, frame: &mut Frame<B>, chunk: Rect, board: &Board, highlights: &[(usize, Color)], debug: bool) {
         // Create the vertical constraints
-        let width = board.get_board_width() as u16 * self.cell_size;
+        let width = board.get_board_width() as u16 * self.cell_size + self.get_row_shift(board, 1);
         let height = board.get_board_height() as u16 * self.cell_size;
-        let center_chunk = self.get_cell_center_chunk(chunk, width, height);
-        self.draw_cells(frame, board, center_chunk, debug);
+        if !board.is_wrapping() {
+            let center_chunk = self.get_cell_center_chunk(chunk, width, height);
+            self.draw_cells(frame, board, center_chunk, highlights, debug);
+            return;
+        }
+
+        // A double border shows that the opposite edges of the board are joined
+        let center_chunk = self.get_cell_center_chunk(chunk, width + 2, height + 2);
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .border_type(BorderType::Double)
+            .border_style(Style::default().fg(Color::LightBlue));
+        let inner_chunk = block.inner(center_chunk);
+        frame.render_widget(block, center_chunk);
+        self.draw_cells(frame, board, inner_chunk, highlights, debug);
+    }
+
+    // Shows the part of the endless board around the cursor that fits on the screen
+    fn draw_world<B: Backend>(&self, frame: &mut Frame<B>, chunk: Rect, world: &World, debug: bool) {
+        let columns = (chunk.width / self.cell_size) as i32;
+        let rows = (chunk.height / self.cell_size) as i32;
+        let (cursor_x, cursor_y) = world.get_cursor();
+        let (left, top) = (cursor_x - columns / 2, cursor_y - rows / 2);
+
+        let center_chunk = self.get_cell_center_chunk(chunk, columns as u16 * self.cell_size, rows as u16 * self.cell_size);
+        let row_chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(vec![Constraint::Length(self.cell_size); rows as usize])
+            .split(center_chunk);
+
+        for (y, row_chunk) in (top..).zip(row_chunks) {
+            let cell_chunks = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints(vec![Constraint::Length(self.cell_size); columns as usize])
+                .split(row_chunk);
+            for (x, cell_chunk) in (left..).zip(cell_chunks) {
+                if let Some(cell) = world.get_cell(x, y) {
+                    cell.draw(frame, cell_chunk, debug);
+                }
+            }
+        }
     }
 
     fn get_cell_center_chunk(&self, chunk: Rect, width: u16, height: u16) -> Rect {
-        let blank_width = (chunk.width - width) / 2;
-        let blank_height = (chunk.height - height) / 2;
+        let blank_width = chunk.width.saturating_sub(width) / 2;
+        let blank_height = chunk.height.saturating_sub(height) / 2;
 
         let vertical_chunks = Layout::default()
             .direction(Direction::Vertical)
@@ -211,7 +581,7 @@ impl Screen{
             .split(vertical_chunks[1])[1]
     }
 
-    fn draw_cells<B: Backend>(&self, frame: &mut Frame<B>, board: &Board, root_chunk: Rect, debug: bool){
+    fn draw_cells<B: Backend>(&self, frame: &mut Frame<B>, board: &Board, root_chunk: Rect, highlights: &[(usize, Color)], debug: bool){
         let mut constraints = vec![];
         let mut i: usize = 0;
         while i < board.get_board_height() {
@@ -221,20 +591,23 @@ impl Screen{
 
         let chunks = Layout::default()
             .direction(Direction::Vertical)
-            .constraints(constraints.as_ref())
+            .constraints(constraints.as_slice())
             .margin(0)
             .split(root_chunk);
 
-        let mut cell_index: usize = 0;
-        for chunk in chunks{
-            self.draw_horizontal_cells(frame, board, chunk, &mut cell_index, debug);
+        // Layered boards only show the layer of the cursor
+        let mut cell_index: usize = board.get_selected_layer() * board.get_layer_size();
+        for chunk in chunks.into_iter() {
+            self.draw_horizontal_cells(frame, board, chunk, &mut cell_index, highlights, debug);
         }
     }
 
-    fn draw_horizontal_cells<B: Backend>(&self, frame: &mut Frame<B>, board: &Board, root_chunk: Rect, cell_index: &mut usize, debug: bool){
+    fn draw_horizontal_cells<B: Backend>(&self, frame: &mut Frame<B>, board: &Board, root_chunk: Rect, cell_index: &mut usize, highlights: &[(usize, Color)], debug: bool){
         let board_width = board.get_board_width();
-        // Create the constraints
-        let mut constraints = vec![];
+        let (_, row) = board.get_pos_from_index(*cell_index as i16);
+        let row_shift = self.get_row_shift(board, row as usize);
+        // Create the constraints, shifted rows start with an empty half cell
+        let mut constraints = vec![Constraint::Length(row_shift)];
         let mut i: usize = 0;
         while i < board_width {
             constraints.push(Constraint::Length(self.cell_size));
@@ -243,16 +616,56 @@ impl Screen{
 
         let chunks = Layout::default()
             .direction(Direction::Horizontal)
-            .constraints(constraints.as_ref())
+            .constraints(constraints.as_slice())
             .margin(0)
             .split(root_chunk);
 
-        for chunk in chunks {
+        for chunk in chunks.into_iter().skip(1) {
             board.get_cells()[*cell_index].draw(frame, chunk, debug);
+            self.draw_ghost_hint(frame, board, chunk, *cell_index);
+            if let Some((_, color)) = highlights.iter().find(|(index, _)| index == cell_index) {
+                let block = Block::default()
+                    .borders(Borders::ALL)
+                    .border_type(BorderType::Thick)
+                    .border_style(Style::default().fg(*color));
+                frame.render_widget(block, chunk);
+            }
             *cell_index += 1;
         }
     }
 
+    // Closed cells show the revealed numbers straight above and below them in the other layers
+    fn draw_ghost_hint<B: Backend>(&self, frame: &mut Frame<B>, board: &Board, chunk: Rect, index: usize) {
+        let cells = board.get_cells();
+        if board.get_depth() == 1 || cells[index].is_open() || cells[index].is_flagged() || cells[index].is_disabled() {
+            return;
+        }
+
+        let layer_size = board.get_layer_size();
+        let ghosts: Vec<String> = [index.checked_sub(layer_size), Some(index + layer_size)].into_iter()
+            .flatten()
+            .filter(|&other| other < cells.len() && cells[other].is_open() && !cells[other].is_bomb())
+            .map(|other| cells[other].get_value().to_string())
+            .collect();
+        if ghosts.is_empty() {
+            return;
+        }
+
+        let inner_chunk = Block::default().borders(Borders::ALL).inner(chunk);
+        let text = if ghosts.join("/").len() <= inner_chunk.width as usize { ghosts.join("/") } else { ghosts[0].clone() };
+        let paragraph = Paragraph::new(Span::styled(text, Style::default().fg(Color::DarkGray)))
+            .alignment(Alignment::Center);
+        frame.render_widget(paragraph, inner_chunk);
+    }
+
+    // Odd rows of a hex board are drawn half a cell to the right, so every cell touches its six neighbours
+    fn get_row_shift(&self, board: &Board, row: usize) -> u16 {
+        match board.get_grid() {
+            Grid::Hex if row % 2 == 1 => self.cell_size / 2,
+            _ => 0,
+        }
+    }
+
     fn get_text_style(&self) -> Style {
         Style::default().fg(Color::Cyan)
     }
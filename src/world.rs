@@ -0,0 +1,387 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fs,
+    io,
+    path::Path,
+};
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app::Difficulty,
+    cell::Cell,
+    input_listener::Direction,
+    neighborhood::Neighborhood,
+};
+
+// Width and height of a chunk in cells
+const CHUNK_SIZE: i32 = 16;
+// Chunks around the cursor that are generated before the cursor gets there
+const GENERATE_RADIUS: i32 = 3;
+// Most cells one open can flood, empty regions are finite but can get large
+const MAX_FLOOD_CELLS: usize = 100_000;
+
+pub fn get_mine_density(difficulty: Difficulty) -> f64 {
+    match difficulty {
+        Difficulty::Easy => 0.12,
+        Difficulty::Medium => 0.16,
+        Difficulty::Hard => 0.20,
+    }
+}
+
+// Only the progress is saved, the mines are generated again from the seed
+#[derive(Serialize, Deserialize)]
+struct WorldData {
+    seed: u64,
+    density: f64,
+    #[serde(default)]
+    neighborhood: Neighborhood,
+    cursor: (i32, i32),
+    exploded: bool,
+    chunks: Vec<ChunkData>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ChunkData {
+    x: i32,
+    y: i32,
+    open: Vec<u16>,
+    flagged: Vec<u16>,
+}
+
+// An endless board, generated in seeded chunks as the cursor explores it
+pub struct World {
+    seed: u64,
+    density: f64,
+    neighborhood: Neighborhood,
+    chunks: HashMap<(i32, i32), Vec<Cell>>,
+    cursor: (i32, i32),
+    open_safe_count: usize,
+    exploded: bool,
+}
+
+impl World {
+    pub fn new(seed: u64, density: f64, neighborhood: Neighborhood) -> Self {
+        let mut world = Self {
+            seed,
+            density,
+            neighborhood,
+            chunks: HashMap::new(),
+            cursor: (0, 0),
+            open_safe_count: 0,
+            exploded: false,
+        };
+        world.generate_around_cursor();
+        world.get_cell_mut(0, 0).set_is_selected(true);
+        world
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let data: WorldData = serde_json::from_str(&fs::read_to_string(path)?)?;
+        let mut world = Self::new(data.seed, data.density, data.neighborhood);
+        for chunk in data.chunks.iter() {
+            for &index in chunk.open.iter() {
+                let (x, y) = get_cell_pos((chunk.x, chunk.y), index as i32);
+                let cell = world.get_cell_mut(x, y);
+                cell.open();
+                if !cell.is_bomb() {
+                    world.open_safe_count += 1;
+                }
+            }
+            for &index in chunk.flagged.iter() {
+                let (x, y) = get_cell_pos((chunk.x, chunk.y), index as i32);
+                world.get_cell_mut(x, y).cycle_flag(1);
+            }
+        }
+
+        world.get_cell_mut(0, 0).set_is_selected(false);
+        world.cursor = data.cursor;
+        world.generate_around_cursor();
+        world.get_cell_mut(data.cursor.0, data.cursor.1).set_is_selected(true);
+        world.exploded = data.exploded;
+        Ok(world)
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut chunks: Vec<ChunkData> = vec![];
+        for (&(x, y), cells) in self.chunks.iter() {
+            let get_indices = |filter: fn(&Cell) -> bool| -> Vec<u16> {
+                cells.iter()
+                    .enumerate()
+                    .filter(|(_, cell)| filter(cell))
+                    .map(|(i, _)| i as u16)
+                    .collect()
+            };
+            let chunk = ChunkData { x, y, open: get_indices(Cell::is_open), flagged: get_indices(Cell::is_flagged) };
+            if !chunk.open.is_empty() || !chunk.flagged.is_empty() {
+                chunks.push(chunk);
+            }
+        }
+        chunks.sort_by_key(|chunk| (chunk.y, chunk.x));
+
+        let data = WorldData {
+            seed: self.seed,
+            density: self.density,
+            neighborhood: self.neighborhood,
+            cursor: self.cursor,
+            exploded: self.exploded,
+            chunks,
+        };
+        fs::write(path, serde_json::to_string(&data)?)
+    }
+
+    pub fn get_cursor(&self) -> (i32, i32) {
+        self.cursor
+    }
+
+    // Safe cells opened, the game ends at the first mine
+    pub fn get_score(&self) -> usize {
+        self.open_safe_count
+    }
+
+    pub fn is_exploded(&self) -> bool {
+        self.exploded
+    }
+
+    // Cells of chunks that have not been generated yet are None
+    pub fn get_cell(&self, x: i32, y: i32) -> Option<&Cell> {
+        let (chunk_pos, index) = get_chunk_pos(x, y);
+        self.chunks.get(&chunk_pos).map(|cells| &cells[index])
+    }
+
+    fn get_cell_mut(&mut self, x: i32, y: i32) -> &mut Cell {
+        let (chunk_pos, index) = get_chunk_pos(x, y);
+        if !self.chunks.contains_key(&chunk_pos) {
+            self.generate_chunk(chunk_pos);
+        }
+        &mut self.chunks.get_mut(&chunk_pos).expect("chunk expect")[index]
+    }
+
+    pub fn move_cursor(&mut self, direction: Direction) {
+        let (dx, dy) = match direction {
+            Direction::Up => (0, -1),
+            Direction::Down => (0, 1),
+            Direction::Left => (-1, 0),
+            Direction::Right => (1, 0),
+            Direction::UpLeft => (-1, -1),
+            Direction::UpRight => (1, -1),
+            Direction::DownLeft => (-1, 1),
+            Direction::DownRight => (1, 1),
+            // The endless board is flat
+            Direction::LayerUp | Direction::LayerDown => (0, 0),
+        };
+
+        let (x, y) = self.cursor;
+        self.get_cell_mut(x, y).set_is_selected(false);
+        self.cursor = (x + dx, y + dy);
+        self.generate_around_cursor();
+        self.get_cell_mut(x + dx, y + dy).set_is_selected(true);
+    }
+
+    pub fn toggle_flag(&mut self) {
+        let (x, y) = self.cursor;
+        self.get_cell_mut(x, y).cycle_flag(1);
+    }
+
+    // Returns true when a mine was opened
+    pub fn open(&mut self) -> bool {
+        let (x, y) = self.cursor;
+        self.open_cell(x, y)
+    }
+
+    // Open the unflagged neighbours of an open number when all its mines are flagged
+    pub fn chord(&mut self) -> bool {
+        let (x, y) = self.cursor;
+        let cell = self.get_cell_mut(x, y);
+        if !cell.is_open() || cell.is_bomb() || cell.get_value() == 0 {
+            return false;
+        }
+
+        let value = cell.get_value();
+        let flag_count = get_neighbors(self.neighborhood, x, y)
+            .filter(|&(x, y)| self.get_cell_mut(x, y).is_flagged())
+            .count();
+        if flag_count as i16 != value {
+            return false;
+        }
+
+        let mut exploded = false;
+        for (x, y) in get_neighbors(self.neighborhood, x, y) {
+            if !self.get_cell_mut(x, y).is_flagged() {
+                exploded |= self.open_cell(x, y);
+            }
+        }
+        exploded
+    }
+
+    fn open_cell(&mut self, x: i32, y: i32) -> bool {
+        let cell = self.get_cell_mut(x, y);
+        if cell.is_open() || cell.is_flagged() {
+            return false;
+        }
+        if cell.is_bomb() {
+            cell.open();
+            self.exploded = true;
+            return true;
+        }
+
+        let mut queue: VecDeque<(i32, i32)> = VecDeque::from([(x, y)]);
+        let mut opened_count = 0;
+        while let Some((x, y)) = queue.pop_front() {
+            let cell = self.get_cell_mut(x, y);
+            if cell.is_open() || cell.is_bomb() || opened_count >= MAX_FLOOD_CELLS {
+                continue;
+            }
+
+            cell.open();
+            opened_count += 1;
+            if cell.get_value() == 0 {
+                queue.extend(get_neighbors(self.neighborhood, x, y));
+            }
+        }
+        self.open_safe_count += opened_count;
+        false
+    }
+
+    fn generate_around_cursor(&mut self) {
+        let ((chunk_x, chunk_y), _) = get_chunk_pos(self.cursor.0, self.cursor.1);
+        for y in (chunk_y - GENERATE_RADIUS)..=(chunk_y + GENERATE_RADIUS) {
+            for x in (chunk_x - GENERATE_RADIUS)..=(chunk_x + GENERATE_RADIUS) {
+                if !self.chunks.contains_key(&(x, y)) {
+                    self.generate_chunk((x, y));
+                }
+            }
+        }
+    }
+
+    fn generate_chunk(&mut self, chunk_pos: (i32, i32)) {
+        let mut cells: Vec<Cell> = (0..CHUNK_SIZE * CHUNK_SIZE).map(|_| Cell::new()).collect();
+        let mines = self.get_chunk_mines(chunk_pos);
+        for (i, cell) in cells.iter_mut().enumerate() {
+            if mines[i] {
+                cell.add_mine();
+            }
+        }
+
+        // Numbers on the border count the mines of the chunks around, so those are generated too
+        let mut neighbor_mines: HashMap<(i32, i32), Vec<bool>> = HashMap::new();
+        for (i, cell) in cells.iter_mut().enumerate() {
+            let (x, y) = get_cell_pos(chunk_pos, i as i32);
+            for (x, y) in get_neighbors(self.neighborhood, x, y) {
+                let (neighbor_chunk, index) = get_chunk_pos(x, y);
+                let is_mine = if neighbor_chunk == chunk_pos {
+                    mines[index]
+                }
+                else {
+                    neighbor_mines.entry(neighbor_chunk)
+                        .or_insert_with(|| self.get_chunk_mines(neighbor_chunk))[index]
+                };
+                if is_mine {
+                    cell.increase_value(1);
+                }
+            }
+        }
+
+        self.chunks.insert(chunk_pos, cells);
+    }
+
+    // Every chunk has its own seed, so it comes out the same whatever order the world is explored in
+    fn get_chunk_mines(&self, chunk_pos: (i32, i32)) -> Vec<bool> {
+        let mut hash: u64 = self.seed ^ 0xcbf29ce484222325;
+        for value in [chunk_pos.0, chunk_pos.1] {
+            for byte in value.to_le_bytes() {
+                hash ^= byte as u64;
+                hash = hash.wrapping_mul(0x100000001b3);
+            }
+        }
+
+        let mut rng = StdRng::seed_from_u64(hash);
+        (0..CHUNK_SIZE * CHUNK_SIZE)
+            .map(|i| {
+                let is_mine = rng.gen_bool(self.density);
+                // The cells around the start are kept free so the first open is safe
+                let (x, y) = get_cell_pos(chunk_pos, i);
+                is_mine && (x.abs() > 1 || y.abs() > 1)
+            })
+            .collect()
+    }
+}
+
+fn get_neighbors(neighborhood: Neighborhood, x: i32, y: i32) -> impl Iterator<Item = (i32, i32)> {
+    neighborhood.get_offsets().iter().map(move |&(dx, dy)| (x + dx as i32, y + dy as i32))
+}
+
+fn get_chunk_pos(x: i32, y: i32) -> ((i32, i32), usize) {
+    let chunk_pos = (x.div_euclid(CHUNK_SIZE), y.div_euclid(CHUNK_SIZE));
+    let index = x.rem_euclid(CHUNK_SIZE) + y.rem_euclid(CHUNK_SIZE) * CHUNK_SIZE;
+    (chunk_pos, index as usize)
+}
+
+fn get_cell_pos(chunk_pos: (i32, i32), index: i32) -> (i32, i32) {
+    (chunk_pos.0 * CHUNK_SIZE + index % CHUNK_SIZE, chunk_pos.1 * CHUNK_SIZE + index / CHUNK_SIZE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunks_are_reproducible(){
+        let mut world = World::new(9, 0.2, Neighborhood::Moore);
+        let mut other_world = World::new(9, 0.2, Neighborhood::Moore);
+        for _ in 0..100 {
+            world.move_cursor(Direction::Right);
+        }
+        for _ in 0..100 {
+            other_world.move_cursor(Direction::Down);
+        }
+
+        // Cells the first world generated from the left and the second from the top agree
+        for x in 90..110 {
+            for y in -5..5 {
+                let cell = world.get_cell(x, y).unwrap();
+                let other_cell = other_world.get_cell_mut(x, y);
+                assert_eq!(cell.is_bomb(), other_cell.is_bomb());
+                assert_eq!(cell.get_value(), other_cell.get_value());
+            }
+        }
+        assert!(!world.get_cell(-1, -1).unwrap().is_bomb());
+    }
+
+    #[test]
+    fn test_numbers_cross_chunks(){
+        for neighborhood in [Neighborhood::Moore, Neighborhood::Knight, Neighborhood::Radius2] {
+            let mut world = World::new(3, 0.2, neighborhood);
+            for x in -20..20 {
+                for y in -20..20 {
+                    let count = get_neighbors(neighborhood, x, y)
+                        .filter(|&(x, y)| world.get_cell_mut(x, y).is_bomb())
+                        .count();
+                    assert_eq!(world.get_cell_mut(x, y).get_value(), count as i16);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_save_and_resume(){
+        let mut world = World::new(5, 0.15, Neighborhood::Moore);
+        assert!(!world.open());
+        let score = world.get_score();
+        assert!(score > 0);
+        world.move_cursor(Direction::Left);
+        world.move_cursor(Direction::Left);
+        world.move_cursor(Direction::Left);
+        world.toggle_flag();
+
+        let path = std::env::temp_dir().join(format!("minesweeper-tui-world-{}.json", std::process::id()));
+        world.save(&path).unwrap();
+        let loaded = World::load(&path).unwrap();
+        fs::remove_file(path).unwrap();
+
+        assert_eq!(loaded.get_score(), score);
+        assert_eq!(loaded.get_cursor(), (-3, 0));
+        assert_eq!(loaded.get_cell(-3, 0).unwrap().is_flagged(), !loaded.get_cell(-3, 0).unwrap().is_open());
+        assert!(loaded.get_cell(0, 0).unwrap().is_open());
+    }
+}